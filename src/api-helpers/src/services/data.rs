@@ -5,6 +5,7 @@ use std::fmt::Debug;
 pub trait QueryStringTrait {
   fn skip(&self) -> Option<i64>;
   fn limit(&self) -> Option<i64>;
+  fn cursor(&self) -> Option<String>;
 }
 
 QueryString! {
@@ -34,6 +34,10 @@ pub struct BaseResponse<T: Serialize> {
   pub count: usize,
   pub skip: i64,
   pub limit: i64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cursor: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub next_cursor: Option<String>,
   pub result: T,
 }
 
@@ -44,12 +48,14 @@ macro_rules! QueryString {
     $pub struct $name {
       pub skip: Option<i64>,
       pub limit: Option<i64>,
+      pub cursor: Option<String>,
       $($fpub $field : $type,)*
     }
 
     impl QueryStringTrait for $name {
       fn skip(&self) -> Option<i64> { self.skip }
       fn limit(&self) -> Option<i64> { self.limit }
+      fn cursor(&self) -> Option<String> { self.cursor.clone() }
     }
   }
 }
@@ -63,6 +69,30 @@ pub fn create_read_response<T: Serialize>(result: Result<Vec<T>>, skip: i64, lim
         result,
         skip: skip,
         limit: limit,
+        cursor: None,
+        next_cursor: None,
+      })
+  })
+  .map_err(|error| error.into())
+}
+
+/// Same as `create_read_response` but for keyset paginated results, where the result comes
+/// with the cursor of the next page
+pub fn create_cursor_read_response<T: Serialize>(
+  result: Result<(Vec<T>, Option<String>)>,
+  cursor: Option<String>,
+  limit: i64,
+) -> Result<HttpResponse, Error> {
+  result
+  .map(|(result, next_cursor)| {
+    HttpResponse::Ok()
+      .json(BaseResponse {
+        count: result.len(),
+        result,
+        skip: 0,
+        limit,
+        cursor,
+        next_cursor,
       })
   })
   .map_err(|error| error.into())
@@ -1,3 +1,4 @@
 pub mod http;
 pub mod data;
+pub mod seat_holds;
 pub mod auth;
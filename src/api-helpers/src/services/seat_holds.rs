@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use eyre::Result;
+use tokio::sync::Mutex;
+use ticketland_core::services::redis::ConnectionPool as RedisPool;
+use ticketland_data::{
+  connection::PostgresConnection,
+  error::Error,
+  models::{cnt::CNT, seat_hold::SeatHold},
+};
+
+/// Seat holds during checkout.
+///
+/// Postgres (`seat_holds`) is the source of truth. When Redis is configured, every held seat also gets a
+/// `SET NX PX` key that expires together with the hold, so contended seats are rejected before touching the
+/// database. If Redis is not configured or not reachable the holds are served by Postgres alone.
+pub struct SeatHolds {
+  postgres: Arc<Mutex<PostgresConnection>>,
+  redis: Option<Arc<RedisPool>>,
+}
+
+impl SeatHolds {
+  pub fn new(postgres: Arc<Mutex<PostgresConnection>>, redis: Option<Arc<RedisPool>>) -> Self {
+    Self {postgres, redis}
+  }
+
+  pub async fn hold(
+    &self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+    seat_indexes: Vec<i32>,
+    ttl_secs: i64,
+    code: Option<String>,
+  ) -> Result<Vec<SeatHold>> {
+    let acquired = self.lock_seats(&event_id, &account_id, &seat_indexes, ttl_secs).await?;
+
+    let result = self.postgres.lock().await
+    .hold_seats(event_id.clone(), ticket_type_index, account_id.clone(), seat_indexes, ttl_secs, code)
+    .await;
+
+    if result.is_err() {
+      self.unlock_seats(&event_id, &account_id, &acquired).await;
+    }
+
+    result
+  }
+
+  pub async fn convert(
+    &self,
+    event_id: String,
+    account_id: String,
+    seat_indexes: Vec<i32>,
+    code: Option<String>,
+  ) -> Result<Vec<CNT>> {
+    let cnts = self.postgres.lock().await
+    .convert_seat_holds(event_id.clone(), account_id.clone(), seat_indexes.clone(), code)
+    .await?;
+
+    self.unlock_seats(&event_id, &account_id, &seat_indexes).await;
+
+    Ok(cnts)
+  }
+
+  pub async fn release(&self, event_id: String, account_id: String, seat_indexes: Vec<i32>) -> Result<()> {
+    let released = self.postgres.lock().await
+    .release_seat_holds(event_id.clone(), account_id.clone(), seat_indexes.clone())
+    .await?;
+
+    if released > 0 {
+      self.unlock_seats(&event_id, &account_id, &seat_indexes).await;
+    }
+
+    Ok(())
+  }
+
+  pub async fn active_holds(&self, event_id: String) -> Result<Vec<SeatHold>> {
+    self.postgres.lock().await.read_active_seat_holds(event_id).await
+  }
+
+  /// Deletes the expired holds from Postgres. Redis keys expire on their own.
+  pub async fn release_expired(&self) -> Result<usize> {
+    self.postgres.lock().await.delete_expired_seat_holds().await
+  }
+
+  /// Acquires the Redis key of every seat. Keys the account already owns are refreshed. If another account owns
+  /// any of them, the keys acquired so far are released and `Error::SeatUnavailable` is returned. Returns the
+  /// seats whose key was newly acquired, which is empty if Redis is not available.
+  ///
+  /// Every step is a single atomic command, so a key is never overwritten once another account holds it.
+  async fn lock_seats(&self, event_id: &str, account_id: &str, seat_indexes: &[i32], ttl_secs: i64) -> Result<Vec<i32>> {
+    let Some(redis) = &self.redis else {return Ok(vec![])};
+    let Ok(mut redis) = redis.connection().await else {return Ok(vec![])};
+    let ttl_millis = (ttl_secs.max(1) * 1000) as usize;
+    let mut acquired = vec![];
+
+    for seat_index in seat_indexes {
+      let key = seat_hold_key(event_id, *seat_index);
+
+      let available = match redis.set_nx_px(&key, account_id, ttl_millis).await {
+        Ok(true) => {
+          acquired.push(*seat_index);
+          true
+        },
+        Ok(false) => match redis.pexpire_if_eq(&key, account_id, ttl_millis).await {
+          Ok(true) => true,
+          // Either another account holds the seat or the key expired in the meantime
+          Ok(false) => match redis.set_nx_px(&key, account_id, ttl_millis).await {
+            Ok(is_set) => {
+              if is_set {
+                acquired.push(*seat_index);
+              }
+
+              is_set
+            },
+            Err(_) => return Ok(acquired),
+          },
+          // Redis stopped responding; Postgres has the final say anyway
+          Err(_) => return Ok(acquired),
+        },
+        Err(_) => return Ok(acquired),
+      };
+
+      if !available {
+        self.unlock_seats(event_id, account_id, &acquired).await;
+        return Err(Error::SeatUnavailable.into())
+      }
+    }
+
+    Ok(acquired)
+  }
+
+  /// Deletes the Redis keys of the seats that are still owned by the account
+  async fn unlock_seats(&self, event_id: &str, account_id: &str, seat_indexes: &[i32]) {
+    if seat_indexes.is_empty() {
+      return
+    }
+
+    let Some(redis) = &self.redis else {return};
+    let Ok(mut redis) = redis.connection().await else {return};
+
+    for seat_index in seat_indexes {
+      // Best effort; the key expires with the hold anyway
+      let _ = redis.delete_if_eq(&seat_hold_key(event_id, *seat_index), account_id).await;
+    }
+  }
+}
+
+fn seat_hold_key(event_id: &str, seat_index: i32) -> String {
+  format!("seat_hold:{}:{}", event_id, seat_index)
+}
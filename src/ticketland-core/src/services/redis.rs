@@ -2,8 +2,25 @@ use deadpool_redis::{Pool, Config, Connection, Runtime};
 use eyre::Result;
 use redis::{
   cmd,
+  Script,
 };
 
+/// Extends the expiry of KEYS[1] to ARGV[2] milliseconds if its value is ARGV[1]
+const PEXPIRE_IF_EQ: &str = "
+  if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+  end
+  return 0
+";
+
+/// Deletes KEYS[1] if its value is ARGV[1]
+const DELETE_IF_EQ: &str = "
+  if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+  end
+  return 0
+";
+
 pub struct ConnectionPool(Pool);
 
 impl ConnectionPool {
@@ -42,6 +59,38 @@ impl Redis {
     .map_err(Into::<_>::into)
   }
 
+  /// Sets the key only if it does not exist yet. Returns false if the key was already set
+  pub async fn set_nx_px(&mut self, key: &str, value: &str, millis: usize) -> Result<bool> {
+    let result: Option<String> = cmd("SET")
+    .arg(&[key, value, "NX", "PX", &millis.to_string()])
+    .query_async(&mut self.0).await?;
+
+    Ok(result.is_some())
+  }
+
+  /// Sets the expiry of the key only if it holds `value`, in a single step. Returns false if the key does not exist
+  /// or holds another value
+  pub async fn pexpire_if_eq(&mut self, key: &str, value: &str, millis: usize) -> Result<bool> {
+    let result: i64 = Script::new(PEXPIRE_IF_EQ)
+    .key(key)
+    .arg(value)
+    .arg(millis)
+    .invoke_async(&mut self.0).await?;
+
+    Ok(result == 1)
+  }
+
+  /// Deletes the key only if it holds `value`, in a single step. Returns false if the key does not exist or holds
+  /// another value
+  pub async fn delete_if_eq(&mut self, key: &str, value: &str) -> Result<bool> {
+    let result: i64 = Script::new(DELETE_IF_EQ)
+    .key(key)
+    .arg(value)
+    .invoke_async(&mut self.0).await?;
+
+    Ok(result == 1)
+  }
+
   pub async fn get(&mut self, key: &str) -> Result<String> {
     cmd("GET")
     .arg(&[key])
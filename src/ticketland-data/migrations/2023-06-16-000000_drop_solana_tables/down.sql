@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+-- The solana tables and their data are not restored
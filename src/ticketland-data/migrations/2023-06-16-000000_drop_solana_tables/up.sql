@@ -0,0 +1,21 @@
+-- Your SQL goes here
+
+-- The sui migration expects the solana tables from create_events to be gone, which was done by hand on the deployed
+-- databases. Drop them here so a fresh database can be migrated; databases that already have the sui tables
+-- are left untouched.
+DO $$
+BEGIN
+  IF to_regclass('ticket_onchain_accounts') IS NOT NULL AND to_regclass('cnts') IS NULL THEN
+    DROP TABLE ticket_onchain_accounts CASCADE;
+    DROP TABLE tickets CASCADE;
+    DROP TABLE sell_listings CASCADE;
+    DROP TABLE buy_listings CASCADE;
+    DROP TABLE metadata_attributes CASCADE;
+    DROP TABLE metadata CASCADE;
+    DROP TABLE seat_ranges CASCADE;
+    DROP TABLE sales CASCADE;
+    DROP TABLE events CASCADE;
+    DROP TABLE ticket_images CASCADE;
+  END IF;
+END
+$$;
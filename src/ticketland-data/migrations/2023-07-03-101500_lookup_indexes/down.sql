@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX events_account_id_idx;
+DROP INDEX events_start_date_idx;
+DROP INDEX events_category_idx;
+DROP INDEX cnts_account_id_idx;
+DROP INDEX cnts_event_id_created_at_idx;
+DROP INDEX ticket_type_nft_details_event_id_idx;
+DROP INDEX ticket_type_nfts_cnt_sui_address_idx;
+DROP INDEX event_nft_details_event_id_idx;
+DROP INDEX listings_event_id_created_at_idx;
+DROP INDEX listings_account_id_idx;
+DROP INDEX listings_cnt_sui_address_idx;
+DROP INDEX offers_event_id_created_at_idx;
+DROP INDEX offers_account_id_idx;
+DROP INDEX canva_accounts_account_id_idx;
+DROP INDEX canva_designs_canva_uid_idx;
+DROP INDEX stripe_accounts_account_id_idx;
+DROP INDEX stripe_customers_account_id_idx;
+DROP INDEX api_clients_account_id_idx;
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+
+-- Primary keys and UNIQUE constraints are already indexed. These cover the foreign keys
+-- and filters used by the repositories in ticketland-data.
+CREATE INDEX events_account_id_idx ON events(account_id);
+CREATE INDEX events_start_date_idx ON events(start_date);
+CREATE INDEX events_category_idx ON events(category);
+
+CREATE INDEX cnts_account_id_idx ON cnts(account_id);
+CREATE INDEX cnts_event_id_created_at_idx ON cnts(event_id, created_at);
+
+CREATE INDEX ticket_type_nft_details_event_id_idx ON ticket_type_nft_details(event_id, ticket_type_index);
+CREATE INDEX ticket_type_nfts_cnt_sui_address_idx ON ticket_type_nfts(cnt_sui_address);
+CREATE INDEX event_nft_details_event_id_idx ON event_nft_details(event_id);
+
+CREATE INDEX listings_event_id_created_at_idx ON listings(event_id, created_at);
+CREATE INDEX listings_account_id_idx ON listings(account_id);
+CREATE INDEX listings_cnt_sui_address_idx ON listings(cnt_sui_address);
+
+CREATE INDEX offers_event_id_created_at_idx ON offers(event_id, created_at);
+CREATE INDEX offers_account_id_idx ON offers(account_id);
+
+CREATE INDEX canva_accounts_account_id_idx ON canva_accounts(account_id);
+CREATE INDEX canva_designs_canva_uid_idx ON canva_designs(canva_uid);
+CREATE INDEX stripe_accounts_account_id_idx ON stripe_accounts(account_id);
+CREATE INDEX stripe_customers_account_id_idx ON stripe_customers(account_id);
+CREATE INDEX api_clients_account_id_idx ON api_clients(account_id);
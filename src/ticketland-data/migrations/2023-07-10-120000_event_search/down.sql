@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX events_search_document_idx;
+DROP FUNCTION event_search_document(VARCHAR, TEXT, VARCHAR);
+DROP FUNCTION event_distance_km(JSONB, FLOAT8, FLOAT8);
+DROP FUNCTION haversine_km(FLOAT8, FLOAT8, FLOAT8, FLOAT8);
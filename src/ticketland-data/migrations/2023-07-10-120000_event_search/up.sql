@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+-- Document used for the full text search over events. It's wrapped in an IMMUTABLE function
+-- so the same expression can be used by the GIN index and by the queries.
+CREATE FUNCTION event_search_document(name VARCHAR, description TEXT, venue VARCHAR) RETURNS tsvector AS $$
+  SELECT setweight(to_tsvector('simple', coalesce(name, '')), 'A')
+    || setweight(to_tsvector('simple', coalesce(venue, '')), 'B')
+    || setweight(to_tsvector('simple', coalesce(description, '')), 'C')
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE INDEX events_search_document_idx ON events USING GIN (event_search_document(name, description, venue));
+
+-- Great-circle distance in km between two points given in degrees
+CREATE FUNCTION haversine_km(lat1 FLOAT8, lon1 FLOAT8, lat2 FLOAT8, lon2 FLOAT8) RETURNS FLOAT8 AS $$
+  SELECT 2 * 6371 * asin(sqrt(
+    power(sin(radians(lat2 - lat1) / 2), 2)
+    + cos(radians(lat1)) * cos(radians(lat2)) * power(sin(radians(lon2 - lon1) / 2), 2)
+  ))
+$$ LANGUAGE sql IMMUTABLE STRICT;
+
+-- Distance in km between the event location JSONB ({ name, latitude, longitude }) and the given point.
+-- NULL if the event has no location.
+CREATE FUNCTION event_distance_km(location JSONB, lat FLOAT8, lon FLOAT8) RETURNS FLOAT8 AS $$
+  SELECT haversine_km((location->>'latitude')::FLOAT8, (location->>'longitude')::FLOAT8, lat, lon)
+$$ LANGUAGE sql IMMUTABLE;
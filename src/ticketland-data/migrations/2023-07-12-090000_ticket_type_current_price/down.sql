@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+DROP FUNCTION ticket_type_current_price(JSONB, TIMESTAMPTZ, TIMESTAMPTZ);
@@ -0,0 +1,35 @@
+-- Your SQL goes here
+
+-- Current price of a ticket type given its `SaleType` JSONB. Prices may have been serialized either
+-- as JSON numbers or strings, hence the `->>` text extraction.
+--
+-- A DutchAuction starts at `start_price` and drops linearly towards `end_price` in discrete steps,
+-- one every `drop_interval` minutes, reaching `end_price` after `curve_length` minutes. Each step is
+-- rounded towards `start_price` (integer division). Must be kept in sync with `SaleType::price_at`.
+CREATE FUNCTION ticket_type_current_price(sale_type JSONB, sale_start_ts TIMESTAMPTZ, at TIMESTAMPTZ) RETURNS NUMERIC AS $$
+  SELECT CASE
+    WHEN sale_type ? 'Free' THEN 0
+    WHEN sale_type ? 'FixedPrice' THEN (sale_type->'FixedPrice'->>'price')::NUMERIC
+    WHEN sale_type ? 'Refundable' THEN (sale_type->'Refundable'->>'price')::NUMERIC
+    WHEN sale_type ? 'DutchAuction' THEN (
+      SELECT CASE
+        WHEN total_drops = 0 THEN
+          CASE WHEN elapsed < curve_length THEN start_price ELSE end_price END
+        ELSE
+          start_price - div((start_price - end_price) * LEAST(floor(elapsed / drop_interval), total_drops), total_drops)
+      END
+      FROM (
+        SELECT
+          (sale_type->'DutchAuction'->>'start_price')::NUMERIC AS start_price,
+          (sale_type->'DutchAuction'->>'end_price')::NUMERIC AS end_price,
+          (sale_type->'DutchAuction'->>'curve_length')::NUMERIC AS curve_length,
+          NULLIF((sale_type->'DutchAuction'->>'drop_interval')::NUMERIC, 0) AS drop_interval,
+          COALESCE(
+            floor((sale_type->'DutchAuction'->>'curve_length')::NUMERIC / NULLIF((sale_type->'DutchAuction'->>'drop_interval')::NUMERIC, 0)),
+            0
+          ) AS total_drops,
+          GREATEST(floor(EXTRACT(EPOCH FROM (at - sale_start_ts)) / 60), 0) AS elapsed
+      ) auction
+    )
+  END
+$$ LANGUAGE sql IMMUTABLE;
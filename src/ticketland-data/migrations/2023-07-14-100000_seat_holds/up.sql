@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE seat_holds (
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  seat_index INT NOT NULL,
+  ticket_type_index SMALLINT NOT NULL,
+  account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+  expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  PRIMARY KEY(event_id, seat_index),
+  FOREIGN KEY(event_id, ticket_type_index) REFERENCES ticket_types(event_id, ticket_type_index) ON DELETE CASCADE
+);
+
+CREATE INDEX seat_holds_account_id_idx ON seat_holds(account_id);
+CREATE INDEX seat_holds_expires_at_idx ON seat_holds(expires_at);
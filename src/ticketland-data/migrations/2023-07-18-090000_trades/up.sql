@@ -0,0 +1,27 @@
+-- Your SQL goes here
+
+CREATE TABLE trades (
+  trade_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  ticket_type_index SMALLINT NOT NULL,
+  cnt_sui_address VARCHAR NOT NULL,
+  listing_id VARCHAR REFERENCES listings(listing_id) ON DELETE SET NULL,
+  offer_id VARCHAR REFERENCES offers(offer_id) ON DELETE SET NULL,
+  seller_account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  buyer_account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  price BIGINT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX trades_event_id_created_at_idx ON trades(event_id, created_at);
+CREATE INDEX trades_seller_account_id_idx ON trades(seller_account_id);
+CREATE INDEX trades_buyer_account_id_idx ON trades(buyer_account_id);
+
+-- Backfill the listings filled so far. The buyer was not recorded, so the current owner of the CNT is used.
+-- Filled offers cannot be backfilled since the seller was not recorded either.
+INSERT INTO trades (event_id, ticket_type_index, cnt_sui_address, listing_id, seller_account_id, buyer_account_id, price, created_at)
+SELECT listings.event_id, cnts.ticket_type_index, listings.cnt_sui_address, listings.listing_id,
+  listings.account_id, cnts.account_id, listings.ask_price, listings.closed_at
+FROM listings
+INNER JOIN cnts ON cnts.cnt_sui_address = listings.cnt_sui_address
+WHERE listings.closed_at IS NOT NULL AND cnts.account_id <> listings.account_id;
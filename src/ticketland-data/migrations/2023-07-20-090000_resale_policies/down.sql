@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE trades
+DROP COLUMN royalty,
+DROP COLUMN seller_proceeds;
+
+DROP TABLE resale_policies;
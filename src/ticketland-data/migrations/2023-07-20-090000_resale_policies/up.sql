@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+CREATE TABLE resale_policies (
+  event_id VARCHAR PRIMARY KEY REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  max_price BIGINT CHECK (max_price >= 0),
+  max_price_percent INTEGER CHECK (max_price_percent >= 0),
+  royalty_bps INTEGER NOT NULL DEFAULT 0 CHECK (royalty_bps BETWEEN 0 AND 10000),
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+ALTER TABLE trades
+ADD COLUMN royalty BIGINT NOT NULL DEFAULT 0,
+ADD COLUMN seller_proceeds BIGINT;
+
+UPDATE trades SET seller_proceeds = price;
+
+ALTER TABLE trades ALTER COLUMN seller_proceeds SET NOT NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE listings DROP COLUMN expires_at;
+ALTER TABLE offers DROP COLUMN expires_at;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+ALTER TABLE listings ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE offers ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX listings_expires_at_idx ON listings(expires_at) WHERE is_open = true AND expires_at IS NOT NULL;
+CREATE INDEX offers_expires_at_idx ON offers(expires_at) WHERE is_open = true AND expires_at IS NOT NULL;
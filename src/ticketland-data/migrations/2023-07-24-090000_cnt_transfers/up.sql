@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+CREATE TABLE cnt_transfers (
+  transfer_id BIGSERIAL PRIMARY KEY,
+  cnt_sui_address VARCHAR(66) NOT NULL REFERENCES cnts(cnt_sui_address) ON DELETE CASCADE,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  sender_account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  recipient_account_id VARCHAR REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  recipient_email VARCHAR,
+  status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'declined', 'cancelled')),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  resolved_at TIMESTAMP WITH TIME ZONE,
+  CHECK (recipient_account_id IS NOT NULL OR recipient_email IS NOT NULL)
+);
+
+-- A CNT can have at most one pending transfer
+CREATE UNIQUE INDEX cnt_transfers_pending_idx ON cnt_transfers(cnt_sui_address) WHERE status = 'pending';
+CREATE INDEX cnt_transfers_sender_account_id_idx ON cnt_transfers(sender_account_id);
+CREATE INDEX cnt_transfers_recipient_account_id_idx ON cnt_transfers(recipient_account_id);
+CREATE INDEX cnt_transfers_recipient_email_idx ON cnt_transfers(recipient_email);
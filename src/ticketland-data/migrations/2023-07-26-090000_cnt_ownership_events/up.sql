@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+-- Append-only. There are no foreign keys so the history outlives the CNT and the accounts involved.
+CREATE TABLE cnt_ownership_events (
+  ownership_event_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL,
+  seat_index INT NOT NULL,
+  cnt_sui_address VARCHAR(66),
+  kind VARCHAR NOT NULL CHECK (kind IN ('issued', 'reassigned', 'sold', 'transferred', 'attended')),
+  from_account_id VARCHAR,
+  to_account_id VARCHAR NOT NULL,
+  trade_id BIGINT,
+  transfer_id BIGINT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX cnt_ownership_events_seat_idx ON cnt_ownership_events(event_id, seat_index, created_at);
+CREATE INDEX cnt_ownership_events_cnt_sui_address_idx ON cnt_ownership_events(cnt_sui_address);
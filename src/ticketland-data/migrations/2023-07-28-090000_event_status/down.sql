@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE events ADD COLUMN draft BOOL;
+UPDATE events SET draft = status = 'draft';
+ALTER TABLE events ALTER COLUMN draft SET NOT NULL;
+ALTER TABLE events DROP COLUMN status;
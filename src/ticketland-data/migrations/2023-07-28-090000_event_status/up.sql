@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+ALTER TABLE events
+ADD COLUMN status VARCHAR NOT NULL DEFAULT 'draft'
+CHECK (status IN ('draft', 'published', 'on_sale', 'paused', 'cancelled', 'postponed', 'completed'));
+
+-- Committed events could be sold until now, so they keep doing so unless they are over
+UPDATE events
+SET status = CASE
+  WHEN draft THEN 'draft'
+  WHEN end_date < now() THEN 'completed'
+  ELSE 'on_sale'
+END;
+
+ALTER TABLE events DROP COLUMN draft;
+
+CREATE INDEX events_status_idx ON events(status);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE cnt_ownership_events DROP CONSTRAINT cnt_ownership_events_kind_check;
+ALTER TABLE cnt_ownership_events ADD CONSTRAINT cnt_ownership_events_kind_check
+CHECK (kind IN ('issued', 'reassigned', 'sold', 'transferred', 'attended'));
+
+DROP TABLE refund_requests;
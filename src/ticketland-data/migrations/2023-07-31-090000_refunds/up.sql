@@ -0,0 +1,23 @@
+-- Your SQL goes here
+
+-- Completing a refund deletes the CNT to free its seat, so the refund only keeps the seat it was issued for
+CREATE TABLE refund_requests (
+  refund_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  seat_index INT NOT NULL,
+  cnt_sui_address VARCHAR(66),
+  account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  reason VARCHAR NOT NULL CHECK (reason IN ('requested', 'event_cancelled')),
+  status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected', 'completed')),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  resolved_at TIMESTAMP WITH TIME ZONE
+);
+
+-- A CNT can have at most one open refund
+CREATE UNIQUE INDEX refund_requests_open_idx ON refund_requests(event_id, seat_index) WHERE status IN ('pending', 'approved');
+CREATE INDEX refund_requests_account_id_idx ON refund_requests(account_id);
+CREATE INDEX refund_requests_event_id_status_idx ON refund_requests(event_id, status);
+
+ALTER TABLE cnt_ownership_events DROP CONSTRAINT cnt_ownership_events_kind_check;
+ALTER TABLE cnt_ownership_events ADD CONSTRAINT cnt_ownership_events_kind_check
+CHECK (kind IN ('issued', 'reassigned', 'sold', 'transferred', 'attended', 'refunded'));
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+
+CREATE TABLE waitlist_entries (
+  waitlist_entry_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  ticket_type_index SMALLINT NOT NULL,
+  account_id VARCHAR NOT NULL REFERENCES accounts(uid) ON DELETE CASCADE ON UPDATE CASCADE,
+  status VARCHAR NOT NULL DEFAULT 'waiting' CHECK (status IN ('waiting', 'offered', 'accepted', 'expired', 'left')),
+  seat_index INT,
+  offer_expires_at TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  resolved_at TIMESTAMP WITH TIME ZONE,
+  FOREIGN KEY(event_id, ticket_type_index) REFERENCES ticket_types(event_id, ticket_type_index) ON DELETE CASCADE,
+  CHECK (status <> 'offered' OR (seat_index IS NOT NULL AND offer_expires_at IS NOT NULL))
+);
+
+-- An account can be in the queue of a ticket type only once at a time
+CREATE UNIQUE INDEX waitlist_entries_active_idx ON waitlist_entries(event_id, ticket_type_index, account_id)
+WHERE status IN ('waiting', 'offered');
+CREATE INDEX waitlist_entries_queue_idx ON waitlist_entries(event_id, ticket_type_index, waitlist_entry_id)
+WHERE status = 'waiting';
+CREATE INDEX waitlist_entries_account_id_idx ON waitlist_entries(account_id);
+CREATE INDEX waitlist_entries_offer_expires_at_idx ON waitlist_entries(offer_expires_at) WHERE status = 'offered';
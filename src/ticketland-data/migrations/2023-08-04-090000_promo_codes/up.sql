@@ -0,0 +1,24 @@
+-- Your SQL goes here
+
+CREATE TABLE promo_codes (
+  promo_code_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  code VARCHAR NOT NULL,
+  kind VARCHAR NOT NULL CHECK (kind IN ('percentage', 'fixed', 'access')),
+  value BIGINT NOT NULL DEFAULT 0 CHECK (value >= 0),
+  ticket_type_index SMALLINT,
+  max_redemptions INTEGER CHECK (max_redemptions >= 0),
+  redemptions INTEGER NOT NULL DEFAULT 0,
+  valid_from TIMESTAMP WITH TIME ZONE,
+  valid_until TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  FOREIGN KEY(event_id, ticket_type_index) REFERENCES ticket_types(event_id, ticket_type_index) ON DELETE CASCADE,
+  UNIQUE(event_id, code),
+  CHECK (kind <> 'percentage' OR value <= 10000),
+  -- Access codes unlock a single hidden ticket type
+  CHECK (kind <> 'access' OR ticket_type_index IS NOT NULL),
+  -- Last line of defence for the redemption limit; redemptions are counted with a conditional UPDATE
+  CHECK (redemptions >= 0 AND (max_redemptions IS NULL OR redemptions <= max_redemptions))
+);
+
+CREATE INDEX promo_codes_access_idx ON promo_codes(event_id, ticket_type_index) WHERE kind = 'access';
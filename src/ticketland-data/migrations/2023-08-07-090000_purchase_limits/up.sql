@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE purchase_limits (
+  purchase_limit_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  ticket_type_index SMALLINT,
+  max_tickets INTEGER NOT NULL CHECK (max_tickets >= 0),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  FOREIGN KEY(event_id, ticket_type_index) REFERENCES ticket_types(event_id, ticket_type_index) ON DELETE CASCADE
+);
+
+-- At most one event wide limit and one limit per ticket type
+CREATE UNIQUE INDEX purchase_limits_event_idx ON purchase_limits(event_id) WHERE ticket_type_index IS NULL;
+CREATE UNIQUE INDEX purchase_limits_ticket_type_idx ON purchase_limits(event_id, ticket_type_index)
+WHERE ticket_type_index IS NOT NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE check_in_attempts;
+DROP INDEX cnts_event_id_attended_at_idx;
+ALTER TABLE cnts DROP COLUMN attended_at;
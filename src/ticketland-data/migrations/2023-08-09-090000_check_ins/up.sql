@@ -0,0 +1,39 @@
+-- Your SQL goes here
+
+ALTER TABLE cnts ADD COLUMN attended_at TIMESTAMP WITH TIME ZONE;
+
+-- Best effort backfill from the ownership history; older check-ins have no timestamp. Check-ins of a refunded
+-- CNT that used to hold the seat are skipped.
+UPDATE cnts SET attended_at = history.attended_at
+FROM (
+  SELECT event_id, seat_index, MIN(created_at) AS attended_at
+  FROM cnt_ownership_events attended
+  WHERE kind = 'attended'
+  AND NOT EXISTS (
+    SELECT 1 FROM cnt_ownership_events refunded
+    WHERE refunded.event_id = attended.event_id
+    AND refunded.seat_index = attended.seat_index
+    AND refunded.kind = 'refunded'
+    AND refunded.ownership_event_id > attended.ownership_event_id
+  )
+  GROUP BY event_id, seat_index
+) history
+WHERE cnts.attended = true AND cnts.event_id = history.event_id AND cnts.seat_index = history.seat_index;
+
+CREATE INDEX cnts_event_id_attended_at_idx ON cnts(event_id, attended_at) WHERE attended_at IS NOT NULL;
+
+-- Every scan at the door, including the ones that did not let anyone in. No FK to cnts since the scanned
+-- address might not exist.
+CREATE TABLE check_in_attempts (
+  check_in_attempt_id BIGSERIAL PRIMARY KEY,
+  event_id VARCHAR NOT NULL REFERENCES events(event_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  cnt_sui_address VARCHAR(66) NOT NULL,
+  seat_index INTEGER,
+  scanner_id VARCHAR NOT NULL,
+  gate VARCHAR,
+  result VARCHAR NOT NULL CHECK (result IN ('admitted', 'duplicate', 'rejected')),
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX check_in_attempts_event_id_created_at_idx ON check_in_attempts(event_id, created_at);
+CREATE INDEX check_in_attempts_cnt_sui_address_idx ON check_in_attempts(cnt_sui_address);
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- The price paid in the primary sale. NULL for CNTs synced from the chain, whose price is not known, and for
+-- DutchAuction tickets sold before the price was recorded.
+ALTER TABLE cnts ADD COLUMN purchase_price BIGINT CHECK (purchase_price >= 0);
+
+UPDATE cnts SET purchase_price = CASE
+  WHEN ticket_types.sale_type ? 'Free' THEN 0
+  WHEN ticket_types.sale_type ? 'FixedPrice' THEN (ticket_types.sale_type->'FixedPrice'->>'price')::BIGINT
+  WHEN ticket_types.sale_type ? 'Refundable' THEN (ticket_types.sale_type->'Refundable'->>'price')::BIGINT
+END
+FROM ticket_types
+WHERE ticket_types.event_id = cnts.event_id AND ticket_types.ticket_type_index = cnts.ticket_type_index;
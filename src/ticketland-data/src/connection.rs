@@ -1,15 +1,30 @@
+use std::{future::Future, ops::DerefMut, pin::Pin};
 use diesel_async::{
+  AsyncConnection,
   AsyncPgConnection,
   pooled_connection::deadpool::Object,
 };
+use eyre::{Report, Result};
 
-pub struct PostgresConnection(Object<AsyncPgConnection>);
+pub type TransactionFuture<'a, R> = Pin<Box<dyn Future<Output = Result<R>> + Send + 'a>>;
+
+/// Anything `PostgresConnection` can run queries on: a pooled connection or the connection of an open transaction
+pub trait ConnectionHandle: DerefMut<Target = AsyncPgConnection> + Send {}
+
+impl<C: DerefMut<Target = AsyncPgConnection> + Send> ConnectionHandle for C {}
+
+pub struct PostgresConnection<C: ConnectionHandle = Object<AsyncPgConnection>>(C);
+
+/// The handle passed to `PostgresConnection::transaction` callbacks
+pub type PostgresTransaction<'a> = PostgresConnection<&'a mut AsyncPgConnection>;
 
 impl PostgresConnection {
   pub fn new(conn: Object<AsyncPgConnection>) -> Self {
     Self(conn)
   }
+}
 
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub fn borrow(&mut self) -> &AsyncPgConnection {
     &self.0
   }
@@ -17,4 +32,31 @@ impl PostgresConnection {
   pub fn borrow_mut(&mut self) -> &mut AsyncPgConnection {
     &mut self.0
   }
+
+  /// Runs the callback inside a database transaction. The callback receives a connection with the same
+  /// repository methods, bound to the transaction. The transaction is committed if the callback returns
+  /// Ok and rolled back otherwise. Repository methods that open their own transaction, and nested calls
+  /// to `transaction`, run as a savepoint of this one.
+  ///
+  /// ```ignore
+  /// postgres.transaction(|tx| Box::pin(async move {
+  ///   tx.check_in_cnt(event_id, cnt_sui_address.clone(), scanner_id, None).await?;
+  ///   tx.fill_listing(listing_id, cnt_sui_address, new_owner).await?;
+  ///
+  ///   Ok(())
+  /// }))
+  /// .await?;
+  /// ```
+  pub async fn transaction<R, F>(&mut self, callback: F) -> Result<R>
+  where
+    F: for<'r> FnOnce(&'r mut PostgresTransaction<'r>) -> TransactionFuture<'r, R> + Send,
+    R: Send,
+  {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let mut tx = PostgresConnection(conn);
+      callback(&mut tx).await
+    }))
+    .await
+  }
 }
@@ -1,23 +1,52 @@
+use diesel::{Connection, PgConnection};
 use diesel_async::{
   AsyncPgConnection, pooled_connection::{AsyncDieselConnectionManager, deadpool::Pool},
 };
-use eyre::Result;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use eyre::{Result, eyre};
 use super::connection::PostgresConnection;
 
-pub struct ConnectionPool(Pool<AsyncPgConnection>);
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+pub struct ConnectionPool {
+  pool: Pool<AsyncPgConnection>,
+  db_uri: String,
+}
 
 impl ConnectionPool {
   pub async fn new(db_uri: &str) -> Self {
     let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_uri);
     let pool = Pool::builder(config).build().unwrap();
 
-    Self(pool)
+    Self {
+      pool,
+      db_uri: db_uri.to_string(),
+    }
   }
 
   pub async fn connection(&self) -> Result<PostgresConnection> {
-    let conn = self.0.get().await?;
+    let conn = self.pool.get().await?;
     Ok(PostgresConnection::new(conn))
   }
-}
 
+  /// Brings the database to the latest schema by running the migrations embedded in this crate
+  /// that have not been applied yet. Returns the versions of the migrations that were run.
+  ///
+  /// diesel_migrations only works with sync connections, so a dedicated `PgConnection` is opened
+  /// on a blocking thread rather than using one from the pool.
+  pub async fn run_pending_migrations(&self) -> Result<Vec<String>> {
+    let db_uri = self.db_uri.clone();
 
+    tokio::task::spawn_blocking(move || {
+      let mut conn = PgConnection::establish(&db_uri)?;
+      let versions = conn.run_pending_migrations(MIGRATIONS)
+      .map_err(|error| eyre!(error))?
+      .into_iter()
+      .map(|version| version.to_string())
+      .collect();
+
+      Ok(versions)
+    })
+    .await?
+  }
+}
@@ -0,0 +1,71 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{sql_function, sql_types::{Nullable, Timestamptz}};
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+
+sql_function! {
+  /// `COALESCE` of a nullable timestamp, used to give rows without a `created_at` a keyset position
+  #[sql_name = "COALESCE"]
+  fn coalesce_ts(value: Nullable<Timestamptz>, fallback: Timestamptz) -> Timestamptz;
+}
+
+/// Position of the last row of a page in a keyset (cursor) paginated listing. `ts`, `id` and, for
+/// keys with a third column, `index` are the sort key of that row e.g. `(created_at, listing_id)`,
+/// `(start_date, event_id)` or `(created_at, event_id, seat_index)`.
+///
+/// Clients only ever see the encoded form, which they send back to fetch the next page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cursor {
+  pub ts: NaiveDateTime,
+  pub id: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub index: Option<i32>,
+}
+
+impl Cursor {
+  pub fn new(ts: NaiveDateTime, id: String) -> Self {
+    Self {ts, id, index: None}
+  }
+
+  pub fn with_index(ts: NaiveDateTime, id: String, index: i32) -> Self {
+    Self {ts, id, index: Some(index)}
+  }
+
+  /// The `ts` of a row keyed by a nullable `created_at`. Rows without one sort as if created at the unix
+  /// epoch, which is what `coalesce_ts(created_at, Cursor::epoch())` and `COALESCE(created_at, 'epoch')` give.
+  pub fn created_at(created_at: Option<NaiveDateTime>) -> NaiveDateTime {
+    created_at.unwrap_or_else(Self::epoch)
+  }
+
+  pub fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1970, 1, 1).and_then(|date| date.and_hms_opt(0, 0, 0)).expect("valid date")
+  }
+
+  pub fn encode(&self) -> String {
+    let json = serde_json::to_string(self).expect("cursor is always serializable");
+    base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+  }
+
+  pub fn decode(cursor: &str) -> Result<Self> {
+    let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+    .map_err(|_| eyre!("invalid cursor"))?;
+
+    serde_json::from_slice(&json).map_err(|_| eyre!("invalid cursor"))
+  }
+
+  pub fn decode_opt(cursor: Option<String>) -> Result<Option<Self>> {
+    cursor.map(|cursor| Self::decode(&cursor)).transpose()
+  }
+
+  /// Returns the encoded cursor of the last item if the page is full, meaning there might be more rows
+  pub fn next<T, F>(items: &[T], limit: i64, key: F) -> Option<String>
+  where
+    F: Fn(&T) -> Option<Cursor>
+  {
+    if (items.len() as i64) < limit {
+      return None
+    }
+
+    items.last().and_then(key).map(|cursor| cursor.encode())
+  }
+}
@@ -0,0 +1,85 @@
+use thiserror::Error;
+
+/// Domain errors returned by the repositories, wrapped in an `eyre::Report`. Callers that need to
+/// react to a specific case can use `report.downcast_ref::<Error>()`.
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+  #[error("Ticket type not found")]
+  TicketTypeNotFound,
+  #[error("Invalid seat count")]
+  InvalidSeatCount,
+  #[error("Sale has not started yet")]
+  SaleNotStarted,
+  #[error("Sale has ended")]
+  SaleEnded,
+  #[error("Not enough tickets left")]
+  SoldOut,
+  #[error("Seat is not available")]
+  SeatUnavailable,
+  #[error("Seat hold not found or expired")]
+  SeatHoldNotFound,
+  #[error("Invalid seat ranges: {0}")]
+  InvalidSeatRanges(String),
+  #[error("Price is above the resale cap of {0}")]
+  ResalePriceAboveCap(i64),
+  #[error("Invalid resale policy: {0}")]
+  InvalidResalePolicy(String),
+  #[error("CNT not found")]
+  CntNotFound,
+  #[error("CNT has an open listing")]
+  CntListed,
+  #[error("Listing not found, closed or expired")]
+  ListingNotAvailable,
+  #[error("Offer not found, closed or expired")]
+  OfferNotAvailable,
+  #[error("CNT is not for the offer's event and ticket type")]
+  CntNotForOffer,
+  #[error("CNT has already been attended")]
+  CntAttended,
+  #[error("CNT already has a pending transfer")]
+  TransferAlreadyPending,
+  #[error("Transfer not found or no longer pending")]
+  TransferNotFound,
+  #[error("Invalid transfer recipient")]
+  InvalidTransferRecipient,
+  #[error("Event not found")]
+  EventNotFound,
+  #[error("Event cannot go from {from} to {to}")]
+  InvalidEventTransition {from: &'static str, to: &'static str},
+  #[error("Event is {0}")]
+  InvalidEventStatus(&'static str),
+  #[error("Ticket type is not refundable")]
+  NotRefundable,
+  #[error("Refunds are only possible before the event starts")]
+  RefundWindowClosed,
+  #[error("CNT has been resold")]
+  CntResold,
+  #[error("CNT already has an open refund request")]
+  RefundAlreadyRequested,
+  #[error("CNT has an open refund request")]
+  CntRefundPending,
+  #[error("Refund request not found or in the wrong state")]
+  RefundNotFound,
+  #[error("Already on the waitlist")]
+  AlreadyOnWaitlist,
+  #[error("Ticket type is not sold out")]
+  NotSoldOut,
+  #[error("Waitlist entry not found or in the wrong state")]
+  WaitlistEntryNotFound,
+  #[error("Invalid promo code: {0}")]
+  InvalidPromoCode(String),
+  #[error("Promo code not found or not valid for this ticket type")]
+  PromoCodeNotFound,
+  #[error("Promo code has no redemptions left")]
+  PromoCodeExhausted,
+  #[error("Ticket type needs an access code")]
+  TicketTypeLocked,
+  #[error("At most {0} tickets per account for this event")]
+  EventPurchaseLimitExceeded(i32),
+  #[error("At most {0} tickets per account for this ticket type")]
+  TicketTypePurchaseLimitExceeded(i32),
+  #[error("Invalid purchase limit: {0}")]
+  InvalidPurchaseLimit(String),
+  #[error("Account not found or already deleted")]
+  AccountNotFound,
+}
@@ -3,3 +3,7 @@ pub mod connection;
 pub mod schema;
 pub mod repositories;
 pub mod models;
+pub mod query;
+pub mod cursor;
+pub mod error;
+pub mod pricing;
@@ -2,6 +2,24 @@ use serde::{Deserialize, Serialize};
 use diesel::prelude::*;
 use chrono::NaiveDateTime;
 use crate::schema::accounts;
+use super::{
+  api_client::ApiClient,
+  canva_account::CanvaAccount,
+  canva_design::CanvaDesign,
+  check_in::CheckInAttempt,
+  cnt::CNT,
+  cnt_ownership_event::CntOwnershipEvent,
+  cnt_transfer::CntTransfer,
+  event::Event,
+  listing::Listing,
+  offer::Offer,
+  refund_request::RefundRequest,
+  seat_hold::SeatHold,
+  stripe_account::StripeAccount,
+  stripe_customer::StripeCustomer,
+  trade::Trade,
+  waitlist::WaitlistEntry,
+};
 
 #[derive(Insertable, Queryable, AsChangeset, Serialize, Deserialize, Clone, Default)]
 #[diesel(table_name = accounts)]
@@ -16,3 +34,27 @@ pub struct Account {
   pub delete_request_at: Option<NaiveDateTime>,
   pub deleted_at: Option<NaiveDateTime>,
 }
+
+/// Everything stored about an account, for data export requests. Secrets, i.e. the dapp share and the API client
+/// secrets, are left out. `check_in_attempts` are the scans of the CNTs the account owns and `ownership_history`
+/// the history entries the account is part of.
+#[derive(Serialize, Clone)]
+pub struct AccountExport {
+  pub account: Account,
+  pub events: Vec<Event>,
+  pub cnts: Vec<CNT>,
+  pub listings: Vec<Listing>,
+  pub offers: Vec<Offer>,
+  pub trades: Vec<Trade>,
+  pub transfers: Vec<CntTransfer>,
+  pub refund_requests: Vec<RefundRequest>,
+  pub waitlist_entries: Vec<WaitlistEntry>,
+  pub seat_holds: Vec<SeatHold>,
+  pub check_in_attempts: Vec<CheckInAttempt>,
+  pub ownership_history: Vec<CntOwnershipEvent>,
+  pub api_clients: Vec<ApiClient>,
+  pub canva_accounts: Vec<CanvaAccount>,
+  pub canva_designs: Vec<CanvaDesign>,
+  pub stripe_accounts: Vec<StripeAccount>,
+  pub stripe_customers: Vec<StripeCustomer>,
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::{self, Text},
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::check_in_attempts;
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckInResult {
+  /// First scan of the CNT; it is now attended
+  Admitted,
+  /// The CNT had already been checked in
+  Duplicate,
+  /// No such CNT for the event, the CNT is being refunded or the event is not admitting anyone e.g. because it
+  /// was cancelled
+  Rejected,
+}
+
+text_enum!(CheckInResult {
+  Admitted => "admitted",
+  Duplicate => "duplicate",
+  Rejected => "rejected",
+});
+
+/// The `scanner_id` of check-ins that did not come from a scanner, see `update_attended`
+pub const API_SCANNER_ID: &str = "api";
+
+/// A single scan at the door. `scanner_id` identifies the device and `gate` the entrance it was used at.
+#[derive(Queryable, QueryableByName, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = check_in_attempts)]
+pub struct CheckInAttempt {
+  pub check_in_attempt_id: i64,
+  pub event_id: String,
+  pub cnt_sui_address: String,
+  pub seat_index: Option<i32>,
+  pub scanner_id: String,
+  pub gate: Option<String>,
+  pub result: CheckInResult,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = check_in_attempts)]
+pub struct NewCheckInAttempt {
+  pub event_id: String,
+  pub cnt_sui_address: String,
+  pub seat_index: Option<i32>,
+  pub scanner_id: String,
+  pub gate: Option<String>,
+  pub result: CheckInResult,
+}
+
+#[derive(QueryableByName, Serialize)]
+pub struct CheckInsData {
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub count: i64,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  #[serde(serialize_with = "to_milli_ts")]
+  pub timestamp: NaiveDateTime,
+}
+
+/// Scan totals of a scanner at a gate
+#[derive(QueryableByName, Serialize)]
+pub struct ScannerCheckIns {
+  #[diesel(sql_type = sql_types::VarChar)]
+  pub scanner_id: String,
+  #[diesel(sql_type = sql_types::Nullable<sql_types::VarChar>)]
+  pub gate: Option<String>,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub admitted: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub duplicates: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub rejected: i64,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  #[serde(serialize_with = "to_milli_ts")]
+  pub first_scan_at: NaiveDateTime,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  #[serde(serialize_with = "to_milli_ts")]
+  pub last_scan_at: NaiveDateTime,
+}
@@ -24,6 +24,8 @@ pub struct CNT {
   pub seat_index: i32,
   pub attended: bool,
   pub draft: bool,
+  pub attended_at: Option<NaiveDateTime>,
+  pub purchase_price: Option<i64>,
 }
 
 #[derive(QueryableByName, Serialize, Deserialize, Clone, Default)]
@@ -43,6 +45,8 @@ pub struct CNTWithMetadata {
   pub seat_index: i32,
   pub attended: bool,
   pub draft: bool,
+  pub attended_at: Option<NaiveDateTime>,
+  pub purchase_price: Option<i64>,
   pub listing: Option<PartialListing>,
   pub ticket_type: ExtendedTicketType,
   pub nfts: Vec<TicketTypeNft>,
@@ -55,22 +59,12 @@ impl CNTWithMetadata {
     .fold(
       Vec::new(),
       |mut acc: Vec<CNTWithMetadata>, (cnt, ticket_type, seat_range, ticket_type_nft_details, ticket_type_nft, listing)| {
-      let key = cnt.cnt_sui_address.clone();
-        let mut extended_ticket_type = ExtendedTicketType::from(ticket_type);
-        extended_ticket_type.seat_range = seat_range;
-        extended_ticket_type.ticket_type_nft_details = vec![ticket_type_nft_details.clone()];
-
+        // Draft CNTs have no sui address yet, so they are keyed by their primary key
         let existing_cnt_index = acc
         .iter()
-        .position(|item| item.cnt_sui_address == key);
-
-        if let Some(index) = existing_cnt_index {
-          acc[index].ticket_type.ticket_type_nft_details.push(ticket_type_nft_details);
+        .position(|item| item.event_id == cnt.event_id && item.seat_index == cnt.seat_index);
 
-          if let Some(ticket_type_nft) = ticket_type_nft {
-            acc[index].nfts.push(ticket_type_nft);
-          }
-        } else {
+        let index = existing_cnt_index.unwrap_or_else(|| {
           acc.push(CNTWithMetadata {
             cnt_sui_address: cnt.cnt_sui_address,
             event_id: cnt.event_id,
@@ -81,11 +75,19 @@ impl CNTWithMetadata {
             seat_index: cnt.seat_index,
             attended: cnt.attended,
             draft: cnt.draft,
+            attended_at: cnt.attended_at,
+            purchase_price: cnt.purchase_price,
             listing,
-            ticket_type: extended_ticket_type,
-            nfts: ticket_type_nft.map_or(vec![], |ticket_type_nft| vec![ticket_type_nft]),
+            ticket_type: ExtendedTicketType::from(ticket_type),
+            nfts: vec![],
           });
-        }
+
+          acc.len() - 1
+        });
+
+        acc[index].ticket_type.add_seat_range(seat_range);
+        acc[index].ticket_type.add_nft_details(ticket_type_nft_details);
+        add_ticket_type_nft(&mut acc[index].nfts, ticket_type_nft);
 
         acc
     })
@@ -103,6 +105,8 @@ pub struct CNTWithEvent {
   pub seat_index: i32,
   pub attended: bool,
   pub draft: bool,
+  pub attended_at: Option<NaiveDateTime>,
+  pub purchase_price: Option<i64>,
   pub listing: Option<PartialListing>,
   pub event: Event,
   pub ticket_type: ExtendedTicketType,
@@ -116,22 +120,12 @@ impl CNTWithEvent {
     .fold(
       Vec::new(),
       |mut acc: Vec<CNTWithEvent>, (cnt, event, ticket_type, seat_range, ticket_type_nft_details, ticket_type_nft, listing)| {
-      let key = cnt.cnt_sui_address.clone();
-        let mut extended_ticket_type = ExtendedTicketType::from(ticket_type);
-        extended_ticket_type.seat_range = seat_range;
-        extended_ticket_type.ticket_type_nft_details = vec![ticket_type_nft_details.clone()];
-
+        // Draft CNTs have no sui address yet, so they are keyed by their primary key
         let existing_cnt_index = acc
         .iter()
-        .position(|item| item.cnt_sui_address == key);
-
-        if let Some(index) = existing_cnt_index {
-          acc[index].ticket_type.ticket_type_nft_details.push(ticket_type_nft_details);
+        .position(|item| item.event_id == cnt.event_id && item.seat_index == cnt.seat_index);
 
-          if let Some(ticket_type_nft) = ticket_type_nft {
-            acc[index].nfts.push(ticket_type_nft);
-          }
-        } else {
+        let index = existing_cnt_index.unwrap_or_else(|| {
           acc.push(CNTWithEvent {
             cnt_sui_address: cnt.cnt_sui_address,
             event_id: cnt.event_id,
@@ -142,14 +136,34 @@ impl CNTWithEvent {
             seat_index: cnt.seat_index,
             attended: cnt.attended,
             draft: cnt.draft,
+            attended_at: cnt.attended_at,
+            purchase_price: cnt.purchase_price,
             listing,
             event,
-            ticket_type: extended_ticket_type,
-            nfts: ticket_type_nft.map_or(vec![], |ticket_type_nft| vec![ticket_type_nft]),
+            ticket_type: ExtendedTicketType::from(ticket_type),
+            nfts: vec![],
           });
-        }
+
+          acc.len() - 1
+        });
+
+        acc[index].ticket_type.add_seat_range(seat_range);
+        acc[index].ticket_type.add_nft_details(ticket_type_nft_details);
+        add_ticket_type_nft(&mut acc[index].nfts, ticket_type_nft);
 
         acc
     })
   }
 }
+
+fn add_ticket_type_nft(nfts: &mut Vec<TicketTypeNft>, ticket_type_nft: Option<TicketTypeNft>) {
+  if let Some(ticket_type_nft) = ticket_type_nft {
+    let exists = nfts
+    .iter()
+    .any(|item| item.ticket_type_nft_sui_address == ticket_type_nft.ticket_type_nft_sui_address);
+
+    if !exists {
+      nfts.push(ticket_type_nft);
+    }
+  }
+}
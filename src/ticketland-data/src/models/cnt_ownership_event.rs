@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::Text,
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::cnt_ownership_events;
+use super::cnt::CNT;
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnershipEventKind {
+  /// The CNT was created for its first owner
+  Issued,
+  /// The owner was overwritten by `upsert_user_cnt` e.g. when syncing with the chain
+  Reassigned,
+  /// Sold on the secondary market; `trade_id` points to the trade
+  Sold,
+  /// Gifted; `transfer_id` points to the transfer
+  Transferred,
+  /// The owner attended the event. Ownership does not change
+  Attended,
+  /// The ticket was refunded and the CNT deleted; `to_account_id` is the organizer
+  Refunded,
+}
+
+text_enum!(OwnershipEventKind {
+  Issued => "issued",
+  Reassigned => "reassigned",
+  Sold => "sold",
+  Transferred => "transferred",
+  Attended => "attended",
+  Refunded => "refunded",
+});
+
+/// An entry of the append-only history of a CNT. Entries are keyed by seat rather than by `cnt_sui_address`
+/// so the history of draft CNTs is kept too.
+#[derive(Queryable, QueryableByName, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = cnt_ownership_events)]
+pub struct CntOwnershipEvent {
+  pub ownership_event_id: i64,
+  pub event_id: String,
+  pub seat_index: i32,
+  pub cnt_sui_address: Option<String>,
+  pub kind: OwnershipEventKind,
+  pub from_account_id: Option<String>,
+  pub to_account_id: String,
+  pub trade_id: Option<i64>,
+  pub transfer_id: Option<i64>,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = cnt_ownership_events)]
+pub struct NewCntOwnershipEvent {
+  pub event_id: String,
+  pub seat_index: i32,
+  pub cnt_sui_address: Option<String>,
+  pub kind: OwnershipEventKind,
+  pub from_account_id: Option<String>,
+  pub to_account_id: String,
+  pub trade_id: Option<i64>,
+  pub transfer_id: Option<i64>,
+}
+
+impl NewCntOwnershipEvent {
+  /// An event for the CNT in its new state i.e. `cnt.account_id` is the owner after the change
+  pub fn new(kind: OwnershipEventKind, cnt: &CNT, from_account_id: Option<String>) -> Self {
+    Self {
+      event_id: cnt.event_id.clone(),
+      seat_index: cnt.seat_index,
+      cnt_sui_address: cnt.cnt_sui_address.clone(),
+      kind,
+      from_account_id,
+      to_account_id: cnt.account_id.clone(),
+      trade_id: None,
+      transfer_id: None,
+    }
+  }
+}
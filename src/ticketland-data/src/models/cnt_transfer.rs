@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::Text,
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::cnt_transfers;
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+  Pending,
+  Accepted,
+  Declined,
+  Cancelled,
+}
+
+text_enum!(TransferStatus {
+  Pending => "pending",
+  Accepted => "accepted",
+  Declined => "declined",
+  Cancelled => "cancelled",
+});
+
+/// A CNT gifted by its owner to another account. The recipient is either an account id or an email; a transfer
+/// to an email can be accepted by whichever account has that email at the time.
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = cnt_transfers)]
+pub struct CntTransfer {
+  pub transfer_id: i64,
+  pub cnt_sui_address: String,
+  pub event_id: String,
+  pub sender_account_id: String,
+  pub recipient_account_id: Option<String>,
+  pub recipient_email: Option<String>,
+  pub status: TransferStatus,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+  pub resolved_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = cnt_transfers)]
+pub struct NewCntTransfer {
+  pub cnt_sui_address: String,
+  pub event_id: String,
+  pub sender_account_id: String,
+  pub recipient_account_id: Option<String>,
+  pub recipient_email: Option<String>,
+}
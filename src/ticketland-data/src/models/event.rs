@@ -27,6 +27,76 @@ pub struct Location {
     pub longitude: f32,
 }
 
+/// The lifecycle of an event. See `can_transition_to` for the allowed changes.
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[diesel(sql_type = sql_types::Text)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+  /// Being set up by the organizer; only visible to them
+  #[default]
+  Draft,
+  /// Committed on chain and visible, but tickets cannot be bought yet
+  Published,
+  /// Primary sales are open within each ticket type's sale window
+  OnSale,
+  /// Primary sales are temporarily stopped
+  Paused,
+  /// Terminal. All CNTs are refunded and the secondary market is closed
+  Cancelled,
+  /// Moved to a later date; primary sales are stopped until the sale is resumed
+  Postponed,
+  /// Terminal. The event took place
+  Completed,
+}
+
+impl EventStatus {
+  /// Drafts only become published through `commit_event`. Cancelled and completed events are final.
+  pub fn can_transition_to(&self, next: EventStatus) -> bool {
+    use EventStatus::*;
+
+    matches!(
+      (self, next),
+      (Draft, Published | Cancelled)
+      | (Published, OnSale | Postponed | Cancelled | Completed)
+      | (OnSale, Paused | Postponed | Cancelled | Completed)
+      | (Paused, OnSale | Postponed | Cancelled | Completed)
+      | (Postponed, Published | OnSale | Cancelled)
+    )
+  }
+
+  /// The statuses in which CNTs can be listed and offered for on the secondary market
+  pub const TRADING: [EventStatus; 4] = [Self::Published, Self::OnSale, Self::Paused, Self::Postponed];
+
+  /// The statuses in which CNTs can be checked in at the door
+  pub const ADMITTING: [EventStatus; 3] = [Self::Published, Self::OnSale, Self::Paused];
+
+  /// Whether the event shows up in search
+  pub fn is_listed(&self) -> bool {
+    Self::TRADING.contains(self)
+  }
+}
+
+text_enum!(EventStatus {
+  Draft => "draft",
+  Published => "published",
+  OnSale => "on_sale",
+  Paused => "paused",
+  Cancelled => "cancelled",
+  Postponed => "postponed",
+  Completed => "completed",
+});
+
+/// The result of `cancel_event`
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EventCancellation {
+  pub event_id: String,
+  pub refund_requests: usize,
+  pub closed_listings: usize,
+  pub closed_offers: usize,
+  pub cancelled_transfers: usize,
+  pub released_seat_holds: usize,
+}
+
 #[derive(Insertable, Queryable, AsChangeset, QueryableByName, Serialize, Deserialize, Clone, Default)]
 #[diesel(table_name = events)]
 pub struct Event {
@@ -50,7 +120,9 @@ pub struct Event {
   pub event_nft: Option<String>,
   pub event_capacity_bitmap_address: Option<String>,
   pub webbundle_arweave_tx_id: Option<String>,
-  pub draft: bool,
+  /// Only changed through the lifecycle operations of the events repository; `upsert_event` keeps the stored one
+  #[serde(default)]
+  pub status: EventStatus,
 }
 
 impl Event {
@@ -81,7 +153,7 @@ pub struct ExtendedEvent {
   pub event_nft: Option<String>,
   pub event_capacity_bitmap_address: Option<String>,
   pub webbundle_arweave_tx_id: Option<String>,
-  pub draft: bool,
+  pub status: EventStatus,
   pub ticket_types: Vec<ExtendedTicketType>,
 }
 
@@ -95,24 +167,42 @@ pub struct AttendedTicketCount {
   attended_count: i64,
 }
 
-fn append_nft_details_to_ticket_type(ticket_types: &mut Vec<ExtendedTicketType>, nft_detail: TicketTypeNftDetail) -> &Vec<ExtendedTicketType> {
-
-  let ticket_type_index = ticket_types
-  .iter()
-  .position(|item| item.ticket_type_index == nft_detail.ticket_type_index);
-
-  // If the nft_details does not refer to this ticket_type, return
-  if let Some(ticket_type_index) = ticket_type_index {
-    let nft_details_index = ticket_types[ticket_type_index].ticket_type_nft_details
-    .iter()
-    .position(|item| item.nft_details_id == nft_detail.nft_details_id);
+/// Events within `radius_km` of the given point. Events without a location never match.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GeoFilter {
+  pub latitude: f64,
+  pub longitude: f64,
+  pub radius_km: f64,
+}
 
-    if None == nft_details_index {
-      ticket_types[ticket_type_index].ticket_type_nft_details.push(nft_detail);
-    }
-  }
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSort {
+  #[default]
+  EventId,
+  StartDate,
+  /// Lowest current price of any of the event's ticket types first
+  Price,
+  /// Best full text match first. Requires `EventFilters::text`
+  Relevance,
+  /// Closest first. Requires `EventFilters::near`
+  Distance,
+}
 
-  ticket_types
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EventFilters {
+  pub category: Option<i16>,
+  /// Inclusive range matched against the current price of each ticket type, whatever its sale type
+  pub price_range: Option<(u32, u32)>,
+  pub start_date_from: Option<NaiveDateTime>,
+  pub start_date_to: Option<NaiveDateTime>,
+  /// Substring match on the event name
+  pub name: Option<String>,
+  /// Full text search over name, venue and description. Accepts the web search syntax
+  /// e.g. `jazz -festival "open air"`
+  pub text: Option<String>,
+  pub near: Option<GeoFilter>,
+  pub sort: EventSort,
 }
 
 impl ExtendedEvent {
@@ -122,52 +212,51 @@ impl ExtendedEvent {
     .fold(
       Vec::new(),
      |mut acc: Vec<ExtendedEvent>, (event, ticket_type, seat_range, ticket_type_nft_details)| {
-
-      let key = event.event_id.clone();
-      // TODO: handle case where a Seat maps with multiple SeatRange.
-      let mut extended_ticket_type = ExtendedTicketType::from(ticket_type);
-      extended_ticket_type.seat_range = seat_range;
-      extended_ticket_type.ticket_type_nft_details = vec![ticket_type_nft_details.clone()];
-
       let existing_event_index = acc
       .iter()
-      .position(|item| item.event_id == key);
+      .position(|item| item.event_id == event.event_id);
 
-      if let Some(index) = existing_event_index {
-        let existing_ticket_type_index = acc[index].ticket_types
-        .iter()
-        .position(|item| item.ticket_type_sui_address == extended_ticket_type.ticket_type_sui_address);
-
-
-        if None == existing_ticket_type_index {
-          acc[index].ticket_types.push(extended_ticket_type);
-        } else {
-          append_nft_details_to_ticket_type(&mut acc[index].ticket_types, ticket_type_nft_details);
-        }
-      } else {
+      let index = existing_event_index.unwrap_or_else(|| {
         acc.push(ExtendedEvent {
-          event_id: event.event_id.clone(),
-          account_id: event.account_id.clone(),
+          event_id: event.event_id,
+          account_id: event.account_id,
           created_at: event.created_at,
-          name: event.name.clone(),
-          description: event.description.clone(),
-          location: event.location.clone(),
-          venue: event.venue.clone(),
+          name: event.name,
+          description: event.description,
+          location: event.location,
+          venue: event.venue,
           event_type: event.event_type,
           visibility: event.visibility,
           start_date: event.start_date,
           end_date: event.end_date,
           category: event.category,
-          event_sui_address: event.event_sui_address.clone(),
-          organizer_cap: event.organizer_cap.clone(),
-          operator_cap: event.operator_cap.clone(),
-          event_nft: event.event_nft.clone(),
-          event_capacity_bitmap_address: event.event_capacity_bitmap_address.clone(),
+          event_sui_address: event.event_sui_address,
+          organizer_cap: event.organizer_cap,
+          operator_cap: event.operator_cap,
+          event_nft: event.event_nft,
+          event_capacity_bitmap_address: event.event_capacity_bitmap_address,
           webbundle_arweave_tx_id: event.webbundle_arweave_tx_id,
-          draft: event.draft,
-          ticket_types: vec![extended_ticket_type],
+          status: event.status,
+          ticket_types: vec![],
         });
-      }
+
+        acc.len() - 1
+      });
+
+      // A ticket type appears once per seat range and nft details combination. Draft ticket types have no
+      // sui address yet, so the index is the only reliable key.
+      let ticket_types = &mut acc[index].ticket_types;
+      let existing_ticket_type_index = ticket_types
+      .iter()
+      .position(|item| item.ticket_type_index == ticket_type.ticket_type_index);
+
+      let ticket_type_index = existing_ticket_type_index.unwrap_or_else(|| {
+        ticket_types.push(ExtendedTicketType::from(ticket_type));
+        ticket_types.len() - 1
+      });
+
+      ticket_types[ticket_type_index].add_seat_range(seat_range);
+      ticket_types[ticket_type_index].add_nft_details(ticket_type_nft_details);
 
       acc
     })
@@ -1,3 +1,32 @@
+/// Implements `as_str` and the diesel conversions for a fieldless enum stored as a VARCHAR column
+macro_rules! text_enum {
+  ($name:ident { $($variant:ident => $value:literal),+ $(,)? }) => {
+    impl $name {
+      pub fn as_str(&self) -> &'static str {
+        match self {
+          $(Self::$variant => $value,)+
+        }
+      }
+    }
+
+    impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+      fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>) -> diesel::serialize::Result {
+        std::io::Write::write_all(out, self.as_str().as_bytes())?;
+        Ok(diesel::serialize::IsNull::No)
+      }
+    }
+
+    impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+      fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match std::str::from_utf8(bytes.as_bytes())? {
+          $($value => Ok(Self::$variant),)+
+          value => Err(format!("Unrecognized {} {}", stringify!($name), value).into()),
+        }
+      }
+    }
+  };
+}
+
 pub mod account;
 pub mod canva_account;
 pub mod event;
@@ -14,3 +43,15 @@ pub mod properties;
 pub mod sales;
 pub mod nft;
 pub mod nft_detail;
+pub mod seat_hold;
+pub mod trade;
+pub mod order_book;
+pub mod resale_policy;
+pub mod order;
+pub mod cnt_transfer;
+pub mod cnt_ownership_event;
+pub mod refund_request;
+pub mod waitlist;
+pub mod promo_code;
+pub mod purchase_limit;
+pub mod check_in;
@@ -16,6 +16,7 @@ pub struct Offer {
   pub is_open: bool,
   pub closed_at: Option<NaiveDateTime>,
   pub draft: bool,
+  pub expires_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, AsChangeset, Deserialize)]
@@ -29,4 +30,6 @@ pub struct NewOffer<'a> {
   pub bid_price: i64,
   pub is_open: bool,
   pub draft: bool,
+  /// The order is closed by `expire_stale_orders` after this time. None means it never expires
+  pub expires_at: Option<NaiveDateTime>,
 }
@@ -0,0 +1,12 @@
+use serde::Serialize;
+use super::{
+  listing::Listing,
+  offer::Offer,
+};
+
+/// Orders closed by `expire_stale_orders`
+#[derive(Serialize, Clone, Default)]
+pub struct ExpiredOrders {
+  pub listings: Vec<Listing>,
+  pub offers: Vec<Offer>,
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use diesel::{prelude::*, sql_types};
+
+/// Open orders of a ticket type at the same price
+#[derive(QueryableByName, Serialize, Clone, Debug)]
+pub struct PriceLevel {
+  #[serde(skip)]
+  #[diesel(sql_type = sql_types::SmallInt)]
+  pub ticket_type_index: i16,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub price: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub quantity: i64,
+}
+
+#[derive(QueryableByName, Clone, Debug)]
+pub struct LastTrade {
+  #[diesel(sql_type = sql_types::SmallInt)]
+  pub ticket_type_index: i16,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub price: i64,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  pub traded_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct TicketTypeOrderBook {
+  pub ticket_type_index: i16,
+  /// Offers, highest price first
+  pub bids: Vec<PriceLevel>,
+  /// Listings, lowest price first
+  pub asks: Vec<PriceLevel>,
+  pub best_bid: Option<i64>,
+  pub best_ask: Option<i64>,
+  /// `best_ask - best_bid`. Negative if the book is crossed
+  pub spread: Option<i64>,
+  pub last_price: Option<i64>,
+  pub last_traded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct OrderBook {
+  pub event_id: String,
+  pub ticket_types: Vec<TicketTypeOrderBook>,
+}
+
+impl OrderBook {
+  /// Builds the book from the price levels, which must be sorted by ticket type and then best price first.
+  /// Only the best `depth` levels of each side are kept.
+  pub fn new(
+    event_id: String,
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+    last_trades: Vec<LastTrade>,
+    depth: usize,
+  ) -> Self {
+    let mut ticket_types = Vec::<TicketTypeOrderBook>::new();
+
+    fn entry(ticket_types: &mut Vec<TicketTypeOrderBook>, ticket_type_index: i16) -> &mut TicketTypeOrderBook {
+      let index = ticket_types
+      .iter()
+      .position(|item| item.ticket_type_index == ticket_type_index)
+      .unwrap_or_else(|| {
+        ticket_types.push(TicketTypeOrderBook {ticket_type_index, ..Default::default()});
+        ticket_types.len() - 1
+      });
+
+      &mut ticket_types[index]
+    }
+
+    for level in bids {
+      let book = entry(&mut ticket_types, level.ticket_type_index);
+      if book.bids.len() < depth {
+        book.bids.push(level);
+      }
+    }
+
+    for level in asks {
+      let book = entry(&mut ticket_types, level.ticket_type_index);
+      if book.asks.len() < depth {
+        book.asks.push(level);
+      }
+    }
+
+    for last_trade in last_trades {
+      let book = entry(&mut ticket_types, last_trade.ticket_type_index);
+      book.last_price = Some(last_trade.price);
+      book.last_traded_at = Some(last_trade.traded_at);
+    }
+
+    for book in ticket_types.iter_mut() {
+      book.best_bid = book.bids.first().map(|level| level.price);
+      book.best_ask = book.asks.first().map(|level| level.price);
+      book.spread = book.best_ask.zip(book.best_bid).map(|(ask, bid)| ask - bid);
+    }
+
+    ticket_types.sort_by_key(|book| book.ticket_type_index);
+
+    Self {event_id, ticket_types}
+  }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::Text,
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::{
+  error::Error,
+  schema::promo_codes,
+};
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum PromoCodeKind {
+  /// `value` is the discount in basis points
+  Percentage,
+  /// `value` is the amount taken off the price
+  Fixed,
+  /// Unlocks a hidden ticket type without changing its price. A ticket type is hidden as long as it has an
+  /// access code.
+  Access,
+}
+
+text_enum!(PromoCodeKind {
+  Percentage => "percentage",
+  Fixed => "fixed",
+  Access => "access",
+});
+
+/// A code organisers hand out for the primary sale of an event. Codes without a `ticket_type_index` apply to every
+/// ticket type of the event. `redemptions` counts the tickets bought with the code.
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = promo_codes)]
+pub struct PromoCode {
+  pub promo_code_id: i64,
+  pub event_id: String,
+  pub code: String,
+  pub kind: PromoCodeKind,
+  pub value: i64,
+  pub ticket_type_index: Option<i16>,
+  pub max_redemptions: Option<i32>,
+  pub redemptions: i32,
+  pub valid_from: Option<NaiveDateTime>,
+  pub valid_until: Option<NaiveDateTime>,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize, Clone, Debug)]
+#[diesel(table_name = promo_codes)]
+pub struct NewPromoCode {
+  pub event_id: String,
+  pub code: String,
+  pub kind: PromoCodeKind,
+  pub value: i64,
+  pub ticket_type_index: Option<i16>,
+  pub max_redemptions: Option<i32>,
+  pub valid_from: Option<NaiveDateTime>,
+  pub valid_until: Option<NaiveDateTime>,
+}
+
+impl NewPromoCode {
+  pub fn validate(&self) -> Result<(), Error> {
+    if normalize_code(&self.code).is_empty() {
+      return Err(Error::InvalidPromoCode("code must not be empty".to_string()))
+    }
+
+    if self.value < 0 {
+      return Err(Error::InvalidPromoCode("value must not be negative".to_string()))
+    }
+
+    if self.kind == PromoCodeKind::Percentage && self.value > 10_000 {
+      return Err(Error::InvalidPromoCode("percentage must be between 0 and 10000 basis points".to_string()))
+    }
+
+    if self.kind == PromoCodeKind::Access && self.ticket_type_index.is_none() {
+      return Err(Error::InvalidPromoCode("access codes need a ticket type".to_string()))
+    }
+
+    if self.max_redemptions.map_or(false, |max_redemptions| max_redemptions < 0) {
+      return Err(Error::InvalidPromoCode("max_redemptions must not be negative".to_string()))
+    }
+
+    if let (Some(valid_from), Some(valid_until)) = (self.valid_from, self.valid_until) {
+      if valid_until <= valid_from {
+        return Err(Error::InvalidPromoCode("valid_until must be after valid_from".to_string()))
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// The price of a single ticket of a ticket type at the time of the quote, with the promo code applied if any
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceQuote {
+  pub event_id: String,
+  pub ticket_type_index: i16,
+  /// The price given by the ticket type's `SaleType`
+  pub base_price: u64,
+  pub price: u64,
+  pub promo_code: Option<PromoCode>,
+}
+
+/// Codes are case insensitive and stored upper case
+pub fn normalize_code(code: &str) -> String {
+  code.trim().to_uppercase()
+}
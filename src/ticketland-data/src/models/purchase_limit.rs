@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use diesel::prelude::*;
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::purchase_limits;
+
+/// The most tickets a single account can get in the primary sale, either across the whole event or, when
+/// `ticket_type_index` is set, for one ticket type. Owned CNTs and active seat holds both count.
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = purchase_limits)]
+pub struct PurchaseLimit {
+  pub purchase_limit_id: i64,
+  pub event_id: String,
+  pub ticket_type_index: Option<i16>,
+  pub max_tickets: i32,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = purchase_limits)]
+pub struct NewPurchaseLimit {
+  pub event_id: String,
+  pub ticket_type_index: Option<i16>,
+  pub max_tickets: i32,
+}
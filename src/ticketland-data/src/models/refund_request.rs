@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::Text,
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::refund_requests;
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundStatus {
+  Pending,
+  Approved,
+  Rejected,
+  Completed,
+}
+
+text_enum!(RefundStatus {
+  Pending => "pending",
+  Approved => "approved",
+  Rejected => "rejected",
+  Completed => "completed",
+});
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundReason {
+  /// Asked for by the owner of the CNT
+  Requested,
+  /// Created for every CNT when the event is cancelled. These are approved right away
+  EventCancelled,
+}
+
+text_enum!(RefundReason {
+  Requested => "requested",
+  EventCancelled => "event_cancelled",
+});
+
+/// A refund of a CNT. Requests go from pending to approved or rejected; approved ones are completed once the
+/// money is paid back, which deletes the CNT and puts its seat back on sale.
+#[derive(Queryable, QueryableByName, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = refund_requests)]
+pub struct RefundRequest {
+  pub refund_id: i64,
+  pub event_id: String,
+  pub seat_index: i32,
+  pub cnt_sui_address: Option<String>,
+  pub account_id: String,
+  pub reason: RefundReason,
+  pub status: RefundStatus,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+  pub resolved_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = refund_requests)]
+pub struct NewRefundRequest {
+  pub event_id: String,
+  pub seat_index: i32,
+  pub cnt_sui_address: Option<String>,
+  pub account_id: String,
+  pub reason: RefundReason,
+  pub status: RefundStatus,
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use diesel::prelude::*;
+use chrono::NaiveDateTime;
+use crate::{
+  error::Error,
+  schema::resale_policies,
+};
+
+/// Secondary market rules of an event. Events without a policy have no cap and no royalty.
+#[derive(Insertable, Queryable, AsChangeset, Serialize, Deserialize, Clone, Default, Debug)]
+#[diesel(table_name = resale_policies)]
+pub struct ResalePolicy {
+  pub event_id: String,
+  /// Absolute maximum resale price
+  pub max_price: Option<i64>,
+  /// Maximum resale price as a percentage of the ticket type's face value e.g. 120 allows a 20% markup
+  pub max_price_percent: Option<i32>,
+  /// Organiser royalty on every secondary sale in basis points
+  pub royalty_bps: i32,
+  pub created_at: Option<NaiveDateTime>,
+}
+
+impl ResalePolicy {
+  pub fn validate(&self) -> Result<(), Error> {
+    if self.max_price.map_or(false, |max_price| max_price < 0) {
+      return Err(Error::InvalidResalePolicy("max_price must not be negative".to_string()))
+    }
+
+    if self.max_price_percent.map_or(false, |percent| percent < 0) {
+      return Err(Error::InvalidResalePolicy("max_price_percent must not be negative".to_string()))
+    }
+
+    if !(0..=10_000).contains(&self.royalty_bps) {
+      return Err(Error::InvalidResalePolicy("royalty_bps must be between 0 and 10000".to_string()))
+    }
+
+    Ok(())
+  }
+
+  /// The highest resale price allowed for a ticket with the given face value. If both caps are set the lower
+  /// one applies. None means there is no cap.
+  pub fn max_price(&self, face_value: u64) -> Option<i64> {
+    let percent_cap = self.max_price_percent.map(|percent| {
+      (face_value as i128 * percent as i128 / 100).min(i64::MAX as i128) as i64
+    });
+
+    match (self.max_price, percent_cap) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b),
+    }
+  }
+
+  /// Splits a sale price into the organiser royalty and the seller proceeds. The royalty is rounded down.
+  pub fn split(&self, price: i64) -> (i64, i64) {
+    let royalty = (price as i128 * self.royalty_bps as i128 / 10_000) as i64;
+    (royalty, price - royalty)
+  }
+}
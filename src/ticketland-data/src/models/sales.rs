@@ -20,3 +20,57 @@ pub struct AverageSalesPrice {
   #[diesel(sql_type = sql_types::Numeric)]
   avg: BigDecimal,
 }
+
+/// Primary sales of a ticket type in a time bin
+#[derive(QueryableByName, Serialize)]
+pub struct PrimarySalesData {
+  #[diesel(sql_type = sql_types::SmallInt)]
+  pub ticket_type_index: i16,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub count: i64,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  pub timestamp: NaiveDateTime,
+}
+
+/// Primary sale revenue of all the ticket types sold with the same `SaleType`. `sale_type` is the variant name,
+/// e.g. `FixedPrice`. Tickets whose price is not known are counted in `tickets_sold` and `unpriced_tickets` but not
+/// in `gross_revenue`.
+#[derive(QueryableByName, Serialize)]
+pub struct SaleTypeRevenue {
+  #[diesel(sql_type = sql_types::Text)]
+  pub sale_type: String,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub tickets_sold: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub unpriced_tickets: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub gross_revenue: i64,
+}
+
+/// How much of a ticket type has been sold. `sell_through` is `sold` as a percentage of `n_tickets`.
+#[derive(QueryableByName, Serialize)]
+pub struct SellThrough {
+  #[diesel(sql_type = sql_types::SmallInt)]
+  pub ticket_type_index: i16,
+  #[diesel(sql_type = sql_types::VarChar)]
+  pub ticket_type_name: String,
+  #[diesel(sql_type = sql_types::Integer)]
+  pub n_tickets: i32,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub sold: i64,
+  #[diesel(sql_type = sql_types::Double)]
+  pub sell_through: f64,
+}
+
+/// Secondary market volume in a time bin
+#[derive(QueryableByName, Serialize)]
+pub struct ResaleVolume {
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub count: i64,
+  #[diesel(sql_type = sql_types::Timestamptz)]
+  pub timestamp: NaiveDateTime,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub volume: i64,
+  #[diesel(sql_type = sql_types::BigInt)]
+  pub royalties: i64,
+}
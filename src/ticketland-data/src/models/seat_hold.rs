@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use diesel::prelude::*;
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::seat_holds;
+
+/// A seat reserved for an account during checkout. The hold stops counting once `expires_at` has passed.
+#[derive(Insertable, Queryable, QueryableByName, Serialize, Deserialize, Clone, Default)]
+#[diesel(table_name = seat_holds)]
+pub struct SeatHold {
+  pub event_id: String,
+  pub seat_index: i32,
+  pub ticket_type_index: i16,
+  pub account_id: String,
+  pub created_at: Option<NaiveDateTime>,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub expires_at: NaiveDateTime,
+}
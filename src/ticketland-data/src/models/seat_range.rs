@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 use diesel::prelude::*;
-use crate::schema::seat_ranges;
+use crate::{
+  error::Error,
+  schema::seat_ranges,
+};
+use super::ticket_type::NewTicketType;
 
-#[derive(Insertable, Queryable, AsChangeset, QueryableByName, Serialize, Deserialize, Clone, Default)]
+#[derive(Insertable, Queryable, AsChangeset, QueryableByName, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 #[diesel(table_name = seat_ranges)]
 pub struct SeatRange {
   event_id: String,
@@ -10,3 +14,121 @@ pub struct SeatRange {
   pub l: i32,
   pub r: i32,
 }
+
+/// Seats `l..r` i.e. `l` is included and `r` is not
+impl SeatRange {
+  pub fn new(event_id: String, ticket_type_index: i16, l: i32, r: i32) -> Self {
+    Self {event_id, ticket_type_index, l, r}
+  }
+
+  pub fn contains(&self, seat_index: i32) -> bool {
+    self.l <= seat_index && seat_index < self.r
+  }
+
+  pub fn len(&self) -> i32 {
+    (self.r - self.l).max(0)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn seats(&self) -> std::ops::Range<i32> {
+    self.l..self.r
+  }
+
+  pub fn is_of(&self, ticket_type: &NewTicketType) -> bool {
+    self.event_id == ticket_type.event_id && self.ticket_type_index == ticket_type.ticket_type_index
+  }
+}
+
+/// Validates the seat ranges an event will have once the ranges of the given ticket types are replaced by
+/// `seat_ranges`, and the `existing` ranges of its other ticket types are kept: ranges are not empty, ranges of
+/// the same event never overlap and the ranges of each of the given ticket types add up to exactly its `n_tickets`.
+pub fn validate_seat_ranges(
+  ticket_types: &[NewTicketType],
+  seat_ranges: &[SeatRange],
+  existing: &[SeatRange],
+) -> Result<(), Error> {
+  let mut all_ranges = existing
+  .iter()
+  .filter(|seat_range| !ticket_types.iter().any(|ticket_type| seat_range.is_of(ticket_type)))
+  .cloned()
+  .collect::<Vec<_>>();
+
+  for seat_range in seat_ranges {
+    if !all_ranges.contains(seat_range) {
+      all_ranges.push(seat_range.clone());
+    }
+  }
+
+  if let Some(seat_range) = all_ranges.iter().find(|seat_range| seat_range.is_empty()) {
+    return Err(Error::InvalidSeatRanges(format!("range [{}, {}) is empty", seat_range.l, seat_range.r)))
+  }
+
+  all_ranges.sort_by(|a, b| (&a.event_id, a.l).cmp(&(&b.event_id, b.l)));
+
+  for pair in all_ranges.windows(2) {
+    let (prev, next) = (&pair[0], &pair[1]);
+
+    if prev.event_id == next.event_id && next.l < prev.r {
+      return Err(Error::InvalidSeatRanges(format!(
+        "range [{}, {}) of ticket type {} overlaps with range [{}, {}) of ticket type {}",
+        prev.l, prev.r, prev.ticket_type_index, next.l, next.r, next.ticket_type_index,
+      )))
+    }
+  }
+
+  for seat_range in seat_ranges {
+    let has_ticket_type = ticket_types.iter().any(|ticket_type| seat_range.is_of(ticket_type));
+
+    if !has_ticket_type {
+      return Err(Error::InvalidSeatRanges(format!("unknown ticket type {}", seat_range.ticket_type_index)))
+    }
+  }
+
+  for ticket_type in ticket_types {
+    let n_seats = all_ranges
+    .iter()
+    .filter(|seat_range| seat_range.is_of(ticket_type))
+    .map(|seat_range| seat_range.len() as i64)
+    .sum::<i64>();
+
+    if n_seats != ticket_type.n_tickets as i64 {
+      return Err(Error::InvalidSeatRanges(format!(
+        "ticket type {} has {} seats but n_tickets is {}",
+        ticket_type.ticket_type_index, n_seats, ticket_type.n_tickets,
+      )))
+    }
+  }
+
+  Ok(())
+}
+
+/// Fails if a seat that is already issued or held, given as `(event_id, ticket_type_index, seat_index)`, belongs
+/// to one of the ticket types but falls outside its new `seat_ranges`
+pub fn validate_allocated_seats(
+  ticket_types: &[NewTicketType],
+  seat_ranges: &[SeatRange],
+  allocated: &[(String, i16, i32)],
+) -> Result<(), Error> {
+  for (event_id, ticket_type_index, seat_index) in allocated {
+    let Some(ticket_type) = ticket_types
+    .iter()
+    .find(|ticket_type| &ticket_type.event_id == event_id && ticket_type.ticket_type_index == *ticket_type_index)
+    else {continue};
+
+    let is_covered = seat_ranges
+    .iter()
+    .any(|seat_range| seat_range.is_of(ticket_type) && seat_range.contains(*seat_index));
+
+    if !is_covered {
+      return Err(Error::InvalidSeatRanges(format!(
+        "seat {} of ticket type {} is already allocated",
+        seat_index, ticket_type_index,
+      )))
+    }
+  }
+
+  Ok(())
+}
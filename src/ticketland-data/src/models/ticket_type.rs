@@ -78,10 +78,31 @@ pub struct ExtendedTicketType {
   #[serde(serialize_with = "to_milli_ts")]
   pub sale_end_ts: NaiveDateTime,
   pub sale_type: SaleType,
-  pub seat_range: SeatRange,
+  pub seat_ranges: Vec<SeatRange>,
   pub ticket_type_nft_details: Vec<TicketTypeNftDetail>,
 }
 
+impl ExtendedTicketType {
+  /// The joined queries repeat the same seat range and nft details once per combination, so these only add
+  /// values that are not there yet.
+  pub fn add_seat_range(&mut self, seat_range: SeatRange) {
+    if !self.seat_ranges.contains(&seat_range) {
+      self.seat_ranges.push(seat_range);
+      self.seat_ranges.sort_by_key(|seat_range| seat_range.l);
+    }
+  }
+
+  pub fn add_nft_details(&mut self, nft_detail: TicketTypeNftDetail) {
+    let exists = self.ticket_type_nft_details
+    .iter()
+    .any(|item| item.ref_name == nft_detail.ref_name);
+
+    if !exists {
+      self.ticket_type_nft_details.push(nft_detail);
+    }
+  }
+}
+
 
 impl From<TicketType> for ExtendedTicketType {
   fn from(ticket_type: TicketType) -> Self {
@@ -95,7 +116,7 @@ impl From<TicketType> for ExtendedTicketType {
       sale_start_ts: ticket_type.sale_start_ts,
       sale_end_ts: ticket_type.sale_end_ts,
       sale_type: ticket_type.sale_type,
-      seat_range: SeatRange::default(),
+      seat_ranges: vec![],
       ticket_type_nft_details: vec![],
     }
   }
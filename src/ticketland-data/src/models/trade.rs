@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use diesel::prelude::*;
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::trades;
+
+/// A CNT sold on the secondary market, recorded when a listing or an offer is filled
+#[derive(Queryable, QueryableByName, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = trades)]
+pub struct Trade {
+  pub trade_id: i64,
+  pub event_id: String,
+  pub ticket_type_index: i16,
+  pub cnt_sui_address: String,
+  pub listing_id: Option<String>,
+  pub offer_id: Option<String>,
+  pub seller_account_id: String,
+  pub buyer_account_id: String,
+  pub price: i64,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+  /// The organiser's cut of `price` according to the event's resale policy
+  pub royalty: i64,
+  /// What the seller is owed, i.e. `price - royalty`
+  pub seller_proceeds: i64,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = trades)]
+pub struct NewTrade {
+  pub event_id: String,
+  pub ticket_type_index: i16,
+  pub cnt_sui_address: String,
+  pub listing_id: Option<String>,
+  pub offer_id: Option<String>,
+  pub seller_account_id: String,
+  pub buyer_account_id: String,
+  pub price: i64,
+  pub royalty: i64,
+  pub seller_proceeds: i64,
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use diesel::{
+  prelude::*,
+  sql_types::Text,
+  FromSqlRow,
+  AsExpression,
+};
+use chrono::{
+  NaiveDateTime,
+  naive::serde::ts_milliseconds::serialize as to_milli_ts,
+};
+use crate::schema::waitlist_entries;
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitlistStatus {
+  /// In the queue
+  Waiting,
+  /// A seat is held for the account until `offer_expires_at`
+  Offered,
+  /// The offered seat was turned into a CNT
+  Accepted,
+  /// The offer was not accepted in time
+  Expired,
+  /// The account left the queue
+  Left,
+}
+
+text_enum!(WaitlistStatus {
+  Waiting => "waiting",
+  Offered => "offered",
+  Accepted => "accepted",
+  Expired => "expired",
+  Left => "left",
+});
+
+/// A place in the FIFO queue of a sold out ticket type. Freed seats are offered to the oldest waiting entry by
+/// holding the seat for the account (see `hold_seats`) for the acceptance window.
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = waitlist_entries)]
+pub struct WaitlistEntry {
+  pub waitlist_entry_id: i64,
+  pub event_id: String,
+  pub ticket_type_index: i16,
+  pub account_id: String,
+  pub status: WaitlistStatus,
+  pub seat_index: Option<i32>,
+  pub offer_expires_at: Option<NaiveDateTime>,
+  #[serde(serialize_with = "to_milli_ts")]
+  pub created_at: NaiveDateTime,
+  pub resolved_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = waitlist_entries)]
+pub struct NewWaitlistEntry {
+  pub event_id: String,
+  pub ticket_type_index: i16,
+  pub account_id: String,
+}
+
+/// An active entry together with the number of waiting entries ahead of it plus one. Entries with an open offer
+/// are at position 0.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WaitlistPosition {
+  pub entry: WaitlistEntry,
+  pub position: i64,
+}
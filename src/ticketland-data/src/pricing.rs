@@ -0,0 +1,218 @@
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use crate::models::{
+  ticket_type::SaleType,
+  promo_code::{PromoCode, PromoCodeKind},
+};
+
+/// The price of a ticket type from `ts` onwards, until the next step of the schedule
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceStep {
+  pub ts: NaiveDateTime,
+  pub price: u64,
+}
+
+impl SaleType {
+  /// The price of a ticket at `now` for a sale that starts at `sale_start_ts`.
+  ///
+  /// A DutchAuction starts at `start_price` and drops linearly towards `end_price` in discrete steps,
+  /// one every `drop_interval` minutes, reaching `end_price` after `curve_length` minutes. Each step is
+  /// rounded towards `start_price`. This is the same calculation as the `ticket_type_current_price`
+  /// SQL function used when filtering and sorting events by price, so both must be kept in sync.
+  pub fn price_at(&self, sale_start_ts: NaiveDateTime, now: NaiveDateTime) -> u64 {
+    match *self {
+      Self::Free {} => 0,
+      Self::FixedPrice {price} | Self::Refundable {price} => price,
+      Self::DutchAuction {start_price, end_price, curve_length, drop_interval} => {
+        let elapsed = (now - sale_start_ts).num_minutes().max(0);
+        let total_drops = total_drops(curve_length, drop_interval);
+
+        if total_drops == 0 {
+          return if elapsed < curve_length as i64 {start_price} else {end_price}
+        }
+
+        let drops = (elapsed / drop_interval as i64).min(total_drops);
+        dutch_auction_step_price(start_price, end_price, drops, total_drops)
+      },
+    }
+  }
+
+  /// The price of a ticket at the moment the sale starts
+  pub fn face_value(&self) -> u64 {
+    match *self {
+      Self::Free {} => 0,
+      Self::FixedPrice {price} | Self::Refundable {price} => price,
+      Self::DutchAuction {start_price, ..} => start_price,
+    }
+  }
+
+  /// Every price the ticket type will have, in chronological order. Only DutchAuction has more than one step;
+  /// its last step is always `end_price`.
+  pub fn price_schedule(&self, sale_start_ts: NaiveDateTime) -> Vec<PriceStep> {
+    match *self {
+      Self::DutchAuction {start_price, end_price, curve_length, drop_interval} => {
+        let total_drops = total_drops(curve_length, drop_interval);
+
+        if total_drops == 0 {
+          let mut schedule = vec![];
+
+          if curve_length > 0 {
+            schedule.push(PriceStep {ts: sale_start_ts, price: start_price});
+          }
+
+          schedule.push(PriceStep {
+            ts: sale_start_ts + Duration::minutes(curve_length as i64),
+            price: end_price,
+          });
+
+          return schedule
+        }
+
+        (0..=total_drops)
+        .map(|drops| PriceStep {
+          ts: sale_start_ts + Duration::minutes(drops * drop_interval as i64),
+          price: dutch_auction_step_price(start_price, end_price, drops, total_drops),
+        })
+        .collect()
+      },
+      _ => vec![PriceStep {ts: sale_start_ts, price: self.price_at(sale_start_ts, sale_start_ts)}],
+    }
+  }
+}
+
+impl PromoCode {
+  /// The price after the discount of the code, never below zero. Percentage discounts are rounded down, like
+  /// resale royalties. Access codes leave the price untouched.
+  pub fn apply(&self, price: u64) -> u64 {
+    let value = self.value.max(0) as u64;
+
+    match self.kind {
+      PromoCodeKind::Percentage => {
+        let discount = (price as u128 * value.min(10_000) as u128 / 10_000) as u64;
+        price - discount
+      },
+      PromoCodeKind::Fixed => price.saturating_sub(value),
+      PromoCodeKind::Access => price,
+    }
+  }
+
+  /// Whether the code can be used for the ticket type at `now`, ignoring the redemption limit
+  pub fn is_applicable(&self, ticket_type_index: i16, now: NaiveDateTime) -> bool {
+    self.ticket_type_index.map_or(true, |index| index == ticket_type_index)
+    && self.valid_from.map_or(true, |valid_from| valid_from <= now)
+    && self.valid_until.map_or(true, |valid_until| now < valid_until)
+  }
+}
+
+fn total_drops(curve_length: u16, drop_interval: u16) -> i64 {
+  if drop_interval == 0 {
+    0
+  } else {
+    (curve_length / drop_interval) as i64
+  }
+}
+
+fn dutch_auction_step_price(start_price: u64, end_price: u64, drops: i64, total_drops: i64) -> u64 {
+  let (start_price, end_price) = (start_price as i128, end_price as i128);
+  // Integer division truncates towards zero, i.e. towards start_price, same as Postgres div()
+  let price = start_price - (start_price - end_price) * drops as i128 / total_drops as i128;
+
+  price as u64
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{Duration, NaiveDate, NaiveDateTime};
+  use proptest::prelude::*;
+  use crate::models::ticket_type::SaleType;
+
+  fn sale_start_ts() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 8, 1).and_then(|date| date.and_hms_opt(18, 0, 0)).unwrap()
+  }
+
+  fn dutch_auction() -> impl Strategy<Value = SaleType> {
+    (0..1_000_000u64, 0..1_000_000u64, any::<u16>(), any::<u16>()).prop_map(
+      |(start_price, end_price, curve_length, drop_interval)| SaleType::DutchAuction {
+        start_price,
+        end_price,
+        curve_length,
+        drop_interval,
+      },
+    )
+  }
+
+  fn sale_type() -> impl Strategy<Value = SaleType> {
+    prop_oneof![
+      Just(SaleType::Free {}),
+      any::<u64>().prop_map(|price| SaleType::FixedPrice {price}),
+      any::<u64>().prop_map(|price| SaleType::Refundable {price}),
+      dutch_auction(),
+    ]
+  }
+
+  /// Offsets in seconds from the sale start, including before it and well past the longest curve
+  fn offset() -> impl Strategy<Value = i64> {
+    -86_400..(u16::MAX as i64 + 1_440) * 60
+  }
+
+  proptest! {
+    #[test]
+    fn price_at_moves_monotonically_from_start_to_end_price(
+      sale_type in dutch_auction(),
+      a in offset(),
+      b in offset(),
+    ) {
+      let SaleType::DutchAuction {start_price, end_price, curve_length, ..} = sale_type else {unreachable!()};
+      let (earlier, later) = (a.min(b), a.max(b));
+      let at = |offset| sale_type.price_at(sale_start_ts(), sale_start_ts() + Duration::seconds(offset));
+
+      let (earlier_price, later_price) = (at(earlier), at(later));
+      prop_assert!(earlier_price.min(later_price) >= start_price.min(end_price));
+      prop_assert!(earlier_price.max(later_price) <= start_price.max(end_price));
+
+      if start_price >= end_price {
+        prop_assert!(earlier_price >= later_price);
+      } else {
+        prop_assert!(earlier_price <= later_price);
+      }
+
+      if earlier <= 0 && curve_length > 0 {
+        prop_assert_eq!(earlier_price, start_price);
+      }
+
+      if later >= curve_length as i64 * 60 {
+        prop_assert_eq!(later_price, end_price);
+      }
+    }
+
+    #[test]
+    fn other_sale_types_have_a_constant_price(sale_type in sale_type(), offset in offset()) {
+      prop_assume!(!matches!(sale_type, SaleType::DutchAuction {..}));
+
+      let price = sale_type.price_at(sale_start_ts(), sale_start_ts() + Duration::seconds(offset));
+      prop_assert_eq!(price, sale_type.face_value());
+    }
+
+    #[test]
+    fn price_schedule_agrees_with_price_at(sale_type in sale_type(), offset in offset()) {
+      let schedule = sale_type.price_schedule(sale_start_ts());
+
+      prop_assert!(!schedule.is_empty());
+      prop_assert!(schedule.windows(2).all(|steps| steps[0].ts < steps[1].ts));
+      prop_assert_eq!(schedule[0].ts, sale_start_ts());
+
+      for step in &schedule {
+        prop_assert_eq!(sale_type.price_at(sale_start_ts(), step.ts), step.price);
+      }
+
+      // Between steps the price is the one of the last step that has started
+      let now = sale_start_ts() + Duration::seconds(offset.max(0));
+      let current = schedule.iter().rev().find(|step| step.ts <= now).unwrap();
+      prop_assert_eq!(sale_type.price_at(sale_start_ts(), now), current.price);
+
+      if let SaleType::DutchAuction {end_price, ..} = sale_type {
+        prop_assert_eq!(schedule.last().unwrap().price, end_price);
+      }
+    }
+  }
+}
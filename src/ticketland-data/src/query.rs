@@ -0,0 +1,175 @@
+use crate::cursor::Cursor;
+use diesel::{
+  pg::Pg,
+  query_builder::{BoxedSqlQuery, SqlQuery},
+  serialize::ToSql,
+  sql_query,
+  sql_types::{HasSqlType, Integer, Text, Timestamptz},
+};
+use eyre::{Result, eyre};
+
+pub type BoxedQuery<'a> = BoxedSqlQuery<'a, Pg, SqlQuery>;
+
+type Filter<'a> = Box<dyn FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a> + Send + 'a>;
+
+/// Builds a raw SQL query where every caller supplied value is sent as a bind parameter.
+/// `bind` appends the next `$n` placeholder to the SQL text, so fragments are written in
+/// the same order they appear in the final statement.
+pub struct QueryBuilder<'a> {
+  query: BoxedQuery<'a>,
+  n_binds: usize,
+}
+
+impl<'a> QueryBuilder<'a> {
+  pub fn new(sql: &str) -> Self {
+    Self {
+      query: sql_query(sql).into_boxed(),
+      n_binds: 0,
+    }
+  }
+
+  pub fn sql(mut self, sql: &str) -> Self {
+    self.query = self.query.sql(sql);
+    self
+  }
+
+  pub fn bind<ST, T>(mut self, value: T) -> Self
+  where
+    Pg: HasSqlType<ST>,
+    T: ToSql<ST, Pg> + Send + 'a,
+    ST: Send + 'a,
+  {
+    self.n_binds += 1;
+    self.query = self.query.sql(format!("${}", self.n_binds)).bind::<ST, _>(value);
+    self
+  }
+
+  /// Appends all the filters joined with AND. An empty list is rendered as `TRUE`
+  pub fn filters(mut self, filters: Filters<'a>) -> Self {
+    if filters.0.is_empty() {
+      return self.sql("TRUE")
+    }
+
+    for (i, filter) in filters.0.into_iter().enumerate() {
+      if i > 0 {
+        self = self.sql(" AND ");
+      }
+
+      self = filter(self.sql("(")).sql(")");
+    }
+
+    self
+  }
+
+  pub fn build(self) -> BoxedQuery<'a> {
+    self.query
+  }
+}
+
+/// A list of conditions that are later rendered in the WHERE clause of a `QueryBuilder`.
+/// Column names are `&'static str` so only values can ever come from the caller.
+#[derive(Default)]
+pub struct Filters<'a>(Vec<Filter<'a>>);
+
+impl<'a> Filters<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn push<F>(&mut self, filter: F)
+  where
+    F: FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a> + Send + 'a,
+  {
+    self.0.push(Box::new(filter));
+  }
+
+  /// Pushes `column <op> value` e.g. `cmp::<Timestamptz, _>("events.start_date", ">=", ts)`
+  pub fn cmp<ST, T>(&mut self, column: &'static str, op: &'static str, value: T)
+  where
+    Pg: HasSqlType<ST>,
+    T: ToSql<ST, Pg> + Send + 'a,
+    ST: Send + 'a,
+  {
+    self.push(move |query| {
+      query
+      .sql(&format!("{} {} ", column, op))
+      .bind::<ST, _>(value)
+    });
+  }
+
+  pub fn eq<ST, T>(&mut self, column: &'static str, value: T)
+  where
+    Pg: HasSqlType<ST>,
+    T: ToSql<ST, Pg> + Send + 'a,
+    ST: Send + 'a,
+  {
+    self.cmp::<ST, T>(column, "=", value);
+  }
+
+  /// Case insensitive substring match. LIKE wildcards in the value are matched literally
+  pub fn contains(&mut self, column: &'static str, value: &str) {
+    let pattern = format!("%{}%", escape_like(value));
+
+    self.push(move |query| {
+      query
+      .sql(&format!("{} ILIKE ", column))
+      .bind::<Text, _>(pattern)
+    });
+  }
+
+  /// Keyset pagination condition `(ts_column, id_column) > (cursor.ts, cursor.id)`. No-op if there is no cursor
+  pub fn after(&mut self, ts_column: &'static str, id_column: &'static str, cursor: Option<Cursor>) {
+    if let Some(Cursor {ts, id, ..}) = cursor {
+      self.push(move |query| {
+        query
+        .sql(&format!("({}, {}) > (", ts_column, id_column))
+        .bind::<Timestamptz, _>(ts)
+        .sql(", ")
+        .bind::<Text, _>(id)
+        .sql(")")
+      });
+    }
+  }
+
+  /// Keyset pagination condition `(ts_column, id_column, index_column) > (cursor.ts, cursor.id, cursor.index)`.
+  /// No-op if there is no cursor and an error if the cursor has no index.
+  pub fn after_index(
+    &mut self,
+    ts_column: &'static str,
+    id_column: &'static str,
+    index_column: &'static str,
+    cursor: Option<Cursor>,
+  ) -> Result<()> {
+    if let Some(Cursor {ts, id, index}) = cursor {
+      let index = index.ok_or_else(|| eyre!("invalid cursor"))?;
+
+      self.push(move |query| {
+        query
+        .sql(&format!("({}, {}, {}) > (", ts_column, id_column, index_column))
+        .bind::<Timestamptz, _>(ts)
+        .sql(", ")
+        .bind::<Text, _>(id)
+        .sql(", ")
+        .bind::<Integer, _>(index)
+        .sql(")")
+      });
+    }
+
+    Ok(())
+  }
+}
+
+/// Escapes the LIKE wildcards using the default `\` escape character
+pub fn escape_like(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+
+  for c in value.chars() {
+    if matches!(c, '\\' | '%' | '_') {
+      escaped.push('\\');
+    }
+
+    escaped.push(c);
+  }
+
+  escaped
+}
@@ -1,20 +1,98 @@
-use chrono::NaiveDateTime;
-use diesel::{prelude::*, sql_query};
-use eyre::Result;
-use diesel_async::RunQueryDsl;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{prelude::*, dsl};
+use eyre::{Report, Result};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::cnt_transfer::account_email,
   models::{
-    account::Account,
+    account::{Account, AccountExport},
+    api_client::ApiClient,
     canva_account::CanvaAccount,
+    canva_design::CanvaDesign,
+    check_in::CheckInAttempt,
+    cnt::CNT,
+    cnt_ownership_event::CntOwnershipEvent,
+    cnt_transfer::{CntTransfer, TransferStatus},
+    event::Event,
+    listing::Listing,
+    offer::Offer,
+    refund_request::RefundRequest,
+    seat_hold::SeatHold,
+    stripe_account::StripeAccount,
+    stripe_customer::StripeCustomer,
+    trade::Trade,
+    waitlist::WaitlistEntry,
   },
   schema::{
     accounts::dsl::*,
     canva_accounts::dsl::*,
+    api_clients::dsl::{
+      self as api_clients_dsl,
+      api_clients,
+    },
+    canva_accounts::dsl as canva_accounts_dsl,
+    canva_designs::dsl::{
+      self as canva_designs_dsl,
+      canva_designs,
+    },
+    check_in_attempts::dsl::{
+      self as check_in_attempts_dsl,
+      check_in_attempts,
+    },
+    cnt_ownership_events::dsl::{
+      self as cnt_ownership_events_dsl,
+      cnt_ownership_events,
+    },
+    cnt_transfers::dsl::{
+      self as cnt_transfers_dsl,
+      cnt_transfers,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    events::dsl::{
+      self as events_dsl,
+      events,
+    },
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    offers::dsl::{
+      self as offers_dsl,
+      offers,
+    },
+    refund_requests::dsl::{
+      self as refund_requests_dsl,
+      refund_requests,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+    stripe_accounts::dsl::{
+      self as stripe_accounts_dsl,
+      stripe_accounts,
+    },
+    stripe_customers::dsl::{
+      self as stripe_customers_dsl,
+      stripe_customers,
+    },
+    trades::dsl::{
+      self as trades_dsl,
+      trades,
+    },
+    waitlist_entries::dsl::{
+      self as waitlist_entries_dsl,
+      waitlist_entries,
+    },
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_account(&mut self, account: Account) -> Result<()> {
     diesel::insert_into(accounts)
     .values(&account)
@@ -64,39 +142,323 @@ impl PostgresConnection {
     user_id: String,
     delete_request_ts: Option<NaiveDateTime>,
   ) -> Result<()> {
-    let query = sql_query(format!(
-      "
-      UPDATE accounts
-      SET delete_request_at = {}
-      WHERE uid = '{}';
-      ",
-      delete_request_ts.map_or("NULL".to_string(), |ts| format!("'{}'", ts.to_string())),
-      user_id,
-    ));
-
-    query.execute(self.borrow_mut()).await?;
+    diesel::update(accounts)
+    .filter(uid.eq(user_id))
+    .set(delete_request_at.eq(delete_request_ts))
+    .execute(self.borrow_mut())
+    .await?;
 
     Ok(())
   }
 
+  /// Anonymises the account right away, see `anonymise_account`
   pub async fn delete_account(
     &mut self,
     user_id: String,
   ) -> Result<()> {
-    let query = sql_query(format!(
-      "
-      UPDATE accounts
-      SET deleted_at = now()
-      SET name = ''
-      SET email = ''
-      SET photo_url = ''
-      WHERE uid = '{}';
-      ",
-      user_id,
-    ));
-
-    query.execute(self.borrow_mut()).await?;
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      accounts
+      .filter(uid.eq(&user_id))
+      .filter(deleted_at.is_null())
+      .select(uid)
+      .for_update()
+      .first::<String>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::AccountNotFound)?;
 
-    Ok(())
+      anonymise_account(conn, &user_id).await
+    }))
+    .await
+  }
+
+  /// Anonymises every account whose deletion was requested more than `grace_period_secs` ago, one transaction per
+  /// account. Accounts being processed by a concurrent job are skipped. Returns the uids of the deleted accounts.
+  pub async fn delete_expired_accounts(&mut self, grace_period_secs: i64) -> Result<Vec<String>> {
+    let requested_before = Utc::now().naive_utc() - Duration::seconds(grace_period_secs);
+    let mut deleted = vec![];
+
+    loop {
+      let user_id = self.borrow_mut()
+      .transaction::<_, Report, _>(|conn| Box::pin(async move {
+        let user_id = accounts
+        .filter(delete_request_at.le(requested_before))
+        .filter(deleted_at.is_null())
+        .select(uid)
+        .order_by(delete_request_at.asc())
+        .for_update()
+        .skip_locked()
+        .first::<String>(conn)
+        .await
+        .optional()?;
+
+        if let Some(user_id) = &user_id {
+          anonymise_account(conn, user_id).await?;
+        }
+
+        Ok(user_id)
+      }))
+      .await?;
+
+      match user_id {
+        Some(user_id) => deleted.push(user_id),
+        None => return Ok(deleted),
+      }
+    }
+  }
+
+  /// All the data stored about the account as a single document
+  pub async fn export_account(&mut self, user_id: String) -> Result<AccountExport> {
+    let conn = self.borrow_mut();
+
+    let mut account = accounts
+    .filter(uid.eq(&user_id))
+    .first::<Account>(conn)
+    .await
+    .optional()?
+    .ok_or(Error::AccountNotFound)?;
+    account.dapp_share = String::new();
+
+    let account_events = events
+    .filter(events_dsl::account_id.eq(&user_id))
+    .order_by(events_dsl::created_at.asc())
+    .load::<Event>(conn)
+    .await?;
+
+    let account_cnts = cnts
+    .filter(cnts_dsl::account_id.eq(&user_id))
+    .order_by((cnts_dsl::event_id.asc(), cnts_dsl::seat_index.asc()))
+    .load::<CNT>(conn)
+    .await?;
+
+    let account_listings = listings
+    .filter(listings_dsl::account_id.eq(&user_id))
+    .order_by(listings_dsl::created_at.asc())
+    .load::<Listing>(conn)
+    .await?;
+
+    let account_offers = offers
+    .filter(offers_dsl::account_id.eq(&user_id))
+    .order_by(offers_dsl::created_at.asc())
+    .load::<Offer>(conn)
+    .await?;
+
+    let account_trades = trades
+    .filter(trades_dsl::seller_account_id.eq(&user_id).or(trades_dsl::buyer_account_id.eq(&user_id)))
+    .order_by(trades_dsl::trade_id.asc())
+    .load::<Trade>(conn)
+    .await?;
+
+    let transfers = cnt_transfers
+    .filter(
+      cnt_transfers_dsl::sender_account_id.eq(&user_id)
+      .or(cnt_transfers_dsl::recipient_account_id.eq(&user_id))
+    )
+    .order_by(cnt_transfers_dsl::transfer_id.asc())
+    .load::<CntTransfer>(conn)
+    .await?
+    .into_iter()
+    // The recipient email of someone else's transfer is not the account's data
+    .map(|mut transfer| {
+      if transfer.sender_account_id != user_id {
+        transfer.recipient_email = None;
+      }
+      transfer
+    })
+    .collect();
+
+    let account_refund_requests = refund_requests
+    .filter(refund_requests_dsl::account_id.eq(&user_id))
+    .order_by(refund_requests_dsl::refund_id.asc())
+    .load::<RefundRequest>(conn)
+    .await?;
+
+    let account_waitlist_entries = waitlist_entries
+    .filter(waitlist_entries_dsl::account_id.eq(&user_id))
+    .order_by(waitlist_entries_dsl::waitlist_entry_id.asc())
+    .load::<WaitlistEntry>(conn)
+    .await?;
+
+    let account_seat_holds = seat_holds
+    .filter(seat_holds_dsl::account_id.eq(&user_id))
+    .order_by((seat_holds_dsl::event_id.asc(), seat_holds_dsl::seat_index.asc()))
+    .load::<SeatHold>(conn)
+    .await?;
+
+    let account_check_in_attempts = check_in_attempts
+    .filter(
+      check_in_attempts_dsl::cnt_sui_address.eq_any(
+        account_cnts.iter().filter_map(|cnt| cnt.cnt_sui_address.clone()).collect::<Vec<_>>()
+      )
+    )
+    .order_by(check_in_attempts_dsl::check_in_attempt_id.asc())
+    .load::<CheckInAttempt>(conn)
+    .await?;
+
+    let ownership_history = cnt_ownership_events
+    .filter(
+      cnt_ownership_events_dsl::from_account_id.eq(&user_id)
+      .or(cnt_ownership_events_dsl::to_account_id.eq(&user_id))
+    )
+    .order_by(cnt_ownership_events_dsl::ownership_event_id.asc())
+    .load::<CntOwnershipEvent>(conn)
+    .await?;
+
+    let account_api_clients = api_clients
+    .filter(api_clients_dsl::account_id.eq(&user_id))
+    .load::<ApiClient>(conn)
+    .await?
+    .into_iter()
+    .map(|api_client| ApiClient {client_secret: String::new(), ..api_client})
+    .collect();
+
+    let account_canva_accounts = canva_accounts
+    .filter(canva_accounts_dsl::account_id.eq(&user_id))
+    .load::<CanvaAccount>(conn)
+    .await?;
+
+    let account_canva_designs = canva_designs
+    .filter(
+      canva_designs_dsl::canva_uid.eq_any(
+        account_canva_accounts.iter().map(|canva_account| canva_account.canva_uid.clone()).collect::<Vec<_>>()
+      )
+    )
+    .order_by(canva_designs_dsl::created_at.asc())
+    .load::<CanvaDesign>(conn)
+    .await?;
+
+    let account_stripe_accounts = stripe_accounts
+    .filter(stripe_accounts_dsl::account_id.eq(&user_id))
+    .load::<StripeAccount>(conn)
+    .await?;
+
+    let account_stripe_customers = stripe_customers
+    .filter(stripe_customers_dsl::account_id.eq(&user_id))
+    .load::<StripeCustomer>(conn)
+    .await?;
+
+    Ok(AccountExport {
+      account,
+      events: account_events,
+      cnts: account_cnts,
+      listings: account_listings,
+      offers: account_offers,
+      trades: account_trades,
+      transfers,
+      refund_requests: account_refund_requests,
+      waitlist_entries: account_waitlist_entries,
+      seat_holds: account_seat_holds,
+      check_in_attempts: account_check_in_attempts,
+      ownership_history,
+      api_clients: account_api_clients,
+      canva_accounts: account_canva_accounts,
+      canva_designs: account_canva_designs,
+      stripe_accounts: account_stripe_accounts,
+      stripe_customers: account_stripe_customers,
+    })
   }
 }
+
+/// Removes the personal data of the account and everything it can still act with, and marks it as deleted.
+///
+/// The account row itself is kept, with its profile cleared and its keys replaced, since events, CNTs, trades and
+/// the ownership history reference it and belong to other accounts as much as to this one. API clients, Canva and
+/// Stripe links, seat holds and waitlist entries are deleted, open listings and offers are closed, pending
+/// transfers are cancelled or declined and the account's email is removed from the transfers it received.
+/// Must run in a transaction.
+async fn anonymise_account(conn: &mut AsyncPgConnection, user_id: &str) -> Result<()> {
+  diesel::delete(api_clients)
+  .filter(api_clients_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  // Deletes their designs too
+  diesel::delete(canva_accounts)
+  .filter(canva_accounts_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  diesel::delete(stripe_accounts)
+  .filter(stripe_accounts_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  diesel::delete(stripe_customers)
+  .filter(stripe_customers_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  diesel::delete(seat_holds)
+  .filter(seat_holds_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  diesel::delete(waitlist_entries)
+  .filter(waitlist_entries_dsl::account_id.eq(user_id))
+  .execute(conn)
+  .await?;
+
+  diesel::update(listings)
+  .filter(listings_dsl::account_id.eq(user_id))
+  .filter(listings_dsl::is_open.eq(true))
+  .set((listings_dsl::is_open.eq(false), listings_dsl::closed_at.eq(dsl::now)))
+  .execute(conn)
+  .await?;
+
+  diesel::update(offers)
+  .filter(offers_dsl::account_id.eq(user_id))
+  .filter(offers_dsl::is_open.eq(true))
+  .set((offers_dsl::is_open.eq(false), offers_dsl::closed_at.eq(dsl::now)))
+  .execute(conn)
+  .await?;
+
+  diesel::update(cnt_transfers)
+  .filter(cnt_transfers_dsl::sender_account_id.eq(user_id))
+  .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+  .set((cnt_transfers_dsl::status.eq(TransferStatus::Cancelled), cnt_transfers_dsl::resolved_at.eq(dsl::now)))
+  .execute(conn)
+  .await?;
+
+  // Transfers sent to the account's email are tied to the account instead, so the email can be removed
+  if let Some(account_email) = account_email(conn, user_id).await? {
+    diesel::update(cnt_transfers)
+    .filter(cnt_transfers_dsl::recipient_account_id.is_null())
+    .filter(cnt_transfers_dsl::recipient_email.eq(account_email))
+    .set(cnt_transfers_dsl::recipient_account_id.eq(user_id))
+    .execute(conn)
+    .await?;
+  }
+
+  diesel::update(cnt_transfers)
+  .filter(cnt_transfers_dsl::recipient_account_id.eq(user_id))
+  .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+  .set((cnt_transfers_dsl::status.eq(TransferStatus::Declined), cnt_transfers_dsl::resolved_at.eq(dsl::now)))
+  .execute(conn)
+  .await?;
+
+  diesel::update(cnt_transfers)
+  .filter(cnt_transfers_dsl::recipient_account_id.eq(user_id))
+  .filter(cnt_transfers_dsl::recipient_email.is_not_null())
+  .set(cnt_transfers_dsl::recipient_email.eq(None::<String>))
+  .execute(conn)
+  .await?;
+
+  // dapp_share and pubkey are unique, so they cannot all be blanked to the same value
+  let tombstone = format!("deleted:{user_id}");
+
+  diesel::update(accounts)
+  .filter(uid.eq(user_id))
+  .set((
+    deleted_at.eq(dsl::now),
+    dapp_share.eq(&tombstone),
+    pubkey.eq(&tombstone),
+    name.eq(None::<String>),
+    email.eq(None::<String>),
+    photo_url.eq(None::<String>),
+  ))
+  .execute(conn)
+  .await?;
+
+  Ok(())
+}
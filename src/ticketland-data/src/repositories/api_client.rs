@@ -2,14 +2,14 @@ use diesel::prelude::*;
 use eyre::Result;
 use diesel_async::RunQueryDsl;
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
   models::{
     api_client::ApiClient,
   },
   schema::api_clients::dsl::*,
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn read_api_client(&mut self, id: String) -> Result<ApiClient> {
     Ok(
       api_clients
@@ -2,7 +2,7 @@ use diesel::prelude::*;
 use eyre::Result;
 use diesel_async::RunQueryDsl;
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
   models::{
     canva_design::CanvaDesign,
   },
@@ -18,7 +18,7 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_ticket_design(&mut self, design: CanvaDesign) -> Result<()> {
     diesel::insert_into(canva_designs)
     .values(&design)
@@ -0,0 +1,203 @@
+use diesel::{
+  dsl,
+  prelude::*,
+  sql_types::{BigInt, Text},
+};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  query::QueryBuilder,
+  repositories::{
+    cnt_history::record_ownership_events,
+    event_status::read_status,
+    refund_request::has_open_refund,
+  },
+  models::{
+    cnt::CNT,
+    event::EventStatus,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    check_in::{CheckInAttempt, CheckInResult, CheckInsData, NewCheckInAttempt, ScannerCheckIns},
+  },
+  schema::{
+    check_in_attempts::dsl::{
+      self as check_in_attempts_dsl,
+      check_in_attempts,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Handles a scan at the door. The first scan of a CNT of the event marks it as attended; later scans are
+  /// duplicates and unknown or draft CNTs are rejected, as are CNTs with an open refund and all scans while the
+  /// event is not admitting anyone (see `EventStatus::ADMITTING`). Every scan is recorded together with the
+  /// scanner and gate.
+  pub async fn check_in_cnt(
+    &mut self,
+    event_id: String,
+    cnt_sui_address: String,
+    scanner_id: String,
+    gate: Option<String>,
+  ) -> Result<CheckInAttempt> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let status = read_status(conn, &event_id).await?;
+
+      let cnt = cnts
+      .filter(cnts_dsl::event_id.eq(&event_id))
+      .filter(cnts_dsl::cnt_sui_address.eq(&cnt_sui_address))
+      .filter(cnts_dsl::draft.eq(false))
+      .for_update()
+      .first::<CNT>(conn)
+      .await
+      .optional()?;
+
+      let refund_pending = match &cnt {
+        Some(cnt) => has_open_refund(conn, &cnt.event_id, cnt.seat_index).await?,
+        None => false,
+      };
+
+      let result = match &cnt {
+        _ if !EventStatus::ADMITTING.contains(&status) => CheckInResult::Rejected,
+        None => CheckInResult::Rejected,
+        // The holder is being paid back for the ticket
+        Some(_) if refund_pending => CheckInResult::Rejected,
+        Some(cnt) if cnt.attended => CheckInResult::Duplicate,
+        Some(cnt) => {
+          let cnt = diesel::update(cnts)
+          .filter(cnts_dsl::event_id.eq(&cnt.event_id))
+          .filter(cnts_dsl::seat_index.eq(cnt.seat_index))
+          .set((cnts_dsl::attended.eq(true), cnts_dsl::attended_at.eq(dsl::now)))
+          .get_result::<CNT>(conn)
+          .await?;
+
+          record_ownership_events(conn, vec![
+            NewCntOwnershipEvent::new(OwnershipEventKind::Attended, &cnt, None),
+          ])
+          .await?;
+
+          CheckInResult::Admitted
+        },
+      };
+
+      let attempt = NewCheckInAttempt {
+        event_id,
+        cnt_sui_address,
+        seat_index: cnt.map(|cnt| cnt.seat_index),
+        scanner_id,
+        gate,
+        result,
+      };
+
+      Ok(
+        diesel::insert_into(check_in_attempts)
+        .values(&attempt)
+        .get_result::<CheckInAttempt>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  /// Number of check-ins per `interval` seconds since `start_ts`, like `read_closed_sales_count`. CNTs marked as
+  /// attended without a scan, e.g. when syncing with the chain, are included.
+  pub async fn read_check_ins_count(
+    &mut self,
+    event_id: String,
+    interval: u64,
+    start_ts: u64
+  ) -> Result<Vec<CheckInsData>> {
+    let (interval, start_ts) = (interval as i64, start_ts as i64);
+
+    let query = QueryBuilder::new("SELECT date_bin(make_interval(secs => ")
+    .bind::<BigInt, _>(interval)
+    .sql("), attended_at, TO_TIMESTAMP(")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      ")::date) as timestamp, COUNT(*)
+      FROM cnts
+      WHERE attended_at IS NOT NULL AND event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(" AND EXTRACT(epoch from attended_at) > ")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      "
+      GROUP BY 1
+      ORDER BY timestamp desc;
+      "
+    )
+    .build();
+
+    Ok(query.load::<CheckInsData>(self.borrow_mut()).await?)
+  }
+
+  /// Scan totals per scanner and gate, busiest first
+  pub async fn read_scanner_check_ins(&mut self, event_id: String) -> Result<Vec<ScannerCheckIns>> {
+    let query = QueryBuilder::new(
+      "
+      SELECT scanner_id, gate,
+      COUNT(*) FILTER (WHERE result = 'admitted') AS admitted,
+      COUNT(*) FILTER (WHERE result = 'duplicate') AS duplicates,
+      COUNT(*) FILTER (WHERE result = 'rejected') AS rejected,
+      MIN(created_at) AS first_scan_at,
+      MAX(created_at) AS last_scan_at
+      FROM check_in_attempts
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(
+      "
+      GROUP BY scanner_id, gate
+      ORDER BY admitted DESC, scanner_id, gate
+      "
+    )
+    .build();
+
+    Ok(query.load::<ScannerCheckIns>(self.borrow_mut()).await?)
+  }
+
+  /// Scans of CNTs that had already been checked in, most recent first
+  pub async fn read_duplicate_scans(&mut self, event_id: String, skip: i64, limit: i64) -> Result<Vec<CheckInAttempt>> {
+    Ok(
+      check_in_attempts
+      .filter(check_in_attempts_dsl::event_id.eq(event_id))
+      .filter(check_in_attempts_dsl::result.eq(CheckInResult::Duplicate))
+      .order_by((check_in_attempts_dsl::created_at.desc(), check_in_attempts_dsl::check_in_attempt_id.desc()))
+      .limit(limit)
+      .offset(skip * limit)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// Every scan of the CNT, oldest first
+  pub async fn read_cnt_check_in_attempts(&mut self, cnt_sui_address: String) -> Result<Vec<CheckInAttempt>> {
+    Ok(
+      check_in_attempts
+      .filter(check_in_attempts_dsl::cnt_sui_address.eq(cnt_sui_address))
+      .order_by(check_in_attempts_dsl::check_in_attempt_id.asc())
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// Issued CNTs of the event that have not been checked in, by seat
+  pub async fn read_no_shows(&mut self, event_id: String, skip: i64, limit: i64) -> Result<Vec<CNT>> {
+    Ok(
+      cnts
+      .filter(cnts_dsl::event_id.eq(event_id))
+      .filter(cnts_dsl::draft.eq(false))
+      .filter(cnts_dsl::attended.eq(false))
+      .order_by(cnts_dsl::seat_index.asc())
+      .limit(limit)
+      .offset(skip * limit)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+}
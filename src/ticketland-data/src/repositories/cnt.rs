@@ -1,10 +1,20 @@
-use diesel::{prelude::*, sql_query};
-use eyre::Result;
-use diesel_async::RunQueryDsl;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+  prelude::*,
+  sql_types::{BigInt, Text},
+};
+use eyre::{Report, Result};
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  cursor::Cursor,
+  query::{QueryBuilder, Filters},
+  repositories::cnt_history::record_ownership_events,
   models::{
     cnt::{CNT, CNTWithMetadata, PartialListing, CNTWithEvent},
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    check_in::{CheckInAttempt, API_SCANNER_ID},
     ticket_type::TicketType,
     event::Event, seat_range::SeatRange, nft_detail::TicketTypeNftDetail, nft::TicketTypeNft,
   },
@@ -13,18 +23,55 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
-  pub async fn upsert_user_cnt(&mut self, user_cnt: CNT) -> Result<()> {
-    diesel::insert_into(cnts_dsl::cnts)
-    .values(&user_cnt)
-    .on_conflict((cnts_dsl::event_id, cnts_dsl::seat_index))
-    .do_update()
-    .set(&user_cnt)
-    .execute(self.borrow_mut())
-    .await?;
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Creates or overwrites the CNT and records the ownership and attendance changes in its history.
+  ///
+  /// Purchase limits are not checked here. This mirrors CNTs that already exist on chain, so rejecting one would
+  /// only leave the database out of sync; the limits are enforced when seats are allocated or held.
+  pub async fn upsert_user_cnt(&mut self, mut user_cnt: CNT) -> Result<()> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let existing = cnts_dsl::cnts
+      .filter(cnts_dsl::event_id.eq(&user_cnt.event_id))
+      .filter(cnts_dsl::seat_index.eq(user_cnt.seat_index))
+      .for_update()
+      .first::<CNT>(conn)
+      .await
+      .optional()?;
 
-    Ok(())
+      // Keep the time of the first check-in; a missing value leaves the stored one untouched on update
+      let newly_attended = existing.as_ref().map_or(true, |existing| !existing.attended);
+      if user_cnt.attended && newly_attended && user_cnt.attended_at.is_none() {
+        user_cnt.attended_at = Some(Utc::now().naive_utc());
+      }
+
+      let cnt = diesel::insert_into(cnts_dsl::cnts)
+      .values(&user_cnt)
+      .on_conflict((cnts_dsl::event_id, cnts_dsl::seat_index))
+      .do_update()
+      .set(&user_cnt)
+      .get_result::<CNT>(conn)
+      .await?;
+
+      let mut events = vec![];
+      match existing {
+        None => events.push(NewCntOwnershipEvent::new(OwnershipEventKind::Issued, &cnt, None)),
+        Some(existing) => {
+          if existing.account_id != cnt.account_id {
+            events.push(NewCntOwnershipEvent::new(OwnershipEventKind::Reassigned, &cnt, Some(existing.account_id)));
+          }
+
+          if !existing.attended && cnt.attended {
+            events.push(NewCntOwnershipEvent::new(OwnershipEventKind::Attended, &cnt, None));
+          }
+        },
+      }
+
+      record_ownership_events(conn, events).await
+    }))
+    .await
   }
+
   pub async fn set_cnt_sui_address(&mut self, event_id: String, seat_index: i32, cnt_sui_address: String) -> Result<()> {
     diesel::update(cnts_dsl::cnts)
     .filter(
@@ -41,35 +88,14 @@ impl PostgresConnection {
   }
 
   pub async fn read_cnts_for_event(&mut self, evt_id: String, skip: i64, limit: i64) -> Result<Vec<CNTWithMetadata>> {
-    let query = sql_query(format!(
-      "
-      SELECT DISTINCT
-        cnts.*,
-        ticket_types.*,
-        seat_ranges.*,
-        ticket_type_nft_details.*,
-        nft_details.*,
-        ticket_type_nfts.*,
-        listings.listing_sui_address
-      FROM (
-        SELECT * FROM cnts
-        WHERE cnts.event_id = '{}' AND cnts.draft = FALSE
-        LIMIT {}
-        OFFSET {}
-      ) cnts
-      INNER JOIN ticket_types USING(event_id, ticket_type_index)
-      INNER JOIN seat_ranges USING(event_id, ticket_type_index)
-      INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
-      INNER JOIN nft_details ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
-      LEFT JOIN ticket_type_nfts USING(ref_name, cnt_sui_address)
-      LEFT JOIN listings ON (
-        listings.event_id = cnts.event_id
-        AND listings.cnt_sui_address = cnts.cnt_sui_address
-        AND listings.is_open = TRUE AND listings.draft = FALSE
-      )
-      ORDER BY cnts.created_at
-      ", evt_id, limit, skip * limit
-    ));
+    let query = QueryBuilder::new(CNTS_WITH_METADATA_SELECT)
+    .filters(event_cnt_filters(evt_id))
+    .sql(" ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .sql(CNTS_WITH_METADATA_JOINS)
+    .build();
 
     let records = query
     .load::<(CNT, TicketType, SeatRange, TicketTypeNftDetail, Option<TicketTypeNft>, Option<PartialListing>)>(self.borrow_mut())
@@ -78,6 +104,34 @@ impl PostgresConnection {
     Ok(CNTWithMetadata::from_tuple(records))
   }
 
+  /// Keyset paginated version of `read_cnts_for_event` ordered by `(created_at, event_id, seat_index)`. Drafts have no
+  /// sui address yet, so the seat is what tells CNTs created at the same time apart.
+  pub async fn read_cnts_for_event_after(
+    &mut self,
+    evt_id: String,
+    cursor: Option<String>,
+    limit: i64,
+  ) -> Result<(Vec<CNTWithMetadata>, Option<String>)> {
+    let mut filters = event_cnt_filters(evt_id);
+    filters.after_index(CNT_CREATED_AT, "cnts.event_id", "cnts.seat_index", Cursor::decode_opt(cursor)?)?;
+
+    let query = QueryBuilder::new(CNTS_WITH_METADATA_SELECT)
+    .filters(filters)
+    .sql(" ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(CNTS_WITH_METADATA_JOINS)
+    .build();
+
+    let records = query
+    .load::<(CNT, TicketType, SeatRange, TicketTypeNftDetail, Option<TicketTypeNft>, Option<PartialListing>)>(self.borrow_mut())
+    .await?;
+
+    let cnts = CNTWithMetadata::from_tuple(records);
+    let next_cursor = Cursor::next(&cnts, limit, |cnt| Some(cnt_cursor(cnt.created_at, &cnt.event_id, cnt.seat_index)));
+
+    Ok((cnts, next_cursor))
+  }
+
   pub async fn read_user_cnts(
     &mut self,
     uid: String,
@@ -85,45 +139,14 @@ impl PostgresConnection {
     skip: i64,
     limit: i64,
   ) -> Result<Vec<CNTWithEvent>> {
-    let event_id_filter = event_id.map_or("".to_string(), |event_id| {
-      format!("AND cnts.event_id = '{}'", event_id)
-    });
-
-    let query = sql_query(format!(
-      "
-      SELECT DISTINCT
-        cnts.*,
-        events.*,
-        ticket_types.*,
-        seat_ranges.*,
-        ticket_type_nft_details.*,
-        nft_details.*,
-        ticket_type_nfts.*,
-        listings.listing_sui_address
-      FROM (
-        SELECT * FROM cnts
-        WHERE cnts.account_id = '{}' AND cnts.draft = FALSE {}
-        LIMIT {}
-        OFFSET {}
-      ) cnts
-      INNER JOIN events USING(event_id)
-      INNER JOIN ticket_types USING(event_id, ticket_type_index)
-      INNER JOIN seat_ranges USING(event_id, ticket_type_index)
-      INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
-      INNER JOIN nft_details ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
-      LEFT JOIN ticket_type_nfts USING(ref_name, cnt_sui_address)
-      LEFT JOIN listings ON (
-        listings.event_id = cnts.event_id
-        AND listings.cnt_sui_address = cnts.cnt_sui_address
-        AND listings.is_open = TRUE AND listings.draft = FALSE
-      )
-      ORDER BY cnts.created_at
-      ",
-      uid,
-      event_id_filter,
-      limit,
-      skip * limit,
-    ));
+    let query = QueryBuilder::new(CNTS_WITH_EVENT_SELECT)
+    .filters(user_cnt_filters(uid, event_id))
+    .sql(" ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .sql(CNTS_WITH_EVENT_JOINS)
+    .build();
 
     let records = query
     .load::<(CNT, Event, TicketType, SeatRange, TicketTypeNftDetail, Option<TicketTypeNft>, Option<PartialListing>)>(self.borrow_mut())
@@ -132,14 +155,47 @@ impl PostgresConnection {
     Ok(CNTWithEvent::from_tuple(records))
   }
 
-  pub async fn update_attended(&mut self, cnt_sui_address: String) -> Result<()> {
-    diesel::update(cnts_dsl::cnts)
-    .filter(cnts_dsl::cnt_sui_address.eq(cnt_sui_address))
-    .set(cnts_dsl::attended.eq(true))
-    .execute(self.borrow_mut())
+  /// Keyset paginated version of `read_user_cnts` ordered by `(created_at, event_id, seat_index)`. Drafts have no
+  /// sui address yet, so the seat is what tells CNTs created at the same time apart.
+  pub async fn read_user_cnts_after(
+    &mut self,
+    uid: String,
+    event_id: Option<String>,
+    cursor: Option<String>,
+    limit: i64,
+  ) -> Result<(Vec<CNTWithEvent>, Option<String>)> {
+    let mut filters = user_cnt_filters(uid, event_id);
+    filters.after_index(CNT_CREATED_AT, "cnts.event_id", "cnts.seat_index", Cursor::decode_opt(cursor)?)?;
+
+    let query = QueryBuilder::new(CNTS_WITH_EVENT_SELECT)
+    .filters(filters)
+    .sql(" ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(CNTS_WITH_EVENT_JOINS)
+    .build();
+
+    let records = query
+    .load::<(CNT, Event, TicketType, SeatRange, TicketTypeNftDetail, Option<TicketTypeNft>, Option<PartialListing>)>(self.borrow_mut())
     .await?;
 
-    Ok(())
+    let cnts = CNTWithEvent::from_tuple(records);
+    let next_cursor = Cursor::next(&cnts, limit, |cnt| Some(cnt_cursor(cnt.created_at, &cnt.event_id, cnt.seat_index)));
+
+    Ok((cnts, next_cursor))
+  }
+
+  /// Checks the CNT in like a scan at the door, see `check_in_cnt`. The attempt is recorded with `API_SCANNER_ID`
+  /// as the scanner.
+  pub async fn update_attended(&mut self, cnt_sui_address: String) -> Result<CheckInAttempt> {
+    let event_id = cnts_dsl::cnts
+    .filter(cnts_dsl::cnt_sui_address.eq(&cnt_sui_address))
+    .select(cnts_dsl::event_id)
+    .first::<String>(self.borrow_mut())
+    .await
+    .optional()?
+    .ok_or(Error::CntNotFound)?;
+
+    self.check_in_cnt(event_id, cnt_sui_address, API_SCANNER_ID.to_string(), None).await
   }
 
   pub async fn has_attended(&mut self, cnt_sui_address: String) -> Result<bool> {
@@ -161,3 +217,91 @@ impl PostgresConnection {
     )
   }
 }
+
+// The paginated cnts subquery goes in between the SELECT and the JOINS part
+const CNTS_WITH_METADATA_SELECT: &str = "
+  SELECT DISTINCT
+    cnts.*,
+    ticket_types.*,
+    seat_ranges.*,
+    ticket_type_nft_details.*,
+    nft_details.*,
+    ticket_type_nfts.*,
+    listings.listing_sui_address
+  FROM (
+    SELECT * FROM cnts
+    WHERE ";
+
+const CNTS_WITH_METADATA_JOINS: &str = "
+  ) cnts
+  INNER JOIN ticket_types USING(event_id, ticket_type_index)
+  INNER JOIN seat_ranges USING(event_id, ticket_type_index)
+  INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
+  INNER JOIN nft_details ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
+  LEFT JOIN ticket_type_nfts USING(ref_name, cnt_sui_address)
+  LEFT JOIN listings ON (
+    listings.event_id = cnts.event_id
+    AND listings.cnt_sui_address = cnts.cnt_sui_address
+    AND listings.is_open = TRUE AND listings.draft = FALSE
+    AND (listings.expires_at IS NULL OR listings.expires_at > now())
+  )
+  ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index
+";
+
+const CNTS_WITH_EVENT_SELECT: &str = "
+  SELECT DISTINCT
+    cnts.*,
+    events.*,
+    ticket_types.*,
+    seat_ranges.*,
+    ticket_type_nft_details.*,
+    nft_details.*,
+    ticket_type_nfts.*,
+    listings.listing_sui_address
+  FROM (
+    SELECT * FROM cnts
+    WHERE ";
+
+const CNTS_WITH_EVENT_JOINS: &str = "
+  ) cnts
+  INNER JOIN events USING(event_id)
+  INNER JOIN ticket_types USING(event_id, ticket_type_index)
+  INNER JOIN seat_ranges USING(event_id, ticket_type_index)
+  INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
+  INNER JOIN nft_details ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
+  LEFT JOIN ticket_type_nfts USING(ref_name, cnt_sui_address)
+  LEFT JOIN listings ON (
+    listings.event_id = cnts.event_id
+    AND listings.cnt_sui_address = cnts.cnt_sui_address
+    AND listings.is_open = TRUE AND listings.draft = FALSE
+    AND (listings.expires_at IS NULL OR listings.expires_at > now())
+  )
+  ORDER BY cnts.created_at NULLS FIRST, cnts.event_id, cnts.seat_index
+";
+
+fn event_cnt_filters<'a>(evt_id: String) -> Filters<'a> {
+  let mut filters = Filters::new();
+  filters.eq::<Text, _>("cnts.event_id", evt_id);
+  filters.push(|query| query.sql("cnts.draft = FALSE"));
+
+  filters
+}
+
+fn user_cnt_filters<'a>(uid: String, event_id: Option<String>) -> Filters<'a> {
+  let mut filters = Filters::new();
+  filters.eq::<Text, _>("cnts.account_id", uid);
+  filters.push(|query| query.sql("cnts.draft = FALSE"));
+
+  if let Some(event_id) = event_id {
+    filters.eq::<Text, _>("cnts.event_id", event_id);
+  }
+
+  filters
+}
+
+/// The SQL side of `Cursor::created_at` for CNTs. Sorts like `created_at NULLS FIRST` in the queries above.
+const CNT_CREATED_AT: &str = "COALESCE(cnts.created_at, 'epoch')";
+
+fn cnt_cursor(created_at: Option<NaiveDateTime>, event_id: &str, seat_index: i32) -> Cursor {
+  Cursor::with_index(Cursor::created_at(created_at), event_id.to_string(), seat_index)
+}
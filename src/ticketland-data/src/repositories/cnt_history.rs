@@ -0,0 +1,59 @@
+use diesel::sql_types::Text;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use eyre::Result;
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  query::QueryBuilder,
+  models::cnt_ownership_event::{CntOwnershipEvent, NewCntOwnershipEvent},
+  schema::cnt_ownership_events::dsl::cnt_ownership_events,
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Every ownership and attendance change of the CNT, oldest first. Includes the changes that happened while
+  /// the CNT was a draft and had no address yet, but not those of a refunded CNT that used to hold the seat.
+  pub async fn read_cnt_history(&mut self, cnt_sui_address: String) -> Result<Vec<CntOwnershipEvent>> {
+    let query = QueryBuilder::new("SELECT * FROM cnt_ownership_events history WHERE cnt_sui_address = ")
+    .bind::<Text, _>(cnt_sui_address.clone())
+    .sql(
+      "
+      OR (
+        (event_id, seat_index) IN (
+          SELECT event_id, seat_index FROM cnts WHERE cnt_sui_address = "
+    )
+    .bind::<Text, _>(cnt_sui_address)
+    .sql(
+      "
+        )
+        AND NOT EXISTS (
+          SELECT 1 FROM cnt_ownership_events refunded
+          WHERE refunded.event_id = history.event_id
+          AND refunded.seat_index = history.seat_index
+          AND refunded.kind = 'refunded'
+          AND refunded.ownership_event_id >= history.ownership_event_id
+        )
+      )
+      ORDER BY created_at, ownership_event_id
+      "
+    )
+    .build();
+
+    Ok(query.load::<CntOwnershipEvent>(self.borrow_mut()).await?)
+  }
+}
+
+/// Appends to the history. Must run in the same transaction as the change it records
+pub(crate) async fn record_ownership_events(
+  conn: &mut AsyncPgConnection,
+  events: Vec<NewCntOwnershipEvent>,
+) -> Result<()> {
+  if events.is_empty() {
+    return Ok(())
+  }
+
+  diesel::insert_into(cnt_ownership_events)
+  .values(&events)
+  .execute(conn)
+  .await?;
+
+  Ok(())
+}
@@ -0,0 +1,282 @@
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::{
+    cnt_history::record_ownership_events,
+    refund_request::check_no_open_refund,
+  },
+  models::{
+    cnt::CNT,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    cnt_transfer::{CntTransfer, NewCntTransfer, TransferStatus},
+  },
+  schema::{
+    cnt_transfers::dsl::{
+      self as cnt_transfers_dsl,
+      cnt_transfers,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    ticket_type_nfts::dsl::{
+      self as ticket_type_nfts_dsl,
+      ticket_type_nfts,
+    },
+    accounts::dsl::{
+      self as accounts_dsl,
+      accounts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Starts gifting a CNT to another account, identified by either its id or its email. The CNT must belong to the
+  /// sender, must not be attended, listed, being refunded or already part of a pending transfer.
+  pub async fn create_cnt_transfer(
+    &mut self,
+    cnt_address: String,
+    sender: String,
+    recipient_account_id: Option<String>,
+    recipient_email: Option<String>,
+  ) -> Result<CntTransfer> {
+    let recipient_email = recipient_email
+    .map(|email| email.trim().to_lowercase())
+    .filter(|email| !email.is_empty());
+
+    if (recipient_account_id.is_none() && recipient_email.is_none())
+    || recipient_account_id.as_ref() == Some(&sender) {
+      return Err(Error::InvalidTransferRecipient.into())
+    }
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let cnt = lock_transferable_cnt(conn, &cnt_address, &sender).await?;
+
+      if recipient_email.is_some() && account_email(conn, &sender).await? == recipient_email {
+        return Err(Error::InvalidTransferRecipient.into())
+      }
+
+      let pending = cnt_transfers
+      .filter(cnt_transfers_dsl::cnt_sui_address.eq(&cnt_address))
+      .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+      .count()
+      .get_result::<i64>(conn)
+      .await?;
+
+      if pending > 0 {
+        return Err(Error::TransferAlreadyPending.into())
+      }
+
+      let transfer = NewCntTransfer {
+        cnt_sui_address: cnt_address,
+        event_id: cnt.event_id,
+        sender_account_id: sender,
+        recipient_account_id,
+        recipient_email,
+      };
+
+      Ok(
+        diesel::insert_into(cnt_transfers)
+        .values(&transfer)
+        .get_result::<CntTransfer>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  /// Moves the CNT, together with the ticket type NFTs the sender holds for it, to the accepting account.
+  /// The same checks as `create_cnt_transfer` are repeated since the CNT may have changed in the meantime.
+  pub async fn accept_cnt_transfer(&mut self, id: i64, uid: String) -> Result<CntTransfer> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let transfer = lock_incoming_transfer(conn, id, &uid).await?;
+      lock_transferable_cnt(conn, &transfer.cnt_sui_address, &transfer.sender_account_id).await?;
+
+      let cnt = diesel::update(cnts)
+      .filter(cnts_dsl::cnt_sui_address.eq(&transfer.cnt_sui_address))
+      .set(cnts_dsl::account_id.eq(&uid))
+      .get_result::<CNT>(conn)
+      .await?;
+
+      diesel::update(ticket_type_nfts)
+      .filter(ticket_type_nfts_dsl::cnt_sui_address.eq(&transfer.cnt_sui_address))
+      .filter(ticket_type_nfts_dsl::account_id.eq(&transfer.sender_account_id))
+      .set(ticket_type_nfts_dsl::account_id.eq(&uid))
+      .execute(conn)
+      .await?;
+
+      record_ownership_events(conn, vec![NewCntOwnershipEvent {
+        transfer_id: Some(id),
+        ..NewCntOwnershipEvent::new(OwnershipEventKind::Transferred, &cnt, Some(transfer.sender_account_id))
+      }])
+      .await?;
+
+      Ok(
+        diesel::update(cnt_transfers)
+        .filter(cnt_transfers_dsl::transfer_id.eq(id))
+        .set((
+          cnt_transfers_dsl::status.eq(TransferStatus::Accepted),
+          cnt_transfers_dsl::recipient_account_id.eq(&uid),
+          cnt_transfers_dsl::resolved_at.eq(dsl::now),
+        ))
+        .get_result::<CntTransfer>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  pub async fn decline_cnt_transfer(&mut self, id: i64, uid: String) -> Result<CntTransfer> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      lock_incoming_transfer(conn, id, &uid).await?;
+
+      Ok(
+        diesel::update(cnt_transfers)
+        .filter(cnt_transfers_dsl::transfer_id.eq(id))
+        .set((
+          cnt_transfers_dsl::status.eq(TransferStatus::Declined),
+          cnt_transfers_dsl::resolved_at.eq(dsl::now),
+        ))
+        .get_result::<CntTransfer>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  pub async fn cancel_cnt_transfer(&mut self, id: i64, uid: String) -> Result<CntTransfer> {
+    diesel::update(cnt_transfers)
+    .filter(cnt_transfers_dsl::transfer_id.eq(id))
+    .filter(cnt_transfers_dsl::sender_account_id.eq(uid))
+    .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+    .set((
+      cnt_transfers_dsl::status.eq(TransferStatus::Cancelled),
+      cnt_transfers_dsl::resolved_at.eq(dsl::now),
+    ))
+    .get_result::<CntTransfer>(self.borrow_mut())
+    .await
+    .optional()?
+    .ok_or_else(|| Error::TransferNotFound.into())
+  }
+
+  /// Pending transfers addressed to the account, either by id or by its current email
+  pub async fn read_incoming_cnt_transfers(&mut self, uid: String) -> Result<Vec<CntTransfer>> {
+    let email = account_email(self.borrow_mut(), &uid).await?;
+
+    let mut query = cnt_transfers
+    .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+    .into_boxed();
+
+    query = match email {
+      Some(email) => query.filter(
+        cnt_transfers_dsl::recipient_account_id.eq(uid)
+        .or(
+          cnt_transfers_dsl::recipient_account_id.is_null()
+          .and(cnt_transfers_dsl::recipient_email.eq(email))
+        )
+      ),
+      None => query.filter(cnt_transfers_dsl::recipient_account_id.eq(uid)),
+    };
+
+    Ok(
+      query
+      .order_by(cnt_transfers_dsl::created_at.desc())
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  pub async fn read_outgoing_cnt_transfers(&mut self, uid: String) -> Result<Vec<CntTransfer>> {
+    Ok(
+      cnt_transfers
+      .filter(cnt_transfers_dsl::sender_account_id.eq(uid))
+      .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+      .order_by(cnt_transfers_dsl::created_at.desc())
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+}
+
+/// Locks the CNT and makes sure it can change hands i.e. it belongs to `owner`, it has not been attended and it
+/// has no open listing or refund.
+async fn lock_transferable_cnt(conn: &mut AsyncPgConnection, cnt_address: &str, owner: &str) -> Result<CNT> {
+  let cnt = cnts
+  .filter(cnts_dsl::cnt_sui_address.eq(cnt_address))
+  .filter(cnts_dsl::account_id.eq(owner))
+  .for_update()
+  .first::<CNT>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::CntNotFound)?;
+
+  if cnt.attended {
+    return Err(Error::CntAttended.into())
+  }
+
+  let open_listings = listings
+  .filter(listings_dsl::cnt_sui_address.eq(cnt_address))
+  .filter(listings_dsl::is_open.eq(true))
+  .filter(listings_dsl::expires_at.is_null().or(listings_dsl::expires_at.gt(dsl::now)))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  if open_listings > 0 {
+    return Err(Error::CntListed.into())
+  }
+
+  check_no_open_refund(conn, &cnt).await?;
+
+  Ok(cnt)
+}
+
+/// Locks a pending transfer addressed to the account
+async fn lock_incoming_transfer(conn: &mut AsyncPgConnection, id: i64, uid: &str) -> Result<CntTransfer> {
+  let transfer = cnt_transfers
+  .filter(cnt_transfers_dsl::transfer_id.eq(id))
+  .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+  .for_update()
+  .first::<CntTransfer>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::TransferNotFound)?;
+
+  let is_recipient = match &transfer.recipient_account_id {
+    Some(recipient) => recipient == uid,
+    None => transfer.recipient_email.is_some() && account_email(conn, uid).await? == transfer.recipient_email,
+  };
+
+  if !is_recipient {
+    return Err(Error::TransferNotFound.into())
+  }
+
+  Ok(transfer)
+}
+
+/// The account's email in the normalized form transfers are stored with
+pub(crate) async fn account_email(conn: &mut AsyncPgConnection, uid: &str) -> Result<Option<String>> {
+  let email = accounts
+  .filter(accounts_dsl::uid.eq(uid))
+  .select(accounts_dsl::email)
+  .first::<Option<String>>(conn)
+  .await
+  .optional()?
+  .flatten();
+
+  Ok(
+    email
+    .map(|email| email.trim().to_lowercase())
+    .filter(|email| !email.is_empty())
+  )
+}
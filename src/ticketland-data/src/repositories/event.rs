@@ -1,15 +1,24 @@
 use chrono::NaiveDateTime;
-use diesel::{prelude::*, sql_query};
+use diesel::{
+  prelude::*,
+  sql_types::{BigInt, Double, SmallInt, Text, Timestamptz},
+};
 use diesel::result::Error;
 use diesel_async::{AsyncConnection, RunQueryDsl};
-use eyre::Result;
+use eyre::{Report, Result, bail, eyre};
 use crate::models::nft_detail::TicketTypeNftDetail;
 use crate::models::ticket_type::TicketType;
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  cursor::Cursor,
+  repositories::{
+    ticket_type::store_ticket_types,
+    event_status::{editable_event_status, publish_draft_event},
+  },
+  query::{QueryBuilder, Filters},
   models::{
     account::Account,
-    event::{Event, ExtendedEvent},
+    event::{Event, ExtendedEvent, EventFilters, EventSort, GeoFilter},
     ticket_type::{NewTicketType},
     seat_range::SeatRange,
     event::AttendedTicketCount,
@@ -29,10 +38,6 @@ use crate::{
       self as ticket_types_dsl,
       ticket_types,
     },
-    seat_ranges::dsl::{
-      self as seat_ranges_dsl,
-      seat_ranges,
-    },
     nft_details::dsl::{
       self as nft_details_dsl,
       nft_details,
@@ -52,7 +57,7 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_event(
     &mut self,
     event: Event,
@@ -64,7 +69,10 @@ impl PostgresConnection {
     // properties_list: Vec<NewProperty>,
   ) -> Result<()> {
     self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let status = editable_event_status(conn, &event.event_id).await?;
+      let event = Event {status, ..event};
+
       diesel::insert_into(events)
       .values(&event)
       .on_conflict(events_dsl::event_id)
@@ -73,24 +81,7 @@ impl PostgresConnection {
       .execute(conn)
       .await?;
 
-      diesel::insert_into(ticket_types)
-      .values(&ticket_types_list)
-      .on_conflict((ticket_types_dsl::event_id, ticket_types_dsl::ticket_type_index))
-      .do_nothing()
-      .execute(conn)
-      .await?;
-
-      diesel::insert_into(seat_ranges)
-      .values(&seat_ranges_list)
-      .on_conflict((
-        seat_ranges_dsl::event_id,
-        seat_ranges_dsl::ticket_type_index,
-        seat_ranges_dsl::l,
-        seat_ranges_dsl::r
-      ))
-      .do_nothing()
-      .execute(conn)
-      .await?;
+      store_ticket_types(conn, &ticket_types_list, &seat_ranges_list).await?;
 
       diesel::insert_into(nft_details)
       .values(&nft_details_list)
@@ -187,42 +178,24 @@ impl PostgresConnection {
     skip: i64,
     limit: i64
   ) -> Result<Vec<ExtendedEvent>> {
-    let mut filters = vec![];
-
-    if let Some(name) = name {
-      filters.push(format!("events.name ILIKE '%{}%'", name));
-    };
-
-    if let Some(start_date_from) = start_date_from {
-      filters.push(format!("events.start_date >= '{0}'", start_date_from));
-    };
-
-    if let Some(start_date_to) = start_date_to {
-      filters.push(format!("events.start_date <= '{0}'", start_date_to));
-    };
-
-    if let Some(end_date_from) = end_date_from {
-      filters.push(format!("events.end_date >= '{0}'", end_date_from));
-    };
-
-    if let Some(end_date_to) = end_date_to {
-      filters.push(format!("events.end_date <= '{0}'", end_date_to));
-    };
+    let filters = date_and_name_filters(start_date_from, start_date_to, end_date_from, end_date_to, name);
 
-    let filters_query = if filters.len() > 0 {
-      filters.join(" AND ")
-    } else {
-      "true = true".to_string()
-    };
-
-    let query = sql_query(format!(
+    let query = QueryBuilder::new(
       "
       SELECT *
       FROM (
         SELECT * FROM events
-        WHERE events.account_id = '{0}' AND events.draft = false AND {1}
-        LIMIT {2}
-        OFFSET {3}
+        WHERE events.account_id = "
+    )
+    .bind::<Text, _>(user_id)
+    .sql(" AND events.status <> 'draft' AND ")
+    .filters(filters)
+    .sql(" LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .sql(
+      "
       ) events
       INNER JOIN ticket_types USING(event_id)
       INNER JOIN seat_ranges USING(event_id, ticket_type_index)
@@ -230,12 +203,9 @@ impl PostgresConnection {
       INNER JOIN nft_details
       ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
       ORDER BY events.start_date
-      ",
-      user_id,
-      filters_query,
-      limit,
-      skip * limit,
-    ));
+      "
+    )
+    .build();
 
     let records = query
     .load::<(Event, TicketType, SeatRange, TicketTypeNftDetail)>(self.borrow_mut())
@@ -266,75 +236,37 @@ impl PostgresConnection {
 
   pub async fn read_filtered_events(
     &mut self,
-    category: Option<i16>,
-    price_range: Option<(u32, u32)>,
-    start_date_from: Option<NaiveDateTime>,
-    start_date_to: Option<NaiveDateTime>,
-    name: Option<String>,
+    filters: EventFilters,
     skip: i64,
     limit: i64,
   ) -> Result<Vec<ExtendedEvent>> {
-    let mut filters = vec![];
-
-    if let Some(category) = category {
-      filters.push(format!("events.category = {}", category));
-    };
-
-    if let Some(name) = name {
-      filters.push(format!("events.name ILIKE '%{}%'", name));
-    };
-
-    if let Some(start_date_from) = start_date_from {
-      filters.push(format!("events.start_date >= '{0}'", start_date_from));
-    };
-
-    if let Some(start_date_to) = start_date_to {
-      filters.push(format!("events.start_date <= '{0}'", start_date_to));
-    };
-
-    if let Some(price_range) = price_range {
-      // There are 2 possible cases for (x, y) with the precondition that x <= y
-      // 1. (x > 0, y > 0) -> We include only FixedPrice sale type
-      // 2. (x >= 0, y >= 0) -> We include Free and FixedPrice sale types
-      if price_range.0 > 0 {
-        filters.push(format!("
-          (sale_type->'FixedPrice'->'price')::numeric >= {0}
-          AND (sale_type->'FixedPrice'->'price')::numeric <= {1}
-        ", price_range.0, price_range.1));
-      } else {
-        filters.push(format!("
-          ((sale_type->'FixedPrice'->'price')::numeric <= {0}
-          OR (sale_type->'Free') = '{{}}')
-        ", price_range.1));
-      }
-    };
-
-    let filters_query = if filters.len() > 0 {
-      filters.join(" AND ")
-    } else {
-      "true = true".to_string()
-    };
-
-    let query = sql_query(format!("
-      WITH filtered_events AS (
-        SELECT *
-        FROM (SELECT * FROM events WHERE events.draft = false) events
-        INNER JOIN ticket_types USING(event_id)
-        INNER JOIN seat_ranges USING(event_id, ticket_type_index)
-        INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
-        INNER JOIN nft_details
-        ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
-        WHERE {}
+    let (sort_key, direction) = event_sort_key(&filters)?;
+
+    let query = QueryBuilder::new(FILTERED_EVENTS_CTE)
+    .filters(event_search_filters(filters))
+    .sql("
       )
       SELECT * FROM filtered_events
       INNER JOIN (
-        SELECT DISTINCT event_id FROM filtered_events
-        ORDER BY event_id
-        LIMIT {} OFFSET {}
-      ) limited_events ON limited_events.event_id = filtered_events.event_id
-    ", filters_query, limit, skip * limit)
+        SELECT event_id, "
     );
 
+    let query = sort_key(query)
+    .sql(&format!(" AS sort_key
+        FROM filtered_events
+        GROUP BY event_id
+        ORDER BY sort_key {}, event_id
+        LIMIT ", direction)
+    )
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .sql(&format!("
+      ) limited_events ON limited_events.event_id = filtered_events.event_id
+      ORDER BY limited_events.sort_key {}, limited_events.event_id
+    ", direction))
+    .build();
+
     let records = query
     .load::<(Event, TicketType, SeatRange, TicketTypeNftDetail)>(self.borrow_mut())
     .await?;
@@ -342,6 +274,55 @@ impl PostgresConnection {
     Ok(ExtendedEvent::from_tuple(records))
   }
 
+  /// Keyset paginated version of `read_filtered_events`. Events are always ordered by `(start_date, event_id)`
+  /// and the returned cursor, if any, points to the next page. Price, relevance and distance sorting
+  /// are only available through `read_filtered_events`.
+  pub async fn read_filtered_events_after(
+    &mut self,
+    filters: EventFilters,
+    cursor: Option<String>,
+    limit: i64,
+  ) -> Result<(Vec<ExtendedEvent>, Option<String>)> {
+    if matches!(filters.sort, EventSort::Price | EventSort::Relevance | EventSort::Distance) {
+      bail!("cursor pagination only supports ordering events by start date");
+    }
+
+    let mut page_filters = Filters::new();
+    page_filters.after("start_date", "event_id", Cursor::decode_opt(cursor)?);
+
+    let query = QueryBuilder::new(FILTERED_EVENTS_CTE)
+    .filters(event_search_filters(filters))
+    .sql("
+      )
+      SELECT * FROM filtered_events
+      INNER JOIN (
+        SELECT DISTINCT event_id, start_date AS page_start_date FROM filtered_events
+        WHERE "
+    )
+    .filters(page_filters)
+    .sql("
+        ORDER BY page_start_date, event_id
+        LIMIT "
+    )
+    .bind::<BigInt, _>(limit)
+    .sql("
+      ) limited_events ON limited_events.event_id = filtered_events.event_id
+      ORDER BY limited_events.page_start_date, limited_events.event_id
+    ")
+    .build();
+
+    let records = query
+    .load::<(Event, TicketType, SeatRange, TicketTypeNftDetail)>(self.borrow_mut())
+    .await?;
+
+    let extended_events = ExtendedEvent::from_tuple(records);
+    let next_cursor = Cursor::next(&extended_events, limit, |event| {
+      Some(Cursor::new(event.start_date, event.event_id.clone()))
+    });
+
+    Ok((extended_events, next_cursor))
+  }
+
   pub async fn commit_event(
     &mut self,
     evt_id: String,
@@ -353,42 +334,29 @@ impl PostgresConnection {
     ticket_type_accounts: Vec<String>,
   ) -> Result<()> {
     self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
-      let update_event_query = sql_query(format!(
-        "
-        UPDATE events
-        SET event_sui_address = '{1}',
-            organizer_cap = '{2}',
-            operator_cap = '{3}',
-            event_nft = '{4}',
-            event_capacity_bitmap_address = '{5}',
-            draft = false
-        WHERE events.event_id = '{0}';
-        ",
-        evt_id,
-        event_sui_address,
-        organizer_cap,
-        operator_cap,
-        event_nft,
-        event_capacity_bitmap_address,
-      ));
-
-      update_event_query.execute(conn).await?;
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      publish_draft_event(conn, &evt_id).await?;
+
+      diesel::update(events)
+      .filter(events_dsl::event_id.eq(&evt_id))
+      .set((
+        events_dsl::event_sui_address.eq(event_sui_address),
+        events_dsl::organizer_cap.eq(organizer_cap),
+        events_dsl::operator_cap.eq(operator_cap),
+        events_dsl::event_nft.eq(event_nft),
+        events_dsl::event_capacity_bitmap_address.eq(event_capacity_bitmap_address),
+      ))
+      .execute(conn)
+      .await?;
 
       // We cannot do multiple UPDATEs in one query, so these need to be separate
-      for (ticket_type_index, ticket_type_account) in ticket_type_accounts.iter().enumerate() {
-        let update_query = format!(
-          "
-          UPDATE ticket_types
-          SET ticket_type_sui_address = '{}'
-          WHERE ticket_types.event_id = '{}' AND ticket_type_index = {};
-          ",
-          ticket_type_account,
-          evt_id.clone(),
-          ticket_type_index
-        );
-
-        sql_query(update_query).execute(conn).await?;
+      for (ticket_type_index, ticket_type_account) in ticket_type_accounts.into_iter().enumerate() {
+        diesel::update(ticket_types)
+        .filter(ticket_types_dsl::event_id.eq(&evt_id))
+        .filter(ticket_types_dsl::ticket_type_index.eq(ticket_type_index as i16))
+        .set(ticket_types_dsl::ticket_type_sui_address.eq(ticket_type_account))
+        .execute(conn)
+        .await?;
       }
 
       Ok(())
@@ -399,12 +367,19 @@ impl PostgresConnection {
     Ok(())
   }
 
+  /// Reads the event if it is a draft when `draft` is true, or if it is past the draft stage otherwise. Published
+  /// events leave out the ticket types hidden behind an access code, see `read_access_code_ticket_type`.
   pub async fn read_event_with_ticket_types(&mut self, evt_id: String, draft: bool) -> Result<Vec<ExtendedEvent>> {
-    let query = sql_query(format!(
+    let query = QueryBuilder::new(
       "
       SELECT * FROM (
         SELECT * FROM events
-        WHERE events.event_id = '{}' AND events.draft = {}
+        WHERE events.event_id = "
+    )
+    .bind::<Text, _>(evt_id)
+    .sql(if draft {" AND events.status = 'draft'"} else {" AND events.status <> 'draft'"})
+    .sql(
+      "
       ) events
       INNER JOIN ticket_types
       ON ticket_types.event_id = events.event_id
@@ -414,8 +389,18 @@ impl PostgresConnection {
       ON ticket_type_nft_details.event_id = ticket_types.event_id AND ticket_type_nft_details.ticket_type_index = ticket_types.ticket_type_index
       INNER JOIN nft_details
       ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
-      ", evt_id, draft
-    ));
+      "
+    )
+    .sql(if draft {""} else {
+      "
+      WHERE NOT EXISTS (
+        SELECT 1 FROM promo_codes
+        WHERE promo_codes.kind = 'access' AND promo_codes.event_id = ticket_types.event_id
+        AND promo_codes.ticket_type_index = ticket_types.ticket_type_index
+      )
+      "
+    })
+    .build();
 
     let records = query
     .load::<(Event, TicketType, SeatRange, TicketTypeNftDetail)>(self.borrow_mut())
@@ -425,17 +410,22 @@ impl PostgresConnection {
   }
 
   pub async fn read_attended_tickets_count(&mut self, evt_id: String) -> Result<Vec<AttendedTicketCount>> {
-    let query = sql_query(format!(
+    let query = QueryBuilder::new(
       "
       SELECT ticket_types.ticket_type_index,
       COUNT(cnts.ticket_type_index) AS total_count,
       COUNT(CASE WHEN attended = TRUE THEN 1 END) AS attended_count
       FROM ticket_types
       LEFT OUTER JOIN cnts ON ticket_types.ticket_type_index = cnts.ticket_type_index AND ticket_types.event_id = cnts.event_id
-      WHERE ticket_types.event_id = '{}'
+      WHERE ticket_types.event_id = "
+    )
+    .bind::<Text, _>(evt_id)
+    .sql(
+      "
       GROUP BY ticket_types.ticket_type_index;
-      ", evt_id
-    ));
+      "
+    )
+    .build();
 
     Ok(query.load::<AttendedTicketCount>(self.borrow_mut()).await?)
   }
@@ -451,43 +441,27 @@ impl PostgresConnection {
     skip: i64,
     limit: i64
   ) -> Result<Vec<ExtendedEvent>> {
-    let mut filters = vec![];
-
-    if let Some(name) = name {
-      filters.push(format!("events.name ILIKE '%{}%'", name));
-    };
-
-    if let Some(start_date_from) = start_date_from {
-      filters.push(format!("events.start_date >= '{0}'", start_date_from));
-    };
-
-    if let Some(start_date_to) = start_date_to {
-      filters.push(format!("events.start_date <= '{0}'", start_date_to));
-    };
-
-    if let Some(end_date_from) = end_date_from {
-      filters.push(format!("events.end_date >= '{0}'", end_date_from));
-    };
-
-    if let Some(end_date_to) = end_date_to {
-      filters.push(format!("events.end_date <= '{0}'", end_date_to));
-    };
+    let filters = date_and_name_filters(start_date_from, start_date_to, end_date_from, end_date_to, name);
 
-    let filters_query = if filters.len() > 0 {
-      filters.join(" AND ")
-    } else {
-      "true = true".to_string()
-    };
-
-    let query = sql_query(format!(
+    let query = QueryBuilder::new(
       "
       SELECT *
       FROM (
         SELECT events.* FROM events
-        WHERE events.draft = false AND
-              EXISTS (SELECT * FROM cnts WHERE events.event_id = cnts.event_id AND cnts.account_id = '{}' AND {})
-        LIMIT {}
-        OFFSET {}
+        WHERE events.status <> 'draft' AND
+              EXISTS (SELECT * FROM cnts WHERE events.event_id = cnts.event_id AND cnts.account_id = "
+    )
+    .bind::<Text, _>(user_id)
+    .sql(" AND ")
+    .filters(filters)
+    .sql(")
+        LIMIT "
+    )
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .sql(
+      "
       ) events
       INNER JOIN ticket_types USING(event_id)
       INNER JOIN seat_ranges USING(event_id, ticket_type_index)
@@ -495,12 +469,9 @@ impl PostgresConnection {
       INNER JOIN nft_details
       ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
       ORDER BY events.start_date
-      ",
-      user_id,
-      filters_query,
-      limit,
-      skip * limit,
-    ));
+      "
+    )
+    .build();
 
     let records = query
     .load::<(Event, TicketType, SeatRange, TicketTypeNftDetail)>(self.borrow_mut())
@@ -509,3 +480,141 @@ impl PostgresConnection {
     Ok(ExtendedEvent::from_tuple(records))
   }
 }
+
+fn date_and_name_filters<'a>(
+  start_date_from: Option<NaiveDateTime>,
+  start_date_to: Option<NaiveDateTime>,
+  end_date_from: Option<NaiveDateTime>,
+  end_date_to: Option<NaiveDateTime>,
+  name: Option<String>,
+) -> Filters<'a> {
+  let mut filters = Filters::new();
+
+  if let Some(name) = name {
+    filters.contains("events.name", &name);
+  };
+
+  if let Some(start_date_from) = start_date_from {
+    filters.cmp::<Timestamptz, _>("events.start_date", ">=", start_date_from);
+  };
+
+  if let Some(start_date_to) = start_date_to {
+    filters.cmp::<Timestamptz, _>("events.start_date", "<=", start_date_to);
+  };
+
+  if let Some(end_date_from) = end_date_from {
+    filters.cmp::<Timestamptz, _>("events.end_date", ">=", end_date_from);
+  };
+
+  if let Some(end_date_to) = end_date_to {
+    filters.cmp::<Timestamptz, _>("events.end_date", "<=", end_date_to);
+  };
+
+  filters
+}
+
+fn event_search_filters<'a>(filters: EventFilters) -> Filters<'a> {
+  let EventFilters {category, price_range, start_date_from, start_date_to, name, text, near, ..} = filters;
+  let mut filters = date_and_name_filters(start_date_from, start_date_to, None, None, name);
+
+  if let Some(category) = category {
+    filters.eq::<SmallInt, _>("events.category", category);
+  };
+
+  if let Some((min_price, max_price)) = price_range {
+    // Covers all the sale types. Free ticket types have a price of 0 and Dutch auctions are
+    // evaluated at their current price.
+    filters.push(move |query| {
+      query
+      .sql("ticket_type_current_price(ticket_types.sale_type, ticket_types.sale_start_ts, now()) BETWEEN ")
+      .bind::<BigInt, _>(min_price as i64)
+      .sql(" AND ")
+      .bind::<BigInt, _>(max_price as i64)
+    });
+  };
+
+  if let Some(text) = text {
+    // Must be the same expression as the one in events_search_document_idx so the index is used
+    filters.push(move |query| {
+      query
+      .sql("event_search_document(events.name, events.description, events.venue) @@ websearch_to_tsquery('simple', ")
+      .bind::<Text, _>(text)
+      .sql(")")
+    });
+  };
+
+  if let Some(GeoFilter {latitude, longitude, radius_km}) = near {
+    filters.push(move |query| {
+      query
+      .sql("event_distance_km(events.location, ")
+      .bind::<Double, _>(latitude)
+      .sql(", ")
+      .bind::<Double, _>(longitude)
+      .sql(") <= ")
+      .bind::<Double, _>(radius_km)
+    });
+  };
+
+  filters
+}
+
+type SortKey<'a> = Box<dyn FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a> + Send + 'a>;
+
+/// Returns the aggregate over `filtered_events` used to order the events and the sort direction.
+/// The `event_id` is always the tie breaker so pages are stable.
+fn event_sort_key<'a>(filters: &EventFilters) -> Result<(SortKey<'a>, &'static str)> {
+  Ok(match filters.sort {
+    EventSort::EventId => (Box::new(|query| query.sql("MIN(event_id)")), "ASC"),
+    EventSort::StartDate => (Box::new(|query| query.sql("MIN(start_date)")), "ASC"),
+    EventSort::Price => (
+      Box::new(|query| query.sql("MIN(ticket_type_current_price(sale_type, sale_start_ts, now()))")),
+      "ASC",
+    ),
+    EventSort::Relevance => {
+      let text = filters.text.clone().ok_or_else(|| eyre!("sorting by relevance requires a text search"))?;
+
+      (
+        Box::new(move |query| {
+          query
+          .sql("MAX(ts_rank(event_search_document(name, description, venue), websearch_to_tsquery('simple', ")
+          .bind::<Text, _>(text)
+          .sql(")))")
+        }),
+        "DESC",
+      )
+    },
+    EventSort::Distance => {
+      let GeoFilter {latitude, longitude, ..} = filters.near.clone().ok_or_else(|| eyre!("sorting by distance requires a location"))?;
+
+      (
+        Box::new(move |query| {
+          query
+          .sql("MIN(event_distance_km(location, ")
+          .bind::<Double, _>(latitude)
+          .sql(", ")
+          .bind::<Double, _>(longitude)
+          .sql("))")
+        }),
+        "ASC",
+      )
+    },
+  })
+}
+
+// The filters are appended right after this, followed by the part that selects the page of events.
+// The statuses must match `EventStatus::is_listed`. Ticket types hidden behind an access code are left out.
+const FILTERED_EVENTS_CTE: &str = "
+  WITH filtered_events AS (
+    SELECT *
+    FROM (SELECT * FROM events WHERE events.status IN ('published', 'on_sale', 'paused', 'postponed')) events
+    INNER JOIN ticket_types USING(event_id)
+    INNER JOIN seat_ranges USING(event_id, ticket_type_index)
+    INNER JOIN ticket_type_nft_details USING(event_id, ticket_type_index)
+    INNER JOIN nft_details
+    ON nft_details.arweave_tx_id = ticket_type_nft_details.nft_details_id
+    WHERE NOT EXISTS (
+      SELECT 1 FROM promo_codes
+      WHERE promo_codes.kind = 'access' AND promo_codes.event_id = events.event_id
+      AND promo_codes.ticket_type_index = ticket_types.ticket_type_index
+    )
+    AND ";
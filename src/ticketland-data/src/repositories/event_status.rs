@@ -0,0 +1,264 @@
+use chrono::NaiveDateTime;
+use diesel::{
+  dsl,
+  prelude::*,
+  sql_types::Text,
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  query::QueryBuilder,
+  models::{
+    cnt_transfer::TransferStatus,
+    event::{Event, EventCancellation, EventStatus},
+    refund_request::RefundStatus,
+  },
+  schema::{
+    events::dsl::{
+      self as events_dsl,
+      events,
+    },
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    offers::dsl::{
+      self as offers_dsl,
+      offers,
+    },
+    cnt_transfers::dsl::{
+      self as cnt_transfers_dsl,
+      cnt_transfers,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+    refund_requests::dsl::{
+      self as refund_requests_dsl,
+      refund_requests,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  pub async fn read_event_status(&mut self, evt_id: String) -> Result<EventStatus> {
+    read_status(self.borrow_mut(), &evt_id).await
+  }
+
+  /// Moves the event to the given status if `EventStatus::can_transition_to` allows it. Publishing a draft and
+  /// cancelling have their own operations, `commit_event` and `cancel_event`.
+  pub async fn update_event_status(&mut self, evt_id: String, status: EventStatus) -> Result<Event> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let current = lock_status(conn, &evt_id).await?;
+
+      if status == EventStatus::Cancelled || current == EventStatus::Draft {
+        return Err(invalid_transition(current, status))
+      }
+
+      transition(conn, &evt_id, current, status).await
+    }))
+    .await
+  }
+
+  /// Moves the event to new dates. Primary sales stop until the event is put back on sale.
+  pub async fn postpone_event(
+    &mut self,
+    evt_id: String,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+  ) -> Result<Event> {
+    if end_date < start_date {
+      return Err(Error::InvalidEventStatus("ending before it starts").into())
+    }
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let current = lock_status(conn, &evt_id).await?;
+      transition(conn, &evt_id, current, EventStatus::Postponed).await?;
+
+      Ok(
+        diesel::update(events)
+        .filter(events_dsl::event_id.eq(&evt_id))
+        .set((
+          events_dsl::start_date.eq(start_date),
+          events_dsl::end_date.eq(end_date),
+        ))
+        .get_result::<Event>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  /// Cancels the event. In the same transaction every CNT that has not been attended gets an approved refund
+  /// request, open listings and offers are closed, pending transfers are cancelled and seat holds are released.
+  pub async fn cancel_event(&mut self, evt_id: String) -> Result<EventCancellation> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let current = lock_status(conn, &evt_id).await?;
+      transition(conn, &evt_id, current, EventStatus::Cancelled).await?;
+
+      let closed_listings = diesel::update(listings)
+      .filter(listings_dsl::event_id.eq(&evt_id))
+      .filter(listings_dsl::is_open.eq(true))
+      .set((listings_dsl::closed_at.eq(dsl::now), listings_dsl::is_open.eq(false)))
+      .execute(conn)
+      .await?;
+
+      let closed_offers = diesel::update(offers)
+      .filter(offers_dsl::event_id.eq(&evt_id))
+      .filter(offers_dsl::is_open.eq(true))
+      .set((offers_dsl::closed_at.eq(dsl::now), offers_dsl::is_open.eq(false)))
+      .execute(conn)
+      .await?;
+
+      let cancelled_transfers = diesel::update(cnt_transfers)
+      .filter(cnt_transfers_dsl::event_id.eq(&evt_id))
+      .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+      .set((
+        cnt_transfers_dsl::status.eq(TransferStatus::Cancelled),
+        cnt_transfers_dsl::resolved_at.eq(dsl::now),
+      ))
+      .execute(conn)
+      .await?;
+
+      let released_seat_holds = diesel::delete(seat_holds)
+      .filter(seat_holds_dsl::event_id.eq(&evt_id))
+      .execute(conn)
+      .await?;
+
+      // Refunds the owners already asked for are approved; every other CNT gets a new approved one. Completed
+      // refunds belong to earlier CNTs of the seat, since the refunded CNT was deleted.
+      let approved_refunds = diesel::update(refund_requests)
+      .filter(refund_requests_dsl::event_id.eq(&evt_id))
+      .filter(refund_requests_dsl::status.eq(RefundStatus::Pending))
+      .set(refund_requests_dsl::status.eq(RefundStatus::Approved))
+      .execute(conn)
+      .await?;
+
+      let new_refunds = QueryBuilder::new(
+        "
+        INSERT INTO refund_requests (event_id, seat_index, cnt_sui_address, account_id, reason, status)
+        SELECT event_id, seat_index, cnt_sui_address, account_id, 'event_cancelled', 'approved'
+        FROM cnts
+        WHERE attended = false AND event_id = "
+      )
+      .bind::<Text, _>(evt_id.clone())
+      .sql(
+        "
+        AND NOT EXISTS (
+          SELECT 1 FROM refund_requests
+          WHERE refund_requests.event_id = cnts.event_id AND refund_requests.seat_index = cnts.seat_index
+          AND refund_requests.status = 'approved'
+        )
+        "
+      )
+      .build()
+      .execute(conn)
+      .await?;
+
+      Ok(EventCancellation {
+        event_id: evt_id,
+        refund_requests: approved_refunds + new_refunds,
+        closed_listings,
+        closed_offers,
+        cancelled_transfers,
+        released_seat_holds,
+      })
+    }))
+    .await
+  }
+}
+
+pub(crate) async fn read_status(conn: &mut AsyncPgConnection, evt_id: &str) -> Result<EventStatus> {
+  Ok(
+    events
+    .filter(events_dsl::event_id.eq(evt_id))
+    .select(events_dsl::status)
+    .first::<EventStatus>(conn)
+    .await
+    .optional()?
+    .ok_or(Error::EventNotFound)?
+  )
+}
+
+/// Like `read_status` but the event row stays locked until the end of the transaction
+async fn lock_status(conn: &mut AsyncPgConnection, evt_id: &str) -> Result<EventStatus> {
+  Ok(
+    events
+    .filter(events_dsl::event_id.eq(evt_id))
+    .select(events_dsl::status)
+    .for_update()
+    .first::<EventStatus>(conn)
+    .await
+    .optional()?
+    .ok_or(Error::EventNotFound)?
+  )
+}
+
+async fn transition(
+  conn: &mut AsyncPgConnection,
+  evt_id: &str,
+  current: EventStatus,
+  next: EventStatus,
+) -> Result<Event> {
+  if !current.can_transition_to(next) {
+    return Err(invalid_transition(current, next))
+  }
+
+  Ok(
+    diesel::update(events)
+    .filter(events_dsl::event_id.eq(evt_id))
+    .set(events_dsl::status.eq(next))
+    .get_result::<Event>(conn)
+    .await?
+  )
+}
+
+fn invalid_transition(from: EventStatus, to: EventStatus) -> Report {
+  Error::InvalidEventTransition {from: from.as_str(), to: to.as_str()}.into()
+}
+
+/// Moves a draft event to published as part of `commit_event`. Events that are already past the draft stage
+/// keep their status so committing again only refreshes the on chain addresses.
+pub(crate) async fn publish_draft_event(conn: &mut AsyncPgConnection, evt_id: &str) -> Result<()> {
+  let current = lock_status(conn, evt_id).await?;
+
+  if current == EventStatus::Draft {
+    transition(conn, evt_id, current, EventStatus::Published).await?;
+  }
+
+  Ok(())
+}
+
+/// The status an upserted event must keep: the stored one, or draft for new events. Cancelled and completed
+/// events can no longer be edited.
+pub(crate) async fn editable_event_status(conn: &mut AsyncPgConnection, evt_id: &str) -> Result<EventStatus> {
+  match lock_status(conn, evt_id).await {
+    Ok(status @ (EventStatus::Cancelled | EventStatus::Completed)) => {
+      Err(Error::InvalidEventStatus(status.as_str()).into())
+    },
+    Ok(status) => Ok(status),
+    Err(error) if error.downcast_ref::<Error>() == Some(&Error::EventNotFound) => Ok(EventStatus::Draft),
+    Err(error) => Err(error),
+  }
+}
+
+/// Fails with `Error::InvalidEventStatus` unless the event is in one of the given statuses
+pub(crate) async fn check_event_status(
+  conn: &mut AsyncPgConnection,
+  evt_id: &str,
+  allowed: &[EventStatus],
+) -> Result<()> {
+  let status = read_status(conn, evt_id).await?;
+
+  if !allowed.contains(&status) {
+    return Err(Error::InvalidEventStatus(status.as_str()).into())
+  }
+
+  Ok(())
+}
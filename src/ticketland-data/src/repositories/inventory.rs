@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::{
+    cnt_history::record_ownership_events,
+    event_status::check_event_status,
+    promo_code::check_public_ticket_type,
+    purchase_limit::check_purchase_limits,
+  },
+  models::{
+    cnt::CNT,
+    event::EventStatus,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    ticket_type::TicketType,
+    seat_range::SeatRange,
+    promo_code::PromoCode,
+  },
+  schema::{
+    ticket_types::dsl::{
+      self as ticket_types_dsl,
+      ticket_types,
+    },
+    seat_ranges::dsl::{
+      self as seat_ranges_dsl,
+      seat_ranges,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Reserves the next `count` free seats of the given ticket type for the account and stores them as draft CNTs.
+  /// Seats are taken in order across all the seat ranges of the ticket type, skipping the ones other
+  /// accounts hold (see `hold_seats`).
+  ///
+  /// The ticket type row is locked for the duration of the transaction, so concurrent buyers of the same
+  /// ticket type are serialized and can never be allocated the same seat or go over `n_tickets`.
+  /// Fails with `Error::SaleNotStarted`, `Error::SaleEnded`, `Error::SoldOut` or, if the account would go over one
+  /// of the event's purchase limits, `Error::EventPurchaseLimitExceeded` or `Error::TicketTypePurchaseLimitExceeded`
+  /// (see `crate::error`).
+  pub async fn allocate_seats(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+    count: i32,
+  ) -> Result<Vec<CNT>> {
+    if count <= 0 {
+      return Err(Error::InvalidSeatCount.into())
+    }
+
+    let now = Utc::now().naive_utc();
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      check_public_ticket_type(conn, &event_id, ticket_type_index).await?;
+      allocate(conn, &event_id, ticket_type_index, &account_id, count, now, None).await
+    }))
+    .await
+  }
+
+  /// Number of tickets of the given ticket type that can still be allocated
+  pub async fn read_available_tickets(&mut self, event_id: String, ticket_type_index: i16) -> Result<i64> {
+    let ticket_type = self.read_ticket_type(event_id.clone(), ticket_type_index).await?;
+    let sold = cnts
+    .filter(cnts_dsl::event_id.eq(event_id))
+    .filter(cnts_dsl::ticket_type_index.eq(ticket_type_index))
+    .count()
+    .get_result::<i64>(self.borrow_mut())
+    .await?;
+
+    Ok((ticket_type.n_tickets as i64 - sold).max(0))
+  }
+}
+
+/// Allocates the next `count` free seats, see `allocate_seats`. The CNTs record the price at `now`, with the promo
+/// code applied if one is given. Must run in a transaction.
+pub(crate) async fn allocate(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  account_id: &str,
+  count: i32,
+  now: NaiveDateTime,
+  promo_code: Option<&PromoCode>,
+) -> Result<Vec<CNT>> {
+  let ticket_type = lock_ticket_type_for_sale(conn, event_id, ticket_type_index, count as i64, now).await?;
+  let price = ticket_type.sale_type.price_at(ticket_type.sale_start_ts, now);
+  let price = promo_code.map_or(price, |promo_code| promo_code.apply(price));
+  check_purchase_limits(conn, event_id, account_id, ticket_type_index, count as i64, &[]).await?;
+
+  let ranges = seat_ranges
+  .filter(seat_ranges_dsl::event_id.eq(event_id))
+  .filter(seat_ranges_dsl::ticket_type_index.eq(ticket_type_index))
+  .order(seat_ranges_dsl::l)
+  .load::<SeatRange>(conn)
+  .await?;
+
+  // Seat indexes are unique per event, so a seat is taken no matter which ticket type it was sold as.
+  // Seats on hold for other accounts are skipped as well.
+  let mut taken = cnts
+  .filter(cnts_dsl::event_id.eq(event_id))
+  .select(cnts_dsl::seat_index)
+  .load::<i32>(conn)
+  .await?
+  .into_iter()
+  .collect::<HashSet<_>>();
+
+  taken.extend(
+    seat_holds
+    .filter(seat_holds_dsl::event_id.eq(event_id))
+    .filter(seat_holds_dsl::account_id.ne(account_id))
+    .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+    .select(seat_holds_dsl::seat_index)
+    .load::<i32>(conn)
+    .await?
+  );
+
+  let seats = free_seats(&ranges, &taken, count as usize).ok_or(Error::SoldOut)?;
+  let new_cnts = seats
+  .into_iter()
+  .map(|seat_index| new_draft_cnt(event_id, account_id, ticket_type_index, seat_index, price))
+  .collect::<Vec<_>>();
+
+  let new_cnts = diesel::insert_into(cnts)
+  .values(&new_cnts)
+  .get_results::<CNT>(conn)
+  .await?;
+
+  let events = new_cnts
+  .iter()
+  .map(|cnt| NewCntOwnershipEvent::new(OwnershipEventKind::Issued, cnt, None))
+  .collect();
+  record_ownership_events(conn, events).await?;
+
+  Ok(new_cnts)
+}
+
+/// Locks the ticket type row until the end of the current transaction and checks that `count` more tickets
+/// can be sold right now. The event must be on sale.
+pub(crate) async fn lock_ticket_type_for_sale(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  count: i64,
+  now: NaiveDateTime,
+) -> Result<TicketType> {
+  check_event_status(conn, event_id, &[EventStatus::OnSale]).await?;
+
+  let ticket_type = ticket_types
+  .filter(ticket_types_dsl::event_id.eq(event_id))
+  .filter(ticket_types_dsl::ticket_type_index.eq(ticket_type_index))
+  .for_update()
+  .first::<TicketType>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::TicketTypeNotFound)?;
+
+  check_sale_window(&ticket_type, now)?;
+
+  let sold = cnts
+  .filter(cnts_dsl::event_id.eq(event_id))
+  .filter(cnts_dsl::ticket_type_index.eq(ticket_type_index))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  if sold + count > ticket_type.n_tickets as i64 {
+    return Err(Error::SoldOut.into())
+  }
+
+  Ok(ticket_type)
+}
+
+pub(crate) fn new_draft_cnt(
+  event_id: &str,
+  account_id: &str,
+  ticket_type_index: i16,
+  seat_index: i32,
+  purchase_price: u64,
+) -> CNT {
+  CNT {
+    event_id: event_id.to_string(),
+    account_id: account_id.to_string(),
+    ticket_type_index,
+    seat_name: seat_index.to_string(),
+    seat_index,
+    draft: true,
+    purchase_price: Some(purchase_price as i64),
+    ..Default::default()
+  }
+}
+
+fn check_sale_window(ticket_type: &TicketType, now: NaiveDateTime) -> Result<(), Error> {
+  if now < ticket_type.sale_start_ts {
+    return Err(Error::SaleNotStarted)
+  }
+
+  if now >= ticket_type.sale_end_ts {
+    return Err(Error::SaleEnded)
+  }
+
+  Ok(())
+}
+
+/// The first `count` seats of the ranges that are not taken, or None if there are not enough of them
+fn free_seats(ranges: &[SeatRange], taken: &HashSet<i32>, count: usize) -> Option<Vec<i32>> {
+  let seats = ranges
+  .iter()
+  .flat_map(|range| range.seats())
+  .filter(|seat_index| !taken.contains(seat_index))
+  .take(count)
+  .collect::<Vec<_>>();
+
+  (seats.len() == count).then_some(seats)
+}
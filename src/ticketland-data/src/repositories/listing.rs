@@ -1,15 +1,28 @@
 use diesel::{
   prelude::*,
-  result::Error,
   dsl,
-  sql_query,
+  sql_types::{BigInt, Text},
 };
-use eyre::Result;
-use diesel_async::{AsyncConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  cursor::{Cursor, coalesce_ts},
+  query::QueryBuilder,
+  repositories::{
+    trade::insert_trade,
+    cnt_history::record_ownership_events,
+    resale_policy::{check_resale_price, resale_proceeds},
+    event_status::check_event_status,
+    refund_request::check_no_open_refund,
+  },
   models::{
     listing::{NewListing, Listing},
+    cnt::CNT,
+    event::EventStatus,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    trade::{NewTrade, Trade},
   },
   schema::{
     listings::dsl::*,
@@ -20,8 +33,20 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Fails with `Error::ResalePriceAboveCap` if the ask price is above the event's resale cap, with
+  /// `Error::InvalidEventStatus` if the event is not trading e.g. because it was cancelled, or with
+  /// `Error::CntRefundPending` if the CNT is being refunded
   pub async fn upsert_listing(&mut self, listing: NewListing<'_>) -> Result<()> {
+    let cnt = cnts
+    .filter(cnts_dsl::cnt_sui_address.eq(listing.cnt_sui_address))
+    .first::<CNT>(self.borrow_mut())
+    .await?;
+
+    check_event_status(self.borrow_mut(), listing.event_id, &EventStatus::TRADING).await?;
+    check_no_open_refund(self.borrow_mut(), &cnt).await?;
+    check_resale_price(self.borrow_mut(), listing.event_id, cnt.ticket_type_index, listing.ask_price).await?;
+
     diesel::insert_into(listings)
     .values(&listing)
     .on_conflict(listing_id)
@@ -47,6 +72,7 @@ impl PostgresConnection {
       listings
       .filter(event_id.eq(evt_id))
       .filter(is_open.eq(true))
+      .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
       .filter(draft.eq(false))
       .limit(limit)
       .offset(skip * limit)
@@ -56,6 +82,44 @@ impl PostgresConnection {
     )
   }
 
+  /// Keyset paginated version of `read_listings_for_event`. Pages are ordered by `(created_at, listing_id)`
+  /// descending and the returned cursor, if any, points to the next page.
+  pub async fn read_listings_for_event_after(
+    &mut self,
+    evt_id: String,
+    cursor: Option<String>,
+    limit: i64,
+  ) -> Result<(Vec<Listing>, Option<String>)> {
+    let mut query = listings
+    .filter(event_id.eq(evt_id))
+    .filter(is_open.eq(true))
+    .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
+    .filter(draft.eq(false))
+    .into_boxed();
+
+    // Rows without a created_at sort last instead of dropping out of the keyset
+    let created_at_key = || coalesce_ts(created_at, Cursor::epoch());
+
+    if let Some(cursor) = Cursor::decode_opt(cursor)? {
+      query = query.filter(
+        created_at_key().lt(cursor.ts)
+        .or(created_at_key().eq(cursor.ts).and(listing_id.lt(cursor.id)))
+      );
+    }
+
+    let records: Vec<Listing> = query
+    .order_by((created_at_key().desc(), listing_id.desc()))
+    .limit(limit)
+    .load(self.borrow_mut())
+    .await?;
+
+    let next_cursor = Cursor::next(&records, limit, |record| {
+      Some(Cursor::new(Cursor::created_at(record.created_at), record.listing_id.clone()))
+    });
+
+    Ok((records, next_cursor))
+  }
+
   pub async fn read_listings_for_account(
     &mut self,
     evt_id: Option<String>,
@@ -63,24 +127,22 @@ impl PostgresConnection {
     skip: i64,
     limit: i64
   ) -> Result<Vec<Listing>> {
-    let evt_id_filter = if let Some(evt_id) = evt_id {
-      format!("AND event_id = '{}'", evt_id)
-    } else {
-      "".to_string()
-    };
-
-    let query = sql_query(format!(
-      "
-      SELECT * FROM listings
-      WHERE account_id = '{}' AND is_open = true {}
-      ORDER BY created_at DESC
-      LIMIT {} OFFSET {}
-      ",
-      uid,
-      evt_id_filter,
-      limit,
-      skip * limit
-    ));
+    let mut query = QueryBuilder::new("SELECT * FROM listings WHERE account_id = ")
+    .bind::<Text, _>(uid)
+    .sql(" AND is_open = true AND (expires_at IS NULL OR expires_at > now())");
+
+    if let Some(evt_id) = evt_id {
+      query = query
+      .sql(" AND event_id = ")
+      .bind::<Text, _>(evt_id);
+    }
+
+    let query = query
+    .sql(" ORDER BY created_at DESC LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .build();
 
     Ok(query.load::<Listing>(self.borrow_mut()).await?)
   }
@@ -96,31 +158,66 @@ impl PostgresConnection {
     Ok(())
   }
 
+  /// Closes the listing and transfers its CNT to the buyer. Returns the recorded trade, or
+  /// `Error::ListingNotAvailable` if the listing is not open, has expired, is not for `cnt_address` or its
+  /// CNT no longer belongs to the seller, and `Error::CntRefundPending` if the CNT is being refunded
   pub async fn fill_listing(
     &mut self,
     id: String,
     cnt_address: String,
     new_owner: String,
-  ) -> Result<()> {
+  ) -> Result<Trade> {
     self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
-      diesel::update(listings)
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let listing = diesel::update(listings)
       .filter(listing_id.eq(id))
+      .filter(is_open.eq(true))
+      .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
       .set((closed_at.eq(dsl::now), is_open.eq(false)))
-      .execute(conn)
+      .get_result::<Listing>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::ListingNotAvailable)?;
+
+      if cnt_address != listing.cnt_sui_address {
+        return Err(Error::ListingNotAvailable.into())
+      }
+
+      let cnt = lock_listed_cnt(conn, &listing).await?;
+      check_no_open_refund(conn, &cnt).await?;
+
+      let cnt = diesel::update(cnts)
+      .filter(cnts_dsl::cnt_sui_address.eq(&cnt_address))
+      .set(cnts_dsl::account_id.eq(&new_owner))
+      .get_result::<CNT>(conn)
       .await?;
 
-      diesel::update(cnts)
-      .filter(cnts_dsl::cnt_sui_address.eq(cnt_address))
-      .set(cnts_dsl::account_id.eq(new_owner))
-      .execute(conn)
+      let (royalty, seller_proceeds) = resale_proceeds(conn, &listing.event_id, listing.ask_price).await?;
+
+      let seller = listing.account_id.clone();
+      let trade = insert_trade(conn, NewTrade {
+        event_id: listing.event_id,
+        ticket_type_index: cnt.ticket_type_index,
+        cnt_sui_address: cnt_address,
+        listing_id: Some(listing.listing_id),
+        offer_id: None,
+        seller_account_id: listing.account_id,
+        buyer_account_id: new_owner,
+        price: listing.ask_price,
+        royalty,
+        seller_proceeds,
+      })
       .await?;
 
-      Ok(())
-    }))
-    .await?;
+      record_ownership_events(conn, vec![NewCntOwnershipEvent {
+        trade_id: Some(trade.trade_id),
+        ..NewCntOwnershipEvent::new(OwnershipEventKind::Sold, &cnt, Some(seller))
+      }])
+      .await?;
 
-    Ok(())
+      Ok(trade)
+    }))
+    .await
   }
 
   pub async fn update_listing_draft(&mut self, account: &String, id: &String) -> Result<()> {
@@ -134,3 +231,19 @@ impl PostgresConnection {
     Ok(())
   }
 }
+
+/// Locks the CNT of the listing. Fails with `Error::ListingNotAvailable` if the CNT no longer belongs to the
+/// seller or to the listing's event e.g. because the listing went stale after a transfer
+pub(crate) async fn lock_listed_cnt(conn: &mut AsyncPgConnection, listing: &Listing) -> Result<CNT> {
+  let cnt = cnts
+  .filter(cnts_dsl::cnt_sui_address.eq(&listing.cnt_sui_address))
+  .for_update()
+  .first::<CNT>(conn)
+  .await
+  .optional()?;
+
+  match cnt {
+    Some(cnt) if cnt.account_id == listing.account_id && cnt.event_id == listing.event_id => Ok(cnt),
+    _ => Err(Error::ListingNotAvailable.into()),
+  }
+}
@@ -0,0 +1,195 @@
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  repositories::{
+    trade::insert_trade,
+    cnt_history::record_ownership_events,
+    resale_policy::resale_proceeds,
+    listing::lock_listed_cnt,
+    refund_request::check_no_open_refund,
+  },
+  models::{
+    listing::Listing,
+    offer::Offer,
+    cnt::CNT,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    trade::{NewTrade, Trade},
+  },
+  schema::{
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    offers::dsl::{
+      self as offers_dsl,
+      offers,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Matches an open listing against the best crossing offer for the same event and ticket type, i.e. the
+  /// highest bid that is at least the ask price, oldest first on equal bids. Offers of the seller are ignored.
+  /// The trade happens at the bid price since the offer was already in the book.
+  ///
+  /// Returns None if the listing is not open or there is no crossing offer.
+  pub async fn match_listing(&mut self, listing_id: String) -> Result<Option<Trade>> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let listing = listings
+      .filter(listings_dsl::listing_id.eq(&listing_id))
+      .filter(listings_dsl::is_open.eq(true))
+      .filter(listings_dsl::expires_at.is_null().or(listings_dsl::expires_at.gt(dsl::now)))
+      .filter(listings_dsl::draft.eq(false))
+      .for_update()
+      .first::<Listing>(conn)
+      .await
+      .optional()?;
+
+      let Some(listing) = listing else {return Ok(None)};
+
+      // The CNT must still belong to the seller
+      let ticket_type_index = cnts
+      .filter(cnts_dsl::cnt_sui_address.eq(&listing.cnt_sui_address))
+      .filter(cnts_dsl::account_id.eq(&listing.account_id))
+      .select(cnts_dsl::ticket_type_index)
+      .first::<i16>(conn)
+      .await
+      .optional()?;
+
+      let Some(ticket_type_index) = ticket_type_index else {return Ok(None)};
+
+      let offer = offers
+      .filter(offers_dsl::event_id.eq(&listing.event_id))
+      .filter(offers_dsl::ticket_type_index.eq(ticket_type_index))
+      .filter(offers_dsl::is_open.eq(true))
+      .filter(offers_dsl::expires_at.is_null().or(offers_dsl::expires_at.gt(dsl::now)))
+      .filter(offers_dsl::draft.eq(false))
+      .filter(offers_dsl::account_id.ne(&listing.account_id))
+      .filter(offers_dsl::bid_price.ge(listing.ask_price))
+      .order_by((offers_dsl::bid_price.desc(), offers_dsl::created_at.asc(), offers_dsl::offer_id.asc()))
+      .for_update()
+      .skip_locked()
+      .first::<Offer>(conn)
+      .await
+      .optional()?;
+
+      let Some(offer) = offer else {return Ok(None)};
+      let price = offer.bid_price;
+
+      Ok(Some(fill_match(conn, listing, offer, ticket_type_index, price).await?))
+    }))
+    .await
+  }
+
+  /// Matches an open offer against the best crossing listing for the same event and ticket type, i.e. the
+  /// lowest ask that is at most the bid price, oldest first on equal asks. Listings of the buyer are ignored.
+  /// The trade happens at the ask price since the listing was already in the book.
+  ///
+  /// Returns None if the offer is not open or there is no crossing listing.
+  pub async fn match_offer(&mut self, offer_id: String) -> Result<Option<Trade>> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let offer = offers
+      .filter(offers_dsl::offer_id.eq(&offer_id))
+      .filter(offers_dsl::is_open.eq(true))
+      .filter(offers_dsl::expires_at.is_null().or(offers_dsl::expires_at.gt(dsl::now)))
+      .filter(offers_dsl::draft.eq(false))
+      .for_update()
+      .first::<Offer>(conn)
+      .await
+      .optional()?;
+
+      let Some(offer) = offer else {return Ok(None)};
+
+      let listing = listings
+      .inner_join(cnts.on(
+        cnts_dsl::cnt_sui_address.eq(listings_dsl::cnt_sui_address.nullable())
+        .and(cnts_dsl::account_id.eq(listings_dsl::account_id))
+      ))
+      .filter(cnts_dsl::event_id.eq(&offer.event_id))
+      .filter(cnts_dsl::ticket_type_index.eq(offer.ticket_type_index))
+      .filter(listings_dsl::is_open.eq(true))
+      .filter(listings_dsl::expires_at.is_null().or(listings_dsl::expires_at.gt(dsl::now)))
+      .filter(listings_dsl::draft.eq(false))
+      .filter(listings_dsl::account_id.ne(&offer.account_id))
+      .filter(listings_dsl::ask_price.le(offer.bid_price))
+      .order_by((listings_dsl::ask_price.asc(), listings_dsl::created_at.asc(), listings_dsl::listing_id.asc()))
+      .select(listings::all_columns())
+      .for_update()
+      .skip_locked()
+      .first::<Listing>(conn)
+      .await
+      .optional()?;
+
+      let Some(listing) = listing else {return Ok(None)};
+      let (ticket_type_index, price) = (offer.ticket_type_index, listing.ask_price);
+
+      Ok(Some(fill_match(conn, listing, offer, ticket_type_index, price).await?))
+    }))
+    .await
+  }
+}
+
+/// Closes both orders, transfers the CNT to the buyer and records the trade and the ownership change, like `fill_listing` and `fill_offer` do.
+/// Both orders must be locked by the current transaction. Fails with `Error::ListingNotAvailable` if the CNT no longer
+/// belongs to the seller and with `Error::CntRefundPending` if it is being refunded.
+async fn fill_match(
+  conn: &mut AsyncPgConnection,
+  listing: Listing,
+  offer: Offer,
+  ticket_type_index: i16,
+  price: i64,
+) -> Result<Trade> {
+  diesel::update(listings)
+  .filter(listings_dsl::listing_id.eq(&listing.listing_id))
+  .set((listings_dsl::closed_at.eq(dsl::now), listings_dsl::is_open.eq(false)))
+  .execute(conn)
+  .await?;
+
+  diesel::update(offers)
+  .filter(offers_dsl::offer_id.eq(&offer.offer_id))
+  .set((offers_dsl::closed_at.eq(dsl::now), offers_dsl::is_open.eq(false)))
+  .execute(conn)
+  .await?;
+
+  let cnt = lock_listed_cnt(conn, &listing).await?;
+  check_no_open_refund(conn, &cnt).await?;
+
+  let cnt = diesel::update(cnts)
+  .filter(cnts_dsl::cnt_sui_address.eq(&listing.cnt_sui_address))
+  .set(cnts_dsl::account_id.eq(&offer.account_id))
+  .get_result::<CNT>(conn)
+  .await?;
+
+  let (royalty, seller_proceeds) = resale_proceeds(conn, &listing.event_id, price).await?;
+
+  let seller = listing.account_id.clone();
+  let trade = insert_trade(conn, NewTrade {
+    event_id: listing.event_id,
+    ticket_type_index,
+    cnt_sui_address: listing.cnt_sui_address,
+    listing_id: Some(listing.listing_id),
+    offer_id: Some(offer.offer_id),
+    seller_account_id: listing.account_id,
+    buyer_account_id: offer.account_id,
+    price,
+    royalty,
+    seller_proceeds,
+  })
+  .await?;
+
+  record_ownership_events(conn, vec![NewCntOwnershipEvent {
+    trade_id: Some(trade.trade_id),
+    ..NewCntOwnershipEvent::new(OwnershipEventKind::Sold, &cnt, Some(seller))
+  }])
+  .await?;
+
+  Ok(trade)
+}
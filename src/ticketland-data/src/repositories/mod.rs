@@ -10,3 +10,18 @@ pub mod sales;
 pub mod offer;
 pub mod listing;
 pub mod nfts;
+pub mod inventory;
+pub mod seat_hold;
+pub mod matching;
+pub mod order_book;
+pub mod trade;
+pub mod resale_policy;
+pub mod order;
+pub mod cnt_transfer;
+pub mod cnt_history;
+pub mod event_status;
+pub mod refund_request;
+pub mod waitlist;
+pub mod promo_code;
+pub mod purchase_limit;
+pub mod check_in;
@@ -1,56 +1,22 @@
-use diesel::sql_query;
 use eyre::Result;
-use diesel::result::Error;
-use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel_async::RunQueryDsl;
 use crate::{
-  connection::PostgresConnection,
-  models::nft::NewTicketTypeNft
+  connection::{ConnectionHandle, PostgresConnection},
+  models::nft::NewTicketTypeNft,
+  schema::ticket_type_nfts::dsl::ticket_type_nfts,
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_claimed_nfts(
     &mut self,
-    ticket_type_nfts: Vec<NewTicketTypeNft>,
+    ticket_type_nfts_list: Vec<NewTicketTypeNft>,
   ) -> Result<()> {
-    self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
-      // We cannot do multiple UPDATEs in one query, so these need to be separate
-      for ticket_type_nft in ticket_type_nfts.iter() {
-        let update_query = format!(
-          "
-          INSERT INTO ticket_type_nfts (
-            ticket_type_nft_sui_address,
-            cnt_sui_address,
-            account_id,
-            ref_name,
-            event_id,
-            ticket_type_index
-          )
-          VALUES (
-            '{}',
-            '{}',
-            '{}',
-            '{}',
-            '{}',
-            {}
-          )
-          ",
-          ticket_type_nft.ticket_type_nft_sui_address,
-          ticket_type_nft.cnt_sui_address,
-          ticket_type_nft.account_id,
-          ticket_type_nft.ref_name,
-          ticket_type_nft.event_id,
-          ticket_type_nft.ticket_type_index,
-        );
-
-        sql_query(update_query).execute(conn).await?;
-      }
-
-      Ok(())
-    }))
+    // A single multi row INSERT is atomic so there is no need for an explicit transaction
+    diesel::insert_into(ticket_type_nfts)
+    .values(&ticket_type_nfts_list)
+    .execute(self.borrow_mut())
     .await?;
 
-
     Ok(())
   }
 }
@@ -1,27 +1,49 @@
 use diesel::{
   prelude::*,
-  result::Error,
   dsl,
-  sql_query,
+  sql_types::{BigInt, Text},
 };
-use eyre::Result;
+use eyre::{Report, Result};
 use diesel_async::{AsyncConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  cursor::{Cursor, coalesce_ts},
+  query::QueryBuilder,
+  repositories::{
+    trade::insert_trade,
+    cnt_history::record_ownership_events,
+    resale_policy::{check_resale_price, resale_proceeds},
+    event_status::check_event_status,
+    refund_request::check_no_open_refund,
+  },
   models::{
     offer::{NewOffer, Offer},
+    cnt::CNT,
+    event::EventStatus,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    trade::{NewTrade, Trade},
   },
   schema::{
     offers::dsl::*,
     cnts::dsl::{
       self as cnts_dsl,
       cnts,
-    }
+    },
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Fails with `Error::ResalePriceAboveCap` if the bid price is above the event's resale cap, or with
+  /// `Error::InvalidEventStatus` if the event is not trading e.g. because it was cancelled
   pub async fn upsert_offer(&mut self, offer: NewOffer<'_>) -> Result<()> {
+    check_event_status(self.borrow_mut(), offer.event_id, &EventStatus::TRADING).await?;
+    check_resale_price(self.borrow_mut(), offer.event_id, offer.ticket_type_index, offer.bid_price).await?;
+
     diesel::insert_into(offers)
     .values(&offer)
     .on_conflict(offer_id)
@@ -47,6 +69,7 @@ impl PostgresConnection {
       offers
       .filter(event_id.eq(evt_id))
       .filter(is_open.eq(true))
+      .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
       .filter(draft.eq(false))
       .limit(limit)
       .offset(skip * limit)
@@ -56,6 +79,44 @@ impl PostgresConnection {
     )
   }
 
+  /// Keyset paginated version of `read_offers_for_event`. Pages are ordered by `(created_at, offer_id)`
+  /// descending and the returned cursor, if any, points to the next page.
+  pub async fn read_offers_for_event_after(
+    &mut self,
+    evt_id: String,
+    cursor: Option<String>,
+    limit: i64,
+  ) -> Result<(Vec<Offer>, Option<String>)> {
+    let mut query = offers
+    .filter(event_id.eq(evt_id))
+    .filter(is_open.eq(true))
+    .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
+    .filter(draft.eq(false))
+    .into_boxed();
+
+    // Rows without a created_at sort last instead of dropping out of the keyset
+    let created_at_key = || coalesce_ts(created_at, Cursor::epoch());
+
+    if let Some(cursor) = Cursor::decode_opt(cursor)? {
+      query = query.filter(
+        created_at_key().lt(cursor.ts)
+        .or(created_at_key().eq(cursor.ts).and(offer_id.lt(cursor.id)))
+      );
+    }
+
+    let records: Vec<Offer> = query
+    .order_by((created_at_key().desc(), offer_id.desc()))
+    .limit(limit)
+    .load(self.borrow_mut())
+    .await?;
+
+    let next_cursor = Cursor::next(&records, limit, |record| {
+      Some(Cursor::new(Cursor::created_at(record.created_at), record.offer_id.clone()))
+    });
+
+    Ok((records, next_cursor))
+  }
+
   pub async fn read_offers_for_account(
     &mut self,
     evt_id: Option<String>,
@@ -63,24 +124,22 @@ impl PostgresConnection {
     skip: i64,
     limit: i64
   ) -> Result<Vec<Offer>> {
-    let evt_id_filter = if let Some(evt_id) = evt_id {
-      format!("AND event_id = '{}'", evt_id)
-    } else {
-      "".to_string()
-    };
-
-    let query = sql_query(format!(
-      "
-      SELECT * FROM offers
-      WHERE account_id = '{}' AND is_open = true {}
-      ORDER BY created_at DESC
-      LIMIT {} OFFSET {}
-      ",
-      uid,
-      evt_id_filter,
-      limit,
-      skip * limit
-    ));
+    let mut query = QueryBuilder::new("SELECT * FROM offers WHERE account_id = ")
+    .bind::<Text, _>(uid)
+    .sql(" AND is_open = true AND (expires_at IS NULL OR expires_at > now())");
+
+    if let Some(evt_id) = evt_id {
+      query = query
+      .sql(" AND event_id = ")
+      .bind::<Text, _>(evt_id);
+    }
+
+    let query = query
+    .sql(" ORDER BY created_at DESC LIMIT ")
+    .bind::<BigInt, _>(limit)
+    .sql(" OFFSET ")
+    .bind::<BigInt, _>(skip * limit)
+    .build();
 
     Ok(query.load::<Offer>(self.borrow_mut()).await?)
   }
@@ -96,31 +155,81 @@ impl PostgresConnection {
     Ok(())
   }
 
+  /// Closes the offer and any open listing of the CNT, and transfers the CNT from its current owner to the buyer.
+  /// Returns the recorded trade, or `Error::OfferNotAvailable` if the offer is not open or has expired,
+  /// `Error::CntNotForOffer` if the CNT is of another event or ticket type and `Error::CntRefundPending` if the
+  /// CNT is being refunded
   pub async fn fill_offer(
     &mut self,
     id: String,
     cnt_sui_address: String,
     new_owner: String,
-  ) -> Result<()> {
+  ) -> Result<Trade> {
     self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
-      diesel::update(offers)
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let offer = diesel::update(offers)
       .filter(offer_id.eq(id))
+      .filter(is_open.eq(true))
+      .filter(expires_at.is_null().or(expires_at.gt(dsl::now)))
       .set((closed_at.eq(dsl::now), is_open.eq(false)))
+      .get_result::<Offer>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::OfferNotAvailable)?;
+
+      let cnt = cnts
+      .filter(cnts_dsl::cnt_sui_address.eq(&cnt_sui_address))
+      .for_update()
+      .first::<CNT>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::CntNotFound)?;
+
+      if cnt.event_id != offer.event_id || cnt.ticket_type_index != offer.ticket_type_index {
+        return Err(Error::CntNotForOffer.into())
+      }
+
+      check_no_open_refund(conn, &cnt).await?;
+
+      // The seller's listings of the CNT would otherwise sell a ticket they no longer own
+      diesel::update(listings)
+      .filter(listings_dsl::cnt_sui_address.eq(&cnt_sui_address))
+      .filter(listings_dsl::is_open.eq(true))
+      .set((listings_dsl::closed_at.eq(dsl::now), listings_dsl::is_open.eq(false)))
       .execute(conn)
       .await?;
 
-      diesel::update(cnts)
-      .filter(cnts_dsl::cnt_sui_address.eq(cnt_sui_address))
-      .set(cnts_dsl::account_id.eq(new_owner))
-      .execute(conn)
+      let sold_cnt = diesel::update(cnts)
+      .filter(cnts_dsl::cnt_sui_address.eq(&cnt_sui_address))
+      .set(cnts_dsl::account_id.eq(&new_owner))
+      .get_result::<CNT>(conn)
       .await?;
 
-      Ok(())
-    }))
-    .await?;
+      let (royalty, seller_proceeds) = resale_proceeds(conn, &offer.event_id, offer.bid_price).await?;
 
-    Ok(())
+      let trade = insert_trade(conn, NewTrade {
+        event_id: offer.event_id,
+        ticket_type_index: cnt.ticket_type_index,
+        cnt_sui_address,
+        listing_id: None,
+        offer_id: Some(offer.offer_id),
+        seller_account_id: cnt.account_id.clone(),
+        buyer_account_id: new_owner,
+        price: offer.bid_price,
+        royalty,
+        seller_proceeds,
+      })
+      .await?;
+
+      record_ownership_events(conn, vec![NewCntOwnershipEvent {
+        trade_id: Some(trade.trade_id),
+        ..NewCntOwnershipEvent::new(OwnershipEventKind::Sold, &sold_cnt, Some(cnt.account_id))
+      }])
+      .await?;
+
+      Ok(trade)
+    }))
+    .await
   }
 
   pub async fn update_offer_draft(&mut self, account: &String, id: &String) -> Result<()> {
@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  models::{
+    listing::Listing,
+    offer::Offer,
+    order::ExpiredOrders,
+  },
+  schema::{
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    offers::dsl::{
+      self as offers_dsl,
+      offers,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Closes every open listing and offer that expired at or before `now`. Like cancelled orders, expired
+  /// orders are closed without `closed_at`, which is reserved for filled orders. Returns the closed orders.
+  pub async fn expire_stale_orders(&mut self, now: NaiveDateTime) -> Result<ExpiredOrders> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let expired_listings = diesel::update(listings)
+      .filter(listings_dsl::is_open.eq(true))
+      .filter(listings_dsl::expires_at.le(now))
+      .set(listings_dsl::is_open.eq(false))
+      .get_results::<Listing>(conn)
+      .await?;
+
+      let expired_offers = diesel::update(offers)
+      .filter(offers_dsl::is_open.eq(true))
+      .filter(offers_dsl::expires_at.le(now))
+      .set(offers_dsl::is_open.eq(false))
+      .get_results::<Offer>(conn)
+      .await?;
+
+      Ok(ExpiredOrders {
+        listings: expired_listings,
+        offers: expired_offers,
+      })
+    }))
+    .await
+  }
+}
@@ -0,0 +1,64 @@
+use diesel::sql_types::Text;
+use eyre::Result;
+use diesel_async::RunQueryDsl;
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  query::QueryBuilder,
+  models::order_book::{OrderBook, PriceLevel, LastTrade},
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Snapshot of the open, non draft and non expired listings and offers of an event aggregated per ticket type
+  /// and price, with at most `depth` price levels per side.
+  pub async fn read_order_book(&mut self, evt_id: String, depth: i64) -> Result<OrderBook> {
+    let bids = QueryBuilder::new(
+      "
+      SELECT ticket_type_index, bid_price AS price, COUNT(*) AS quantity
+      FROM offers
+      WHERE is_open = true AND draft = false AND (expires_at IS NULL OR expires_at > now()) AND event_id = "
+    )
+    .bind::<Text, _>(evt_id.clone())
+    .sql(
+      "
+      GROUP BY ticket_type_index, bid_price
+      ORDER BY ticket_type_index, bid_price DESC
+      "
+    )
+    .build()
+    .load::<PriceLevel>(self.borrow_mut())
+    .await?;
+
+    let asks = QueryBuilder::new(
+      "
+      SELECT cnts.ticket_type_index, listings.ask_price AS price, COUNT(*) AS quantity
+      FROM listings
+      INNER JOIN cnts ON cnts.cnt_sui_address = listings.cnt_sui_address
+      WHERE listings.is_open = true AND listings.draft = false
+      AND (listings.expires_at IS NULL OR listings.expires_at > now()) AND listings.event_id = "
+    )
+    .bind::<Text, _>(evt_id.clone())
+    .sql(
+      "
+      GROUP BY cnts.ticket_type_index, listings.ask_price
+      ORDER BY cnts.ticket_type_index, listings.ask_price ASC
+      "
+    )
+    .build()
+    .load::<PriceLevel>(self.borrow_mut())
+    .await?;
+
+    let last_trades = QueryBuilder::new(
+      "
+      SELECT DISTINCT ON (ticket_type_index) ticket_type_index, price, created_at AS traded_at
+      FROM trades
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(evt_id.clone())
+    .sql(" ORDER BY ticket_type_index, created_at DESC, trade_id DESC")
+    .build()
+    .load::<LastTrade>(self.borrow_mut())
+    .await?;
+
+    Ok(OrderBook::new(evt_id, bids, asks, last_trades, depth.max(0) as usize))
+  }
+}
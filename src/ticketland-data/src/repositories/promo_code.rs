@@ -0,0 +1,304 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+  prelude::*,
+  result::{DatabaseErrorKind, Error as DieselError},
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::inventory::allocate,
+  models::{
+    cnt::CNT,
+    promo_code::{normalize_code, NewPromoCode, PriceQuote, PromoCode, PromoCodeKind},
+    ticket_type::TicketType,
+  },
+  schema::{
+    promo_codes::dsl::{
+      self as promo_codes_dsl,
+      promo_codes,
+    },
+    ticket_types::dsl::{
+      self as ticket_types_dsl,
+      ticket_types,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  pub async fn create_promo_code(&mut self, mut promo_code: NewPromoCode) -> Result<PromoCode> {
+    promo_code.validate()?;
+    promo_code.code = normalize_code(&promo_code.code);
+
+    let conn = self.borrow_mut();
+    if read_code(conn, &promo_code.event_id, &promo_code.code).await?.is_some() {
+      return Err(Error::InvalidPromoCode("code already exists".to_string()).into())
+    }
+
+    // The check above misses codes created concurrently, which the unique index rejects instead
+    diesel::insert_into(promo_codes)
+    .values(&promo_code)
+    .get_result::<PromoCode>(conn)
+    .await
+    .map_err(|error| match error {
+      DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+        Error::InvalidPromoCode("code already exists".to_string()).into()
+      },
+      error => error.into(),
+    })
+  }
+
+  pub async fn read_event_promo_codes(&mut self, evt_id: String) -> Result<Vec<PromoCode>> {
+    Ok(
+      promo_codes
+      .filter(promo_codes_dsl::event_id.eq(evt_id))
+      .order_by(promo_codes_dsl::created_at.asc())
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// Deleting the last access code of a ticket type makes it public
+  pub async fn delete_promo_code(&mut self, evt_id: String, code: String) -> Result<()> {
+    diesel::delete(promo_codes)
+    .filter(promo_codes_dsl::event_id.eq(evt_id))
+    .filter(promo_codes_dsl::code.eq(normalize_code(&code)))
+    .execute(self.borrow_mut())
+    .await?;
+
+    Ok(())
+  }
+
+  /// The hidden ticket type an access code unlocks
+  pub async fn read_access_code_ticket_type(&mut self, evt_id: String, code: String) -> Result<TicketType> {
+    let now = Utc::now().naive_utc();
+    let conn = self.borrow_mut();
+    let promo_code = read_usable_code(conn, &evt_id, &code, None, now).await?;
+
+    if promo_code.kind != PromoCodeKind::Access {
+      return Err(Error::PromoCodeNotFound.into())
+    }
+
+    let ticket_type_index = promo_code.ticket_type_index.ok_or(Error::PromoCodeNotFound)?;
+
+    read_ticket_type(conn, &evt_id, ticket_type_index).await
+  }
+
+  /// The current price of a ticket of the ticket type, with the code applied if one is given. Nothing is redeemed;
+  /// use `allocate_seats_with_promo_code` to buy with a code.
+  pub async fn quote_ticket_price(
+    &mut self,
+    evt_id: String,
+    ticket_type_index: i16,
+    code: Option<String>,
+  ) -> Result<PriceQuote> {
+    let now = Utc::now().naive_utc();
+    let conn = self.borrow_mut();
+    let ticket_type = read_ticket_type(conn, &evt_id, ticket_type_index).await?;
+    let promo_code = check_ticket_type_access(conn, &evt_id, ticket_type_index, code.as_deref(), now).await?;
+
+    Ok(quote(&ticket_type, promo_code, now))
+  }
+
+  /// Like `allocate_seats` but redeems the code once per seat in the same transaction. Hidden ticket types can only
+  /// be bought this way, with one of their access codes. Fails with `Error::PromoCodeExhausted` if there are fewer
+  /// redemptions left than seats; concurrent buyers can never go over the limit.
+  pub async fn allocate_seats_with_promo_code(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+    count: i32,
+    code: String,
+  ) -> Result<(Vec<CNT>, PriceQuote)> {
+    if count <= 0 {
+      return Err(Error::InvalidSeatCount.into())
+    }
+
+    let now = Utc::now().naive_utc();
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let promo_code = redeem_code(conn, &event_id, &code, ticket_type_index, count, now).await?;
+      let new_cnts = allocate(conn, &event_id, ticket_type_index, &account_id, count, now, Some(&promo_code)).await?;
+
+      let ticket_type = read_ticket_type(conn, &event_id, ticket_type_index).await?;
+      check_unlocked(conn, &event_id, ticket_type_index, Some(&promo_code)).await?;
+
+      Ok((new_cnts, quote(&ticket_type, Some(promo_code), now)))
+    }))
+    .await
+  }
+}
+
+/// Fails with `Error::TicketTypeLocked` if the ticket type is hidden behind an access code
+pub(crate) async fn check_public_ticket_type(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+) -> Result<()> {
+  if is_hidden(conn, event_id, ticket_type_index).await? {
+    return Err(Error::TicketTypeLocked.into())
+  }
+
+  Ok(())
+}
+
+async fn is_hidden(conn: &mut AsyncPgConnection, event_id: &str, ticket_type_index: i16) -> Result<bool> {
+  let access_codes = promo_codes
+  .filter(promo_codes_dsl::event_id.eq(event_id))
+  .filter(promo_codes_dsl::ticket_type_index.eq(ticket_type_index))
+  .filter(promo_codes_dsl::kind.eq(PromoCodeKind::Access))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  Ok(access_codes > 0)
+}
+
+/// Checks that `code`, if given, can be used for the ticket type at `now` and that the ticket type is either public
+/// or unlocked by it, without redeeming it. Returns the code so the caller can price the tickets.
+pub(crate) async fn check_ticket_type_access(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  code: Option<&str>,
+  now: NaiveDateTime,
+) -> Result<Option<PromoCode>> {
+  let promo_code = match code {
+    Some(code) => Some(read_usable_code(conn, event_id, code, Some(ticket_type_index), now).await?),
+    None => None,
+  };
+
+  check_unlocked(conn, event_id, ticket_type_index, promo_code.as_ref()).await?;
+
+  Ok(promo_code)
+}
+
+/// Like `check_ticket_type_access` but redeems the code once per ticket. Must run in a transaction.
+pub(crate) async fn redeem_ticket_type_access(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  code: Option<&str>,
+  count: i32,
+  now: NaiveDateTime,
+) -> Result<Option<PromoCode>> {
+  let promo_code = match code {
+    Some(code) => Some(redeem_code(conn, event_id, code, ticket_type_index, count, now).await?),
+    None => None,
+  };
+
+  check_unlocked(conn, event_id, ticket_type_index, promo_code.as_ref()).await?;
+
+  Ok(promo_code)
+}
+
+/// Hidden ticket types need one of their access codes; discount codes do not unlock them
+async fn check_unlocked(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  promo_code: Option<&PromoCode>,
+) -> Result<()> {
+  let is_access_code = promo_code.map_or(false, |promo_code| promo_code.kind == PromoCodeKind::Access);
+
+  if !is_access_code {
+    check_public_ticket_type(conn, event_id, ticket_type_index).await?;
+  }
+
+  Ok(())
+}
+
+async fn read_ticket_type(conn: &mut AsyncPgConnection, event_id: &str, ticket_type_index: i16) -> Result<TicketType> {
+  Ok(
+    ticket_types
+    .filter(ticket_types_dsl::event_id.eq(event_id))
+    .filter(ticket_types_dsl::ticket_type_index.eq(ticket_type_index))
+    .first::<TicketType>(conn)
+    .await
+    .optional()?
+    .ok_or(Error::TicketTypeNotFound)?
+  )
+}
+
+async fn read_code(conn: &mut AsyncPgConnection, event_id: &str, code: &str) -> Result<Option<PromoCode>> {
+  Ok(
+    promo_codes
+    .filter(promo_codes_dsl::event_id.eq(event_id))
+    .filter(promo_codes_dsl::code.eq(normalize_code(code)))
+    .first::<PromoCode>(conn)
+    .await
+    .optional()?
+  )
+}
+
+/// Reads a code that is valid at `now` for the ticket type, if one is given, and has redemptions left
+async fn read_usable_code(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  code: &str,
+  ticket_type_index: Option<i16>,
+  now: NaiveDateTime,
+) -> Result<PromoCode> {
+  let promo_code = read_code(conn, event_id, code).await?.ok_or(Error::PromoCodeNotFound)?;
+  let ticket_type_index = ticket_type_index.or(promo_code.ticket_type_index).unwrap_or_default();
+
+  if !promo_code.is_applicable(ticket_type_index, now) {
+    return Err(Error::PromoCodeNotFound.into())
+  }
+
+  if promo_code.max_redemptions.map_or(false, |max_redemptions| promo_code.redemptions >= max_redemptions) {
+    return Err(Error::PromoCodeExhausted.into())
+  }
+
+  Ok(promo_code)
+}
+
+/// Adds `count` redemptions to the code if it is usable for the ticket type and the limit allows it. The check and
+/// the increment are a single UPDATE, so concurrent redemptions wait on the row and re-check the limit.
+async fn redeem_code(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  code: &str,
+  ticket_type_index: i16,
+  count: i32,
+  now: NaiveDateTime,
+) -> Result<PromoCode> {
+  let redeemed = diesel::update(promo_codes)
+  .filter(promo_codes_dsl::event_id.eq(event_id))
+  .filter(promo_codes_dsl::code.eq(normalize_code(code)))
+  .filter(promo_codes_dsl::ticket_type_index.is_null().or(promo_codes_dsl::ticket_type_index.eq(ticket_type_index)))
+  .filter(promo_codes_dsl::valid_from.is_null().or(promo_codes_dsl::valid_from.le(now)))
+  .filter(promo_codes_dsl::valid_until.is_null().or(promo_codes_dsl::valid_until.gt(now)))
+  .filter(
+    promo_codes_dsl::max_redemptions.is_null()
+    .or(promo_codes_dsl::max_redemptions.ge((promo_codes_dsl::redemptions + count).nullable()))
+  )
+  .set(promo_codes_dsl::redemptions.eq(promo_codes_dsl::redemptions + count))
+  .get_result::<PromoCode>(conn)
+  .await
+  .optional()?;
+
+  match redeemed {
+    Some(promo_code) => Ok(promo_code),
+    None => {
+      // Find out why for a better error
+      read_usable_code(conn, event_id, code, Some(ticket_type_index), now).await?;
+      Err(Error::PromoCodeExhausted.into())
+    },
+  }
+}
+
+fn quote(ticket_type: &TicketType, promo_code: Option<PromoCode>, now: NaiveDateTime) -> PriceQuote {
+  let base_price = ticket_type.sale_type.price_at(ticket_type.sale_start_ts, now);
+
+  PriceQuote {
+    event_id: ticket_type.event_id.clone(),
+    ticket_type_index: ticket_type.ticket_type_index,
+    base_price,
+    price: promo_code.as_ref().map_or(base_price, |promo_code| promo_code.apply(base_price)),
+    promo_code,
+  }
+}
@@ -0,0 +1,183 @@
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  models::purchase_limit::{NewPurchaseLimit, PurchaseLimit},
+  schema::{
+    purchase_limits::dsl::{
+      self as purchase_limits_dsl,
+      purchase_limits,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+    accounts::dsl::{
+      self as accounts_dsl,
+      accounts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Sets the event wide limit, or the ticket type's limit if `ticket_type_index` is given. A `max_tickets` of None
+  /// removes the limit.
+  pub async fn set_purchase_limit(
+    &mut self,
+    event_id: String,
+    ticket_type_index: Option<i16>,
+    max_tickets: Option<i32>,
+  ) -> Result<Option<PurchaseLimit>> {
+    if max_tickets.map_or(false, |max_tickets| max_tickets < 0) {
+      return Err(Error::InvalidPurchaseLimit("max_tickets must not be negative".to_string()).into())
+    }
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let mut query = diesel::delete(purchase_limits)
+      .filter(purchase_limits_dsl::event_id.eq(&event_id))
+      .into_boxed();
+
+      query = match ticket_type_index {
+        Some(ticket_type_index) => query.filter(purchase_limits_dsl::ticket_type_index.eq(ticket_type_index)),
+        None => query.filter(purchase_limits_dsl::ticket_type_index.is_null()),
+      };
+
+      query.execute(conn).await?;
+
+      let Some(max_tickets) = max_tickets else {return Ok(None)};
+
+      Ok(Some(
+        diesel::insert_into(purchase_limits)
+        .values(&NewPurchaseLimit {event_id, ticket_type_index, max_tickets})
+        .get_result::<PurchaseLimit>(conn)
+        .await?
+      ))
+    }))
+    .await
+  }
+
+  pub async fn read_purchase_limits(&mut self, event_id: String) -> Result<Vec<PurchaseLimit>> {
+    Ok(
+      purchase_limits
+      .filter(purchase_limits_dsl::event_id.eq(event_id))
+      .order_by(purchase_limits_dsl::ticket_type_index.asc().nulls_first())
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// How many more tickets of the ticket type the account can get, or None if there is no limit
+  pub async fn read_purchase_allowance(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+  ) -> Result<Option<i64>> {
+    let conn = self.borrow_mut();
+    let limits = read_limits(conn, &event_id, ticket_type_index).await?;
+    let mut allowance = None;
+
+    for limit in limits {
+      let owned = owned_tickets(conn, &event_id, &account_id, limit.ticket_type_index, &[]).await?;
+      let left = (limit.max_tickets as i64 - owned).max(0);
+      allowance = Some(allowance.map_or(left, |allowance: i64| allowance.min(left)));
+    }
+
+    Ok(allowance)
+  }
+}
+
+/// Fails with `Error::EventPurchaseLimitExceeded` or `Error::TicketTypePurchaseLimitExceeded` if the account would
+/// go over a limit by getting `count` more tickets of the ticket type. Active holds of the account on
+/// `replaced_holds` are not counted since the caller is replacing them.
+///
+/// The account row is locked until the end of the transaction so concurrent purchases of the same account, even of
+/// different ticket types, are counted one after the other.
+pub(crate) async fn check_purchase_limits(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  account_id: &str,
+  ticket_type_index: i16,
+  count: i64,
+  replaced_holds: &[i32],
+) -> Result<()> {
+  let limits = read_limits(conn, event_id, ticket_type_index).await?;
+
+  if limits.is_empty() {
+    return Ok(())
+  }
+
+  accounts
+  .filter(accounts_dsl::uid.eq(account_id))
+  .select(accounts_dsl::uid)
+  .for_no_key_update()
+  .first::<String>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::AccountNotFound)?;
+
+  for limit in limits {
+    let owned = owned_tickets(conn, event_id, account_id, limit.ticket_type_index, replaced_holds).await?;
+
+    if owned + count > limit.max_tickets as i64 {
+      return Err(match limit.ticket_type_index {
+        Some(_) => Error::TicketTypePurchaseLimitExceeded(limit.max_tickets),
+        None => Error::EventPurchaseLimitExceeded(limit.max_tickets),
+      }.into())
+    }
+  }
+
+  Ok(())
+}
+
+/// The event wide limit and the limit of the ticket type, if they exist
+async fn read_limits(conn: &mut AsyncPgConnection, event_id: &str, ticket_type_index: i16) -> Result<Vec<PurchaseLimit>> {
+  Ok(
+    purchase_limits
+    .filter(purchase_limits_dsl::event_id.eq(event_id))
+    .filter(
+      purchase_limits_dsl::ticket_type_index.is_null()
+      .or(purchase_limits_dsl::ticket_type_index.eq(ticket_type_index))
+    )
+    .load::<PurchaseLimit>(conn)
+    .await?
+  )
+}
+
+/// CNTs plus active seat holds of the account, for the whole event or a single ticket type
+async fn owned_tickets(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  account_id: &str,
+  ticket_type_index: Option<i16>,
+  replaced_holds: &[i32],
+) -> Result<i64> {
+  let mut cnts_query = cnts
+  .filter(cnts_dsl::event_id.eq(event_id))
+  .filter(cnts_dsl::account_id.eq(account_id))
+  .into_boxed();
+
+  let mut holds_query = seat_holds
+  .filter(seat_holds_dsl::event_id.eq(event_id))
+  .filter(seat_holds_dsl::account_id.eq(account_id))
+  .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+  .filter(seat_holds_dsl::seat_index.ne_all(replaced_holds.to_vec()))
+  .into_boxed();
+
+  if let Some(ticket_type_index) = ticket_type_index {
+    cnts_query = cnts_query.filter(cnts_dsl::ticket_type_index.eq(ticket_type_index));
+    holds_query = holds_query.filter(seat_holds_dsl::ticket_type_index.eq(ticket_type_index));
+  }
+
+  let owned = cnts_query.count().get_result::<i64>(conn).await?;
+  let held = holds_query.count().get_result::<i64>(conn).await?;
+
+  Ok(owned + held)
+}
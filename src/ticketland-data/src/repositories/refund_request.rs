@@ -0,0 +1,323 @@
+use chrono::Utc;
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::{
+    cnt_history::record_ownership_events,
+    event_status::check_event_status,
+  },
+  models::{
+    cnt::CNT,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    cnt_transfer::TransferStatus,
+    event::EventStatus,
+    refund_request::{NewRefundRequest, RefundReason, RefundRequest, RefundStatus},
+    ticket_type::SaleType,
+  },
+  schema::{
+    refund_requests::dsl::{
+      self as refund_requests_dsl,
+      refund_requests,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    events::dsl::{
+      self as events_dsl,
+      events,
+    },
+    ticket_types::dsl::{
+      self as ticket_types_dsl,
+      ticket_types,
+    },
+    listings::dsl::{
+      self as listings_dsl,
+      listings,
+    },
+    trades::dsl::{
+      self as trades_dsl,
+      trades,
+    },
+    cnt_transfers::dsl::{
+      self as cnt_transfers_dsl,
+      cnt_transfers,
+    },
+    ticket_type_nfts::dsl::{
+      self as ticket_type_nfts_dsl,
+      ticket_type_nfts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Asks for a refund of a CNT of a `SaleType::Refundable` ticket type. The CNT must belong to the account,
+  /// must not be attended, listed, part of a pending transfer or bought on the secondary market, and the event
+  /// must not have started yet.
+  pub async fn request_refund(&mut self, cnt_address: String, uid: String) -> Result<RefundRequest> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let cnt = cnts
+      .filter(cnts_dsl::cnt_sui_address.eq(&cnt_address))
+      .filter(cnts_dsl::account_id.eq(&uid))
+      .for_update()
+      .first::<CNT>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::CntNotFound)?;
+
+      check_refund_eligibility(conn, &cnt, &cnt_address).await?;
+
+      if has_open_refund(conn, &cnt.event_id, cnt.seat_index).await? {
+        return Err(Error::RefundAlreadyRequested.into())
+      }
+
+      let refund = NewRefundRequest {
+        event_id: cnt.event_id,
+        seat_index: cnt.seat_index,
+        cnt_sui_address: cnt.cnt_sui_address,
+        account_id: uid,
+        reason: RefundReason::Requested,
+        status: RefundStatus::Pending,
+      };
+
+      Ok(
+        diesel::insert_into(refund_requests)
+        .values(&refund)
+        .get_result::<RefundRequest>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  pub async fn approve_refund(&mut self, refund_id: i64) -> Result<RefundRequest> {
+    resolve_pending_refund(self.borrow_mut(), refund_id, RefundStatus::Approved).await
+  }
+
+  pub async fn reject_refund(&mut self, refund_id: i64) -> Result<RefundRequest> {
+    resolve_pending_refund(self.borrow_mut(), refund_id, RefundStatus::Rejected).await
+  }
+
+  /// Completes an approved refund once the money has been paid back. The CNT, its closed listings and its ticket
+  /// type NFTs are deleted so the seat is back in the inventory, and a `Refunded` entry is added to the history.
+  /// Fails with `Error::CntResold` or `Error::CntAttended` if the CNT changed hands or was used in the meantime.
+  pub async fn complete_refund(&mut self, refund_id: i64) -> Result<RefundRequest> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let refund = refund_requests
+      .filter(refund_requests_dsl::refund_id.eq(refund_id))
+      .filter(refund_requests_dsl::status.eq(RefundStatus::Approved))
+      .for_update()
+      .first::<RefundRequest>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::RefundNotFound)?;
+
+      // The CNT may already be gone e.g. if it was burned on chain
+      let cnt = cnts
+      .filter(cnts_dsl::event_id.eq(&refund.event_id))
+      .filter(cnts_dsl::seat_index.eq(refund.seat_index))
+      .for_update()
+      .first::<CNT>(conn)
+      .await
+      .optional()?;
+
+      if let Some(cnt) = cnt {
+        // Paying back someone who no longer owns the CNT would refund the seat twice
+        if cnt.account_id != refund.account_id {
+          return Err(Error::CntResold.into())
+        }
+
+        // Check-ins are rejected while the refund is open, but the CNT may have been marked as attended on chain
+        if cnt.attended {
+          return Err(Error::CntAttended.into())
+        }
+
+        release_cnt(conn, cnt).await?;
+      }
+
+      Ok(
+        diesel::update(refund_requests)
+        .filter(refund_requests_dsl::refund_id.eq(refund_id))
+        .set((
+          refund_requests_dsl::status.eq(RefundStatus::Completed),
+          refund_requests_dsl::resolved_at.eq(dsl::now),
+        ))
+        .get_result::<RefundRequest>(conn)
+        .await?
+      )
+    }))
+    .await
+  }
+
+  /// Refund requests of the event waiting for the organizer, oldest first
+  pub async fn read_pending_refunds(&mut self, evt_id: String, skip: i64, limit: i64) -> Result<Vec<RefundRequest>> {
+    Ok(
+      refund_requests
+      .filter(refund_requests_dsl::event_id.eq(evt_id))
+      .filter(refund_requests_dsl::status.eq(RefundStatus::Pending))
+      .order_by((refund_requests_dsl::created_at.asc(), refund_requests_dsl::refund_id.asc()))
+      .limit(limit)
+      .offset(skip * limit)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  pub async fn read_account_refunds(&mut self, uid: String) -> Result<Vec<RefundRequest>> {
+    Ok(
+      refund_requests
+      .filter(refund_requests_dsl::account_id.eq(uid))
+      .order_by((refund_requests_dsl::created_at.desc(), refund_requests_dsl::refund_id.desc()))
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+}
+
+pub(crate) async fn has_open_refund(conn: &mut AsyncPgConnection, evt_id: &str, seat_index: i32) -> Result<bool> {
+  let open_refunds = refund_requests
+  .filter(refund_requests_dsl::event_id.eq(evt_id))
+  .filter(refund_requests_dsl::seat_index.eq(seat_index))
+  .filter(refund_requests_dsl::status.eq_any([RefundStatus::Pending, RefundStatus::Approved]))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  Ok(open_refunds > 0)
+}
+
+/// Fails with `Error::CntRefundPending` if the CNT has a pending or approved refund. Such CNTs cannot be listed,
+/// sold or transferred since the refund would then go to someone who no longer owns them.
+pub(crate) async fn check_no_open_refund(conn: &mut AsyncPgConnection, cnt: &CNT) -> Result<()> {
+  if has_open_refund(conn, &cnt.event_id, cnt.seat_index).await? {
+    return Err(Error::CntRefundPending.into())
+  }
+
+  Ok(())
+}
+
+async fn check_refund_eligibility(conn: &mut AsyncPgConnection, cnt: &CNT, cnt_address: &str) -> Result<()> {
+  if cnt.attended {
+    return Err(Error::CntAttended.into())
+  }
+
+  check_event_status(conn, &cnt.event_id, &EventStatus::TRADING).await?;
+
+  let started = events
+  .filter(events_dsl::event_id.eq(&cnt.event_id))
+  .select(events_dsl::start_date.le(dsl::now))
+  .first::<bool>(conn)
+  .await?;
+
+  if started {
+    return Err(Error::RefundWindowClosed.into())
+  }
+
+  let sale_type = ticket_types
+  .filter(ticket_types_dsl::event_id.eq(&cnt.event_id))
+  .filter(ticket_types_dsl::ticket_type_index.eq(cnt.ticket_type_index))
+  .select(ticket_types_dsl::sale_type)
+  .first::<SaleType>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::TicketTypeNotFound)?;
+
+  if !matches!(sale_type, SaleType::Refundable {..}) {
+    return Err(Error::NotRefundable.into())
+  }
+
+  let resales = trades
+  .filter(trades_dsl::cnt_sui_address.eq(cnt_address))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  if resales > 0 {
+    return Err(Error::CntResold.into())
+  }
+
+  let open_listings = listings
+  .filter(listings_dsl::cnt_sui_address.eq(cnt_address))
+  .filter(listings_dsl::is_open.eq(true))
+  .filter(listings_dsl::expires_at.is_null().or(listings_dsl::expires_at.gt(dsl::now)))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  if open_listings > 0 {
+    return Err(Error::CntListed.into())
+  }
+
+  let pending_transfers = cnt_transfers
+  .filter(cnt_transfers_dsl::cnt_sui_address.eq(cnt_address))
+  .filter(cnt_transfers_dsl::status.eq(TransferStatus::Pending))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  if pending_transfers > 0 {
+    return Err(Error::TransferAlreadyPending.into())
+  }
+
+  Ok(())
+}
+
+async fn resolve_pending_refund(
+  conn: &mut AsyncPgConnection,
+  refund_id: i64,
+  status: RefundStatus,
+) -> Result<RefundRequest> {
+  let resolved_at = (status == RefundStatus::Rejected).then(|| Utc::now().naive_utc());
+
+  diesel::update(refund_requests)
+  .filter(refund_requests_dsl::refund_id.eq(refund_id))
+  .filter(refund_requests_dsl::status.eq(RefundStatus::Pending))
+  .set((
+    refund_requests_dsl::status.eq(status),
+    refund_requests_dsl::resolved_at.eq(resolved_at),
+  ))
+  .get_result::<RefundRequest>(conn)
+  .await
+  .optional()?
+  .ok_or_else(|| Error::RefundNotFound.into())
+}
+
+/// Deletes the CNT and everything that points to it, handing it back to the organizer in the history
+async fn release_cnt(conn: &mut AsyncPgConnection, cnt: CNT) -> Result<()> {
+  let organizer = events
+  .filter(events_dsl::event_id.eq(&cnt.event_id))
+  .select(events_dsl::account_id)
+  .first::<String>(conn)
+  .await?;
+
+  if let Some(cnt_address) = &cnt.cnt_sui_address {
+    diesel::delete(listings)
+    .filter(listings_dsl::cnt_sui_address.eq(cnt_address))
+    .execute(conn)
+    .await?;
+
+    diesel::delete(ticket_type_nfts)
+    .filter(ticket_type_nfts_dsl::cnt_sui_address.eq(cnt_address))
+    .execute(conn)
+    .await?;
+  }
+
+  diesel::delete(cnts)
+  .filter(cnts_dsl::event_id.eq(&cnt.event_id))
+  .filter(cnts_dsl::seat_index.eq(cnt.seat_index))
+  .execute(conn)
+  .await?;
+
+  let owner = cnt.account_id.clone();
+  let refunded = CNT {account_id: organizer, ..cnt};
+
+  record_ownership_events(conn, vec![
+    NewCntOwnershipEvent::new(OwnershipEventKind::Refunded, &refunded, Some(owner)),
+  ])
+  .await
+}
@@ -0,0 +1,93 @@
+use eyre::Result;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  models::{
+    resale_policy::ResalePolicy,
+    ticket_type::SaleType,
+  },
+  schema::{
+    resale_policies::dsl::{
+      self as resale_policies_dsl,
+      resale_policies,
+    },
+    ticket_types::dsl::{
+      self as ticket_types_dsl,
+      ticket_types,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  pub async fn upsert_resale_policy(&mut self, policy: ResalePolicy) -> Result<()> {
+    policy.validate()?;
+
+    diesel::insert_into(resale_policies)
+    .values(&policy)
+    .on_conflict(resale_policies_dsl::event_id)
+    .do_update()
+    .set(&policy)
+    .execute(self.borrow_mut())
+    .await?;
+
+    Ok(())
+  }
+
+  pub async fn read_resale_policy(&mut self, evt_id: String) -> Result<Option<ResalePolicy>> {
+    read_policy(self.borrow_mut(), &evt_id).await
+  }
+
+  pub async fn delete_resale_policy(&mut self, evt_id: String) -> Result<()> {
+    diesel::delete(resale_policies)
+    .filter(resale_policies_dsl::event_id.eq(evt_id))
+    .execute(self.borrow_mut())
+    .await?;
+
+    Ok(())
+  }
+}
+
+async fn read_policy(conn: &mut AsyncPgConnection, evt_id: &str) -> Result<Option<ResalePolicy>> {
+  Ok(
+    resale_policies
+    .filter(resale_policies_dsl::event_id.eq(evt_id))
+    .first::<ResalePolicy>(conn)
+    .await
+    .optional()?
+  )
+}
+
+/// Fails with `Error::ResalePriceAboveCap` if the price is above the cap of the event's resale policy
+/// for the given ticket type.
+pub(crate) async fn check_resale_price(
+  conn: &mut AsyncPgConnection,
+  evt_id: &str,
+  ticket_type_index: i16,
+  price: i64,
+) -> Result<()> {
+  let Some(policy) = read_policy(conn, evt_id).await? else {return Ok(())};
+
+  let sale_type = ticket_types
+  .filter(ticket_types_dsl::event_id.eq(evt_id))
+  .filter(ticket_types_dsl::ticket_type_index.eq(ticket_type_index))
+  .select(ticket_types_dsl::sale_type)
+  .first::<SaleType>(conn)
+  .await
+  .optional()?
+  .ok_or(Error::TicketTypeNotFound)?;
+
+  match policy.max_price(sale_type.face_value()) {
+    Some(max_price) if price > max_price => Err(Error::ResalePriceAboveCap(max_price).into()),
+    _ => Ok(()),
+  }
+}
+
+/// The organiser royalty and the seller proceeds of a secondary sale of the event
+pub(crate) async fn resale_proceeds(conn: &mut AsyncPgConnection, evt_id: &str, price: i64) -> Result<(i64, i64)> {
+  Ok(
+    read_policy(conn, evt_id).await?
+    .map_or((0, price), |policy| policy.split(price))
+  )
+}
@@ -1,71 +1,200 @@
-use diesel::sql_query;
+use diesel::sql_types::{BigInt, Text};
 use eyre::Result;
 use diesel_async::{RunQueryDsl};
-use crate::models::sales::{ClosedSalesData, AverageSalesPrice};
+use crate::models::sales::{
+  ClosedSalesData,
+  AverageSalesPrice,
+  PrimarySalesData,
+  SaleTypeRevenue,
+  SellThrough,
+  ResaleVolume,
+};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  query::QueryBuilder,
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Number of trades per `interval` seconds since `start_ts`
   pub async fn read_closed_sales_count(
     &mut self,
     event_id: String,
     interval: u64,
     start_ts: u64
   ) -> Result<Vec<ClosedSalesData>> {
-    let query = sql_query(format!(
+    let (interval, start_ts) = (interval as i64, start_ts as i64);
+
+    let query = QueryBuilder::new("SELECT date_bin(make_interval(secs => ")
+    .bind::<BigInt, _>(interval)
+    .sql("), created_at, TO_TIMESTAMP(")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      ")::date) as timestamp, COUNT(*)
+      FROM trades
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(" AND EXTRACT(epoch from created_at) > ")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
       "
-      SELECT date_bin(
-        INTERVAL '{1} seconds',
-        closed_at,
-        TO_TIMESTAMP({2})::date
-      ) as timestamp, COUNT(t)
-      FROM
-      (
-        SELECT closed_at, is_open
-        FROM offers
-        WHERE event_id = '{0}' AND is_open = false AND EXTRACT(epoch from closed_at) > {2}
-        UNION ALL
-        SELECT closed_at, is_open
-        FROM listings
-        WHERE event_id = '{0}' AND is_open = false AND EXTRACT(epoch from closed_at) > {2}
-      ) t
       GROUP BY 1
       ORDER BY timestamp desc;
-      ", event_id, interval, start_ts
-    ));
+      "
+    )
+    .build();
 
     Ok(query.load::<ClosedSalesData>(self.borrow_mut()).await?)
   }
 
+  /// Average trade price per `interval` seconds since `start_ts`
   pub async fn read_average_sales_price(
     &mut self,
     event_id: String,
     interval: u64,
     start_ts: u64
   ) -> Result<Vec<AverageSalesPrice>> {
-    let query = sql_query(format!(
+    let (interval, start_ts) = (interval as i64, start_ts as i64);
+
+    let query = QueryBuilder::new("SELECT date_bin(make_interval(secs => ")
+    .bind::<BigInt, _>(interval)
+    .sql("), created_at, TO_TIMESTAMP(")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      ")::date) as timestamp, AVG(price), COUNT(*)
+      FROM trades
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(" AND EXTRACT(epoch from created_at) > ")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
       "
-      SELECT date_bin(
-        INTERVAL '{1} seconds',
-        closed_at,
-        TO_TIMESTAMP({2})::date
-      ) as timestamp, AVG(price), COUNT(t)
-      FROM
-      (
-        SELECT bid_price as price, closed_at
-        FROM offers
-        WHERE event_id = '{0}' AND is_open = false AND EXTRACT(epoch from closed_at) > {2}
-        UNION ALL
-        SELECT ask_price as price, closed_at
-        FROM listings
-        WHERE event_id = '{0}' AND is_open = false AND EXTRACT(epoch from closed_at) > {2}
-      ) t
       GROUP BY 1
       ORDER BY timestamp desc;
-      ", event_id, interval, start_ts
-    ));
+      "
+    )
+    .build();
 
     Ok(query.load::<AverageSalesPrice>(self.borrow_mut()).await?)
   }
+
+  /// Number of primary sales per ticket type per `interval` seconds since `start_ts`. Allocated CNTs count as sold
+  /// even before they are minted; refunded ones do not.
+  pub async fn read_primary_sales_count(
+    &mut self,
+    event_id: String,
+    interval: u64,
+    start_ts: u64
+  ) -> Result<Vec<PrimarySalesData>> {
+    let (interval, start_ts) = (interval as i64, start_ts as i64);
+
+    let query = QueryBuilder::new("SELECT ticket_type_index, date_bin(make_interval(secs => ")
+    .bind::<BigInt, _>(interval)
+    .sql("), created_at, TO_TIMESTAMP(")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      ")::date) as timestamp, COUNT(*)
+      FROM cnts
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(" AND EXTRACT(epoch from created_at) > ")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      "
+      GROUP BY 1, 2
+      ORDER BY timestamp desc, ticket_type_index;
+      "
+    )
+    .build();
+
+    Ok(query.load::<PrimarySalesData>(self.borrow_mut()).await?)
+  }
+
+  /// Gross primary sale revenue of the event per `SaleType`, from the price paid for each CNT
+  pub async fn read_revenue_per_sale_type(&mut self, event_id: String) -> Result<Vec<SaleTypeRevenue>> {
+    let query = QueryBuilder::new(
+      "
+      SELECT CASE
+        WHEN ticket_types.sale_type ? 'Free' THEN 'Free'
+        WHEN ticket_types.sale_type ? 'FixedPrice' THEN 'FixedPrice'
+        WHEN ticket_types.sale_type ? 'Refundable' THEN 'Refundable'
+        WHEN ticket_types.sale_type ? 'DutchAuction' THEN 'DutchAuction'
+      END AS sale_type,
+      COUNT(*) AS tickets_sold,
+      COUNT(*) FILTER (WHERE cnts.purchase_price IS NULL) AS unpriced_tickets,
+      COALESCE(SUM(cnts.purchase_price), 0)::BIGINT AS gross_revenue
+      FROM cnts
+      INNER JOIN ticket_types
+      ON ticket_types.event_id = cnts.event_id AND ticket_types.ticket_type_index = cnts.ticket_type_index
+      WHERE cnts.event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(
+      "
+      GROUP BY 1
+      ORDER BY gross_revenue DESC, sale_type;
+      "
+    )
+    .build();
+
+    Ok(query.load::<SaleTypeRevenue>(self.borrow_mut()).await?)
+  }
+
+  /// Sold tickets against `n_tickets` for every ticket type of the event, including the ones with no sales
+  pub async fn read_sell_through(&mut self, event_id: String) -> Result<Vec<SellThrough>> {
+    let query = QueryBuilder::new(
+      "
+      SELECT ticket_types.ticket_type_index, ticket_types.ticket_type_name, ticket_types.n_tickets,
+      COUNT(cnts.seat_index) AS sold,
+      COALESCE(100.0 * COUNT(cnts.seat_index) / NULLIF(ticket_types.n_tickets, 0), 0)::FLOAT8 AS sell_through
+      FROM ticket_types
+      LEFT JOIN cnts
+      ON cnts.event_id = ticket_types.event_id AND cnts.ticket_type_index = ticket_types.ticket_type_index
+      WHERE ticket_types.event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(
+      "
+      GROUP BY ticket_types.ticket_type_index, ticket_types.ticket_type_name, ticket_types.n_tickets
+      ORDER BY ticket_types.ticket_type_index;
+      "
+    )
+    .build();
+
+    Ok(query.load::<SellThrough>(self.borrow_mut()).await?)
+  }
+
+  /// Trade volume and royalties per `interval` seconds since `start_ts`
+  pub async fn read_resale_volume(
+    &mut self,
+    event_id: String,
+    interval: u64,
+    start_ts: u64
+  ) -> Result<Vec<ResaleVolume>> {
+    let (interval, start_ts) = (interval as i64, start_ts as i64);
+
+    let query = QueryBuilder::new("SELECT date_bin(make_interval(secs => ")
+    .bind::<BigInt, _>(interval)
+    .sql("), created_at, TO_TIMESTAMP(")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      ")::date) as timestamp, COUNT(*), SUM(price)::BIGINT AS volume, SUM(royalty)::BIGINT AS royalties
+      FROM trades
+      WHERE event_id = "
+    )
+    .bind::<Text, _>(event_id)
+    .sql(" AND EXTRACT(epoch from created_at) > ")
+    .bind::<BigInt, _>(start_ts)
+    .sql(
+      "
+      GROUP BY 1
+      ORDER BY timestamp desc;
+      "
+    )
+    .build();
+
+    Ok(query.load::<ResaleVolume>(self.borrow_mut()).await?)
+  }
 }
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+  dsl,
+  prelude::*,
+  sql_types::{Array, BigInt, Int4, SmallInt, Text},
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  query::QueryBuilder,
+  repositories::{
+    inventory::{lock_ticket_type_for_sale, new_draft_cnt},
+    cnt_history::record_ownership_events,
+    event_status::check_event_status,
+    promo_code::{check_ticket_type_access, redeem_ticket_type_access},
+    purchase_limit::check_purchase_limits,
+  },
+  models::{
+    cnt::CNT,
+    event::EventStatus,
+    cnt_ownership_event::{NewCntOwnershipEvent, OwnershipEventKind},
+    seat_hold::SeatHold,
+  },
+  schema::{
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+    cnts::dsl::cnts,
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Holds the given seats of a ticket type for the account for `ttl_secs`. Either all the seats are held or none.
+  /// The event must be on sale.
+  /// A seat can be held if it belongs to one of the ticket type's seat ranges, it has not been sold and it is not held
+  /// by another account. Holding a seat the account already holds extends the hold.
+  /// Hidden ticket types can only be held with one of their access codes. The code is checked but not redeemed;
+  /// that happens when the holds are converted with the same code.
+  pub async fn hold_seats(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+    mut seat_indexes: Vec<i32>,
+    ttl_secs: i64,
+    code: Option<String>,
+  ) -> Result<Vec<SeatHold>> {
+    seat_indexes.sort_unstable();
+    seat_indexes.dedup();
+
+    if seat_indexes.is_empty() {
+      return Err(Error::InvalidSeatCount.into())
+    }
+
+    let now = Utc::now().naive_utc();
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      check_event_status(conn, &event_id, &[EventStatus::OnSale]).await?;
+      check_ticket_type_access(conn, &event_id, ticket_type_index, code.as_deref(), now).await?;
+
+      insert_seat_holds(conn, event_id, ticket_type_index, account_id, seat_indexes, ttl_secs).await
+    }))
+    .await
+  }
+
+  pub async fn read_active_seat_holds(&mut self, event_id: String) -> Result<Vec<SeatHold>> {
+    Ok(
+      seat_holds
+      .filter(seat_holds_dsl::event_id.eq(event_id))
+      .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+      .order(seat_holds_dsl::seat_index)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  pub async fn release_seat_holds(&mut self, event_id: String, account_id: String, seat_indexes: Vec<i32>) -> Result<usize> {
+    Ok(
+      diesel::delete(seat_holds)
+      .filter(seat_holds_dsl::event_id.eq(event_id))
+      .filter(seat_holds_dsl::account_id.eq(account_id))
+      .filter(seat_holds_dsl::seat_index.eq_any(seat_indexes))
+      .execute(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// Expired holds are already ignored everywhere; this only reclaims the rows. Returns the number of deleted holds.
+  pub async fn delete_expired_seat_holds(&mut self) -> Result<usize> {
+    Ok(
+      diesel::delete(seat_holds)
+      .filter(seat_holds_dsl::expires_at.le(dsl::now))
+      .execute(self.borrow_mut())
+      .await?
+    )
+  }
+
+  /// Turns the account's active holds on the given seats into draft CNTs. The same sale window and `n_tickets`
+  /// checks as `allocate_seats` apply. Fails with `Error::SeatHoldNotFound` if any of the holds is missing or expired.
+  /// If a code is given it is redeemed once per seat and the CNTs record the discounted price, like
+  /// `allocate_seats_with_promo_code`.
+  pub async fn convert_seat_holds(
+    &mut self,
+    event_id: String,
+    account_id: String,
+    mut seat_indexes: Vec<i32>,
+    code: Option<String>,
+  ) -> Result<Vec<CNT>> {
+    seat_indexes.sort_unstable();
+    seat_indexes.dedup();
+
+    let now = Utc::now().naive_utc();
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      convert_holds(conn, &event_id, &account_id, seat_indexes, code.as_deref(), now).await
+    }))
+    .await
+  }
+}
+
+/// Holds all the given seats or fails with `Error::SeatUnavailable`. The caller checks the event status.
+/// Seats the account already holds are not counted twice against its purchase limits.
+pub(crate) async fn insert_seat_holds(
+  conn: &mut AsyncPgConnection,
+  event_id: String,
+  ticket_type_index: i16,
+  account_id: String,
+  seat_indexes: Vec<i32>,
+  ttl_secs: i64,
+) -> Result<Vec<SeatHold>> {
+  let n_seats = seat_indexes.len();
+  check_purchase_limits(conn, &event_id, &account_id, ticket_type_index, n_seats as i64, &seat_indexes).await?;
+
+  let query = QueryBuilder::new(
+    "
+    INSERT INTO seat_holds (event_id, seat_index, ticket_type_index, account_id, expires_at)
+    SELECT seat_ranges.event_id, seats.seat_index, seat_ranges.ticket_type_index, "
+  )
+  .bind::<Text, _>(account_id)
+  .sql(", now() + make_interval(secs => ")
+  .bind::<BigInt, _>(ttl_secs)
+  .sql(")
+    FROM unnest(")
+  .bind::<Array<Int4>, _>(seat_indexes)
+  .sql("::int[]) AS seats(seat_index)
+    INNER JOIN seat_ranges
+    ON seat_ranges.event_id = ")
+  .bind::<Text, _>(event_id)
+  .sql(" AND seat_ranges.ticket_type_index = ")
+  .bind::<SmallInt, _>(ticket_type_index)
+  .sql(
+    "
+    AND seat_ranges.l <= seats.seat_index AND seats.seat_index < seat_ranges.r
+    WHERE NOT EXISTS (
+      SELECT 1 FROM cnts
+      WHERE cnts.event_id = seat_ranges.event_id AND cnts.seat_index = seats.seat_index
+    )
+    ON CONFLICT (event_id, seat_index) DO UPDATE
+    SET ticket_type_index = EXCLUDED.ticket_type_index,
+        account_id = EXCLUDED.account_id,
+        created_at = now(),
+        expires_at = EXCLUDED.expires_at
+    WHERE seat_holds.account_id = EXCLUDED.account_id OR seat_holds.expires_at <= now()
+    RETURNING *
+    "
+  )
+  .build();
+
+  let holds = query.load::<SeatHold>(conn).await?;
+
+  // Some seats are sold, held by someone else or not part of the ticket type
+  if holds.len() != n_seats {
+    return Err(Error::SeatUnavailable.into())
+  }
+
+  Ok(holds)
+}
+
+/// Deletes the account's active holds on the given seats and inserts draft CNTs for them, priced with the code if
+/// one is given. Purchase limits are not checked again since the holds already counted towards them.
+pub(crate) async fn convert_holds(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  account_id: &str,
+  seat_indexes: Vec<i32>,
+  code: Option<&str>,
+  now: NaiveDateTime,
+) -> Result<Vec<CNT>> {
+  let n_seats = seat_indexes.len();
+
+  let holds = diesel::delete(seat_holds)
+  .filter(seat_holds_dsl::event_id.eq(event_id))
+  .filter(seat_holds_dsl::account_id.eq(account_id))
+  .filter(seat_holds_dsl::seat_index.eq_any(seat_indexes))
+  .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+  .get_results::<SeatHold>(conn)
+  .await?;
+
+  if n_seats == 0 || holds.len() != n_seats {
+    return Err(Error::SeatHoldNotFound.into())
+  }
+
+  let mut seats_per_ticket_type = BTreeMap::<i16, Vec<i32>>::new();
+  for hold in holds {
+    seats_per_ticket_type.entry(hold.ticket_type_index).or_default().push(hold.seat_index);
+  }
+
+  let mut new_cnts = vec![];
+  for (ticket_type_index, seats) in seats_per_ticket_type {
+    let ticket_type = lock_ticket_type_for_sale(conn, event_id, ticket_type_index, seats.len() as i64, now).await?;
+    let promo_code = redeem_ticket_type_access(conn, event_id, ticket_type_index, code, seats.len() as i32, now).await?;
+    let price = ticket_type.sale_type.price_at(ticket_type.sale_start_ts, now);
+    let price = promo_code.map_or(price, |promo_code| promo_code.apply(price));
+
+    new_cnts.extend(
+      seats
+      .into_iter()
+      .map(|seat_index| new_draft_cnt(event_id, account_id, ticket_type_index, seat_index, price))
+    );
+  }
+
+  let new_cnts = diesel::insert_into(cnts)
+  .values(&new_cnts)
+  .get_results::<CNT>(conn)
+  .await?;
+
+  let events = new_cnts
+  .iter()
+  .map(|cnt| NewCntOwnershipEvent::new(OwnershipEventKind::Issued, cnt, None))
+  .collect();
+  record_ownership_events(conn, events).await?;
+
+  Ok(new_cnts)
+}
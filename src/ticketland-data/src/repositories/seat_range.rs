@@ -1,12 +1,21 @@
 use eyre::Result;
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
   models::{
-    seat_range::SeatRange,
+    seat_range::{SeatRange, validate_allocated_seats, validate_seat_ranges},
+    ticket_type::NewTicketType,
   },
   schema::{
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
     seat_ranges::dsl::{
       self as seat_ranges_dsl,
       seat_ranges,
@@ -14,7 +23,7 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn read_ticket_type_seat_ranges(
     &mut self,
     event_id: String,
@@ -29,3 +38,63 @@ impl PostgresConnection {
     )
   }
 }
+
+/// Replaces the seat ranges of the given ticket types by `new_seat_ranges`, keeping the ranges of the other
+/// ticket types of their events. See `validate_seat_ranges` and `validate_allocated_seats`.
+pub(crate) async fn replace_seat_ranges(
+  conn: &mut AsyncPgConnection,
+  ticket_types: &[NewTicketType],
+  new_seat_ranges: &[SeatRange],
+) -> Result<()> {
+  let event_ids = ticket_types
+  .iter()
+  .map(|ticket_type| ticket_type.event_id.clone())
+  .collect::<Vec<_>>();
+
+  let existing = seat_ranges
+  .filter(seat_ranges_dsl::event_id.eq_any(&event_ids))
+  .for_update()
+  .load::<SeatRange>(conn)
+  .await?;
+
+  validate_seat_ranges(ticket_types, new_seat_ranges, &existing)?;
+
+  let mut allocated = cnts
+  .filter(cnts_dsl::event_id.eq_any(&event_ids))
+  .select((cnts_dsl::event_id, cnts_dsl::ticket_type_index, cnts_dsl::seat_index))
+  .load::<(String, i16, i32)>(conn)
+  .await?;
+
+  allocated.extend(
+    seat_holds
+    .filter(seat_holds_dsl::event_id.eq_any(&event_ids))
+    .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+    .select((seat_holds_dsl::event_id, seat_holds_dsl::ticket_type_index, seat_holds_dsl::seat_index))
+    .load::<(String, i16, i32)>(conn)
+    .await?
+  );
+
+  validate_allocated_seats(ticket_types, new_seat_ranges, &allocated)?;
+
+  for ticket_type in ticket_types {
+    diesel::delete(seat_ranges)
+    .filter(seat_ranges_dsl::event_id.eq(&ticket_type.event_id))
+    .filter(seat_ranges_dsl::ticket_type_index.eq(ticket_type.ticket_type_index))
+    .execute(conn)
+    .await?;
+  }
+
+  diesel::insert_into(seat_ranges)
+  .values(new_seat_ranges)
+  .on_conflict((
+    seat_ranges_dsl::event_id,
+    seat_ranges_dsl::ticket_type_index,
+    seat_ranges_dsl::l,
+    seat_ranges_dsl::r
+  ))
+  .do_nothing()
+  .execute(conn)
+  .await?;
+
+  Ok(())
+}
@@ -2,7 +2,7 @@ use diesel::prelude::*;
 use eyre::Result;
 use diesel_async::RunQueryDsl;
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
   models::{stripe_account::StripeAccount, stripe_customer::StripeCustomer},
   schema::{
     stripe_accounts::dsl::{
@@ -24,7 +24,7 @@ use crate::{
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_stripe_account(&mut self, account: StripeAccount) -> Result<()> {
     diesel::insert_into(stripe_accounts)
     .values(&account)
@@ -1,9 +1,9 @@
-use diesel::prelude::*;
-use diesel::result::Error;
-use eyre::Result;
-use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel::{prelude::*, upsert::excluded};
+use eyre::{Report, Result};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use crate::{
-  connection::PostgresConnection,
+  connection::{ConnectionHandle, PostgresConnection},
+  repositories::seat_range::replace_seat_ranges,
   models::{
     ticket_type::{NewTicketType, TicketType},
     seat_range::SeatRange,
@@ -13,37 +13,14 @@ use crate::{
       self as ticket_types_dsl,
       ticket_types,
     },
-    seat_ranges::dsl::{
-      self as seat_ranges_dsl,
-      seat_ranges,
-    }
   },
 };
 
-impl PostgresConnection {
+impl<C: ConnectionHandle> PostgresConnection<C> {
   pub async fn upsert_ticket_types(&mut self, ticket_types_list: Vec<NewTicketType>, seat_ranges_list: Vec<SeatRange>) -> Result<()> {
     self.borrow_mut()
-    .transaction::<_, Error, _>(|conn| Box::pin(async move {
-      diesel::insert_into(ticket_types)
-      .values(&ticket_types_list)
-      .on_conflict((ticket_types_dsl::event_id, ticket_types_dsl::ticket_type_index))
-      .do_nothing()
-      .execute(conn)
-      .await?;
-      
-      diesel::insert_into(seat_ranges)
-      .values(&seat_ranges_list)
-      .on_conflict((
-        seat_ranges_dsl::event_id,
-        seat_ranges_dsl::ticket_type_index,
-        seat_ranges_dsl::l,
-        seat_ranges_dsl::r
-      ))
-      .do_nothing()
-      .execute(conn)
-      .await?;
-
-      Ok(())
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      store_ticket_types(conn, &ticket_types_list, &seat_ranges_list).await
     }))
     .await?;
 
@@ -71,3 +48,20 @@ impl PostgresConnection {
     )
   }
 }
+
+/// Stores the ticket types, updating the `n_tickets` of existing ones, and replaces their seat ranges
+pub(crate) async fn store_ticket_types(
+  conn: &mut AsyncPgConnection,
+  ticket_types_list: &[NewTicketType],
+  seat_ranges_list: &[SeatRange],
+) -> Result<()> {
+  diesel::insert_into(ticket_types)
+  .values(ticket_types_list)
+  .on_conflict((ticket_types_dsl::event_id, ticket_types_dsl::ticket_type_index))
+  .do_update()
+  .set(ticket_types_dsl::n_tickets.eq(excluded(ticket_types_dsl::n_tickets)))
+  .execute(conn)
+  .await?;
+
+  replace_seat_ranges(conn, ticket_types_list, seat_ranges_list).await
+}
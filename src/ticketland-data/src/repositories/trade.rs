@@ -0,0 +1,61 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use eyre::Result;
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  models::trade::{NewTrade, Trade},
+  schema::trades::dsl::{
+    self as trades_dsl,
+    trades,
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Trades where the account is either the buyer or the seller, most recent first
+  pub async fn read_account_trades(
+    &mut self,
+    uid: String,
+    evt_id: Option<String>,
+    skip: i64,
+    limit: i64,
+  ) -> Result<Vec<Trade>> {
+    let mut query = trades
+    .filter(trades_dsl::seller_account_id.eq(uid.clone()).or(trades_dsl::buyer_account_id.eq(uid)))
+    .into_boxed();
+
+    if let Some(evt_id) = evt_id {
+      query = query.filter(trades_dsl::event_id.eq(evt_id));
+    }
+
+    Ok(
+      query
+      .order_by((trades_dsl::created_at.desc(), trades_dsl::trade_id.desc()))
+      .limit(limit)
+      .offset(skip * limit)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+
+  pub async fn read_event_trades(&mut self, evt_id: String, skip: i64, limit: i64) -> Result<Vec<Trade>> {
+    Ok(
+      trades
+      .filter(trades_dsl::event_id.eq(evt_id))
+      .order_by((trades_dsl::created_at.desc(), trades_dsl::trade_id.desc()))
+      .limit(limit)
+      .offset(skip * limit)
+      .load(self.borrow_mut())
+      .await?
+    )
+  }
+}
+
+/// Records a trade. Must run in the same transaction that fills the orders
+pub(crate) async fn insert_trade(conn: &mut AsyncPgConnection, trade: NewTrade) -> Result<Trade> {
+  Ok(
+    diesel::insert_into(trades)
+    .values(&trade)
+    .get_result(conn)
+    .await?
+  )
+}
@@ -0,0 +1,352 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{dsl, prelude::*};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::{Report, Result};
+use crate::{
+  connection::{ConnectionHandle, PostgresConnection},
+  error::Error,
+  repositories::{
+    event_status::check_event_status,
+    promo_code::check_public_ticket_type,
+    purchase_limit::check_purchase_limits,
+    seat_hold::{convert_holds, insert_seat_holds},
+  },
+  models::{
+    cnt::CNT,
+    event::EventStatus,
+    waitlist::{NewWaitlistEntry, WaitlistEntry, WaitlistPosition, WaitlistStatus},
+  },
+  schema::{
+    waitlist_entries::dsl::{
+      self as waitlist_entries_dsl,
+      waitlist_entries,
+    },
+    ticket_types::dsl::{
+      self as ticket_types_dsl,
+      ticket_types,
+    },
+    seat_holds::dsl::{
+      self as seat_holds_dsl,
+      seat_holds,
+    },
+    cnts::dsl::{
+      self as cnts_dsl,
+      cnts,
+    },
+  },
+};
+
+impl<C: ConnectionHandle> PostgresConnection<C> {
+  /// Adds the account to the end of the ticket type's waitlist. The event must be on sale and every seat of the
+  /// ticket type sold or held, otherwise fails with `Error::NotSoldOut`. An account can only wait once per
+  /// ticket type.
+  pub async fn join_waitlist(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+  ) -> Result<WaitlistPosition> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      check_event_status(conn, &event_id, &[EventStatus::OnSale]).await?;
+
+      let n_tickets = ticket_types
+      .filter(ticket_types_dsl::event_id.eq(&event_id))
+      .filter(ticket_types_dsl::ticket_type_index.eq(ticket_type_index))
+      .select(ticket_types_dsl::n_tickets)
+      .first::<i32>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::TicketTypeNotFound)?;
+
+      check_public_ticket_type(conn, &event_id, ticket_type_index).await?;
+
+      let sold = cnts
+      .filter(cnts_dsl::event_id.eq(&event_id))
+      .filter(cnts_dsl::ticket_type_index.eq(ticket_type_index))
+      .count()
+      .get_result::<i64>(conn)
+      .await?;
+
+      let held = seat_holds
+      .filter(seat_holds_dsl::event_id.eq(&event_id))
+      .filter(seat_holds_dsl::ticket_type_index.eq(ticket_type_index))
+      .filter(seat_holds_dsl::expires_at.gt(dsl::now))
+      .count()
+      .get_result::<i64>(conn)
+      .await?;
+
+      if sold + held < n_tickets as i64 {
+        return Err(Error::NotSoldOut.into())
+      }
+
+      if read_active_entry(conn, &event_id, ticket_type_index, &account_id).await?.is_some() {
+        return Err(Error::AlreadyOnWaitlist.into())
+      }
+
+      let entry = diesel::insert_into(waitlist_entries)
+      .values(&NewWaitlistEntry {event_id, ticket_type_index, account_id})
+      .get_result::<WaitlistEntry>(conn)
+      .await?;
+
+      with_position(conn, entry).await
+    }))
+    .await
+  }
+
+  /// Removes the account from the waitlist. If a seat was offered to it, the seat hold is released so the seat
+  /// can be offered to the next account.
+  pub async fn leave_waitlist(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+  ) -> Result<WaitlistEntry> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let entry = read_active_entry(conn, &event_id, ticket_type_index, &account_id).await?
+      .ok_or(Error::WaitlistEntryNotFound)?;
+
+      if let Some(seat_index) = entry.seat_index {
+        diesel::delete(seat_holds)
+        .filter(seat_holds_dsl::event_id.eq(&event_id))
+        .filter(seat_holds_dsl::seat_index.eq(seat_index))
+        .filter(seat_holds_dsl::account_id.eq(&account_id))
+        .execute(conn)
+        .await?;
+      }
+
+      resolve_entry(conn, entry.waitlist_entry_id, WaitlistStatus::Left).await
+    }))
+    .await
+  }
+
+  /// Offers a freed seat, e.g. from a completed refund or a released hold, to the oldest waiting account by holding
+  /// it for that account for `window_secs`. The event must be on sale. Accounts that already reached a purchase
+  /// limit are skipped but keep their place in the queue. Returns None if nobody else is waiting.
+  /// Fails with `Error::SeatUnavailable` if the seat is sold, held by someone else or not part of the ticket type,
+  /// and with `Error::TicketTypeLocked` if the ticket type is hidden behind an access code.
+  pub async fn offer_waitlist_seat(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    seat_index: i32,
+    window_secs: i64,
+  ) -> Result<Option<WaitlistEntry>> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      check_event_status(conn, &event_id, &[EventStatus::OnSale]).await?;
+      // The ticket type may have been hidden after the accounts joined
+      check_public_ticket_type(conn, &event_id, ticket_type_index).await?;
+
+      let mut last_entry_id = 0;
+      let next = loop {
+        let next = waitlist_entries
+        .filter(waitlist_entries_dsl::event_id.eq(&event_id))
+        .filter(waitlist_entries_dsl::ticket_type_index.eq(ticket_type_index))
+        .filter(waitlist_entries_dsl::status.eq(WaitlistStatus::Waiting))
+        .filter(waitlist_entries_dsl::waitlist_entry_id.gt(last_entry_id))
+        .order_by(waitlist_entries_dsl::waitlist_entry_id.asc())
+        .for_update()
+        .skip_locked()
+        .first::<WaitlistEntry>(conn)
+        .await
+        .optional()?;
+
+        let Some(next) = next else {return Ok(None)};
+        last_entry_id = next.waitlist_entry_id;
+
+        match check_purchase_limits(conn, &event_id, &next.account_id, ticket_type_index, 1, &[]).await {
+          Ok(()) => break next,
+          Err(error) if is_purchase_limit_error(&error) => continue,
+          Err(error) => return Err(error),
+        }
+      };
+
+      let holds = insert_seat_holds(
+        conn,
+        event_id,
+        ticket_type_index,
+        next.account_id,
+        vec![seat_index],
+        window_secs,
+      )
+      .await?;
+
+      Ok(Some(
+        diesel::update(waitlist_entries)
+        .filter(waitlist_entries_dsl::waitlist_entry_id.eq(next.waitlist_entry_id))
+        .set((
+          waitlist_entries_dsl::status.eq(WaitlistStatus::Offered),
+          waitlist_entries_dsl::seat_index.eq(seat_index),
+          waitlist_entries_dsl::offer_expires_at.eq(holds[0].expires_at),
+        ))
+        .get_result::<WaitlistEntry>(conn)
+        .await?
+      ))
+    }))
+    .await
+  }
+
+  /// Turns the seat offered to the account into a draft CNT, like `convert_seat_holds`.
+  pub async fn accept_waitlist_offer(&mut self, waitlist_entry_id: i64, account_id: String) -> Result<CNT> {
+    let now = Utc::now().naive_utc();
+
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let entry = waitlist_entries
+      .filter(waitlist_entries_dsl::waitlist_entry_id.eq(waitlist_entry_id))
+      .filter(waitlist_entries_dsl::account_id.eq(&account_id))
+      .filter(waitlist_entries_dsl::status.eq(WaitlistStatus::Offered))
+      .filter(waitlist_entries_dsl::offer_expires_at.gt(dsl::now))
+      .for_update()
+      .first::<WaitlistEntry>(conn)
+      .await
+      .optional()?
+      .ok_or(Error::WaitlistEntryNotFound)?;
+
+      let seat_index = entry.seat_index.ok_or(Error::WaitlistEntryNotFound)?;
+      let mut new_cnts = convert_holds(conn, &entry.event_id, &account_id, vec![seat_index], None, now).await?;
+
+      resolve_entry(conn, waitlist_entry_id, WaitlistStatus::Accepted).await?;
+
+      Ok(new_cnts.remove(0))
+    }))
+    .await
+  }
+
+  /// Marks the offers that were not accepted by `now` as expired and returns them, so their seats can be offered
+  /// to the next account. The seat holds of the offers are released in the same transaction.
+  pub async fn expire_waitlist_offers(&mut self, now: NaiveDateTime) -> Result<Vec<WaitlistEntry>> {
+    self.borrow_mut()
+    .transaction::<_, Report, _>(|conn| Box::pin(async move {
+      let expired = diesel::update(waitlist_entries)
+      .filter(waitlist_entries_dsl::status.eq(WaitlistStatus::Offered))
+      .filter(waitlist_entries_dsl::offer_expires_at.le(now))
+      .set((
+        waitlist_entries_dsl::status.eq(WaitlistStatus::Expired),
+        waitlist_entries_dsl::resolved_at.eq(dsl::now),
+      ))
+      .get_results::<WaitlistEntry>(conn)
+      .await?;
+
+      for entry in &expired {
+        let Some(seat_index) = entry.seat_index else {continue};
+
+        diesel::delete(seat_holds)
+        .filter(seat_holds_dsl::event_id.eq(&entry.event_id))
+        .filter(seat_holds_dsl::seat_index.eq(seat_index))
+        .filter(seat_holds_dsl::account_id.eq(&entry.account_id))
+        .execute(conn)
+        .await?;
+      }
+
+      Ok(expired)
+    }))
+    .await
+  }
+
+  pub async fn read_waitlist_position(
+    &mut self,
+    event_id: String,
+    ticket_type_index: i16,
+    account_id: String,
+  ) -> Result<Option<WaitlistPosition>> {
+    let conn = self.borrow_mut();
+    let Some(entry) = read_active_entry(conn, &event_id, ticket_type_index, &account_id).await? else {
+      return Ok(None)
+    };
+
+    Ok(Some(with_position(conn, entry).await?))
+  }
+
+  /// Every waitlist the account is currently on, oldest first
+  pub async fn read_account_waitlist_positions(&mut self, account_id: String) -> Result<Vec<WaitlistPosition>> {
+    let conn = self.borrow_mut();
+    let entries = waitlist_entries
+    .filter(waitlist_entries_dsl::account_id.eq(account_id))
+    .filter(waitlist_entries_dsl::status.eq_any([WaitlistStatus::Waiting, WaitlistStatus::Offered]))
+    .order_by(waitlist_entries_dsl::waitlist_entry_id.asc())
+    .load::<WaitlistEntry>(conn)
+    .await?;
+
+    let mut positions = Vec::with_capacity(entries.len());
+    for entry in entries {
+      positions.push(with_position(conn, entry).await?);
+    }
+
+    Ok(positions)
+  }
+
+  /// Number of accounts waiting for a seat of the ticket type
+  pub async fn read_waitlist_length(&mut self, event_id: String, ticket_type_index: i16) -> Result<i64> {
+    Ok(
+      waitlist_entries
+      .filter(waitlist_entries_dsl::event_id.eq(event_id))
+      .filter(waitlist_entries_dsl::ticket_type_index.eq(ticket_type_index))
+      .filter(waitlist_entries_dsl::status.eq(WaitlistStatus::Waiting))
+      .count()
+      .get_result::<i64>(self.borrow_mut())
+      .await?
+    )
+  }
+}
+
+fn is_purchase_limit_error(error: &Report) -> bool {
+  matches!(
+    error.downcast_ref::<Error>(),
+    Some(Error::EventPurchaseLimitExceeded(_) | Error::TicketTypePurchaseLimitExceeded(_)),
+  )
+}
+
+async fn read_active_entry(
+  conn: &mut AsyncPgConnection,
+  event_id: &str,
+  ticket_type_index: i16,
+  account_id: &str,
+) -> Result<Option<WaitlistEntry>> {
+  Ok(
+    waitlist_entries
+    .filter(waitlist_entries_dsl::event_id.eq(event_id))
+    .filter(waitlist_entries_dsl::ticket_type_index.eq(ticket_type_index))
+    .filter(waitlist_entries_dsl::account_id.eq(account_id))
+    .filter(waitlist_entries_dsl::status.eq_any([WaitlistStatus::Waiting, WaitlistStatus::Offered]))
+    .first::<WaitlistEntry>(conn)
+    .await
+    .optional()?
+  )
+}
+
+async fn with_position(conn: &mut AsyncPgConnection, entry: WaitlistEntry) -> Result<WaitlistPosition> {
+  if entry.status != WaitlistStatus::Waiting {
+    return Ok(WaitlistPosition {entry, position: 0})
+  }
+
+  let position = waitlist_entries
+  .filter(waitlist_entries_dsl::event_id.eq(&entry.event_id))
+  .filter(waitlist_entries_dsl::ticket_type_index.eq(entry.ticket_type_index))
+  .filter(waitlist_entries_dsl::status.eq(WaitlistStatus::Waiting))
+  .filter(waitlist_entries_dsl::waitlist_entry_id.le(entry.waitlist_entry_id))
+  .count()
+  .get_result::<i64>(conn)
+  .await?;
+
+  Ok(WaitlistPosition {entry, position})
+}
+
+async fn resolve_entry(
+  conn: &mut AsyncPgConnection,
+  waitlist_entry_id: i64,
+  status: WaitlistStatus,
+) -> Result<WaitlistEntry> {
+  Ok(
+    diesel::update(waitlist_entries)
+    .filter(waitlist_entries_dsl::waitlist_entry_id.eq(waitlist_entry_id))
+    .set((
+      waitlist_entries_dsl::status.eq(status),
+      waitlist_entries_dsl::resolved_at.eq(dsl::now),
+    ))
+    .get_result::<WaitlistEntry>(conn)
+    .await?
+  )
+}
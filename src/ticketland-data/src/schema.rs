@@ -42,6 +42,48 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    check_in_attempts (check_in_attempt_id) {
+        check_in_attempt_id -> Int8,
+        event_id -> Varchar,
+        cnt_sui_address -> Varchar,
+        seat_index -> Nullable<Int4>,
+        scanner_id -> Varchar,
+        gate -> Nullable<Varchar>,
+        result -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    cnt_ownership_events (ownership_event_id) {
+        ownership_event_id -> Int8,
+        event_id -> Varchar,
+        seat_index -> Int4,
+        cnt_sui_address -> Nullable<Varchar>,
+        kind -> Varchar,
+        from_account_id -> Nullable<Varchar>,
+        to_account_id -> Varchar,
+        trade_id -> Nullable<Int8>,
+        transfer_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    cnt_transfers (transfer_id) {
+        transfer_id -> Int8,
+        cnt_sui_address -> Varchar,
+        event_id -> Varchar,
+        sender_account_id -> Varchar,
+        recipient_account_id -> Nullable<Varchar>,
+        recipient_email -> Nullable<Varchar>,
+        status -> Varchar,
+        created_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     cnts (event_id, seat_index) {
         cnt_sui_address -> Nullable<Varchar>,
@@ -53,6 +95,8 @@ diesel::table! {
         seat_index -> Int4,
         attended -> Bool,
         draft -> Bool,
+        attended_at -> Nullable<Timestamptz>,
+        purchase_price -> Nullable<Int8>,
     }
 }
 
@@ -93,7 +137,7 @@ diesel::table! {
         event_nft -> Nullable<Varchar>,
         event_capacity_bitmap_address -> Nullable<Varchar>,
         webbundle_arweave_tx_id -> Nullable<Varchar>,
-        draft -> Bool,
+        status -> Varchar,
     }
 }
 
@@ -109,6 +153,7 @@ diesel::table! {
         is_open -> Bool,
         closed_at -> Nullable<Timestamptz>,
         draft -> Bool,
+        expires_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -133,6 +178,23 @@ diesel::table! {
         is_open -> Bool,
         closed_at -> Nullable<Timestamptz>,
         draft -> Bool,
+        expires_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    promo_codes (promo_code_id) {
+        promo_code_id -> Int8,
+        event_id -> Varchar,
+        code -> Varchar,
+        kind -> Varchar,
+        value -> Int8,
+        ticket_type_index -> Nullable<Int2>,
+        max_redemptions -> Nullable<Int4>,
+        redemptions -> Int4,
+        valid_from -> Nullable<Timestamptz>,
+        valid_until -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
     }
 }
 
@@ -145,6 +207,51 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    purchase_limits (purchase_limit_id) {
+        purchase_limit_id -> Int8,
+        event_id -> Varchar,
+        ticket_type_index -> Nullable<Int2>,
+        max_tickets -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    refund_requests (refund_id) {
+        refund_id -> Int8,
+        event_id -> Varchar,
+        seat_index -> Int4,
+        cnt_sui_address -> Nullable<Varchar>,
+        account_id -> Varchar,
+        reason -> Varchar,
+        status -> Varchar,
+        created_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    resale_policies (event_id) {
+        event_id -> Varchar,
+        max_price -> Nullable<Int8>,
+        max_price_percent -> Nullable<Int4>,
+        royalty_bps -> Int4,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    seat_holds (event_id, seat_index) {
+        event_id -> Varchar,
+        seat_index -> Int4,
+        ticket_type_index -> Int2,
+        account_id -> Varchar,
+        created_at -> Nullable<Timestamptz>,
+        expires_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     seat_ranges (event_id, ticket_type_index, l, r) {
         event_id -> Varchar,
@@ -206,9 +313,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    trades (trade_id) {
+        trade_id -> Int8,
+        event_id -> Varchar,
+        ticket_type_index -> Int2,
+        cnt_sui_address -> Varchar,
+        listing_id -> Nullable<Varchar>,
+        offer_id -> Nullable<Varchar>,
+        seller_account_id -> Varchar,
+        buyer_account_id -> Varchar,
+        price -> Int8,
+        created_at -> Timestamptz,
+        royalty -> Int8,
+        seller_proceeds -> Int8,
+    }
+}
+
+diesel::table! {
+    waitlist_entries (waitlist_entry_id) {
+        waitlist_entry_id -> Int8,
+        event_id -> Varchar,
+        ticket_type_index -> Int2,
+        account_id -> Varchar,
+        status -> Varchar,
+        seat_index -> Nullable<Int4>,
+        offer_expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::joinable!(api_clients -> accounts (account_id));
 diesel::joinable!(canva_accounts -> accounts (account_id));
 diesel::joinable!(canva_designs -> canva_accounts (canva_uid));
+diesel::joinable!(check_in_attempts -> events (event_id));
+diesel::joinable!(cnt_transfers -> events (event_id));
 diesel::joinable!(cnts -> accounts (account_id));
 diesel::joinable!(cnts -> events (event_id));
 diesel::joinable!(event_nft_details -> events (event_id));
@@ -221,7 +361,14 @@ diesel::joinable!(listings -> accounts (account_id));
 diesel::joinable!(listings -> events (event_id));
 diesel::joinable!(offers -> accounts (account_id));
 diesel::joinable!(offers -> events (event_id));
+diesel::joinable!(promo_codes -> events (event_id));
 diesel::joinable!(properties -> nft_details (nft_details_id));
+diesel::joinable!(purchase_limits -> events (event_id));
+diesel::joinable!(refund_requests -> accounts (account_id));
+diesel::joinable!(refund_requests -> events (event_id));
+diesel::joinable!(resale_policies -> events (event_id));
+diesel::joinable!(seat_holds -> accounts (account_id));
+diesel::joinable!(seat_holds -> events (event_id));
 diesel::joinable!(stripe_accounts -> accounts (account_id));
 diesel::joinable!(stripe_customers -> accounts (account_id));
 diesel::joinable!(ticket_type_nft_details -> events (event_id));
@@ -230,12 +377,20 @@ diesel::joinable!(ticket_type_nfts -> accounts (account_id));
 diesel::joinable!(ticket_type_nfts -> events (event_id));
 diesel::joinable!(ticket_type_nfts -> ticket_type_nft_details (ref_name));
 diesel::joinable!(ticket_types -> events (event_id));
+diesel::joinable!(trades -> events (event_id));
+diesel::joinable!(trades -> listings (listing_id));
+diesel::joinable!(trades -> offers (offer_id));
+diesel::joinable!(waitlist_entries -> accounts (account_id));
+diesel::joinable!(waitlist_entries -> events (event_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     accounts,
     api_clients,
     canva_accounts,
     canva_designs,
+    check_in_attempts,
+    cnt_ownership_events,
+    cnt_transfers,
     cnts,
     event_nft_details,
     event_nfts,
@@ -243,11 +398,18 @@ diesel::allow_tables_to_appear_in_same_query!(
     listings,
     nft_details,
     offers,
+    promo_codes,
     properties,
+    purchase_limits,
+    refund_requests,
+    resale_policies,
+    seat_holds,
     seat_ranges,
     stripe_accounts,
     stripe_customers,
     ticket_type_nft_details,
     ticket_type_nfts,
     ticket_types,
+    trades,
+    waitlist_entries,
 );
@@ -0,0 +1,60 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::models::{cnt_transfer::TransferStatus, cnt_ownership_event::OwnershipEventKind};
+
+#[tokio::test]
+async fn deleted_accounts_are_removed_from_received_transfers() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+
+  let by_id = postgres.create_cnt_transfer("c4".into(), "bob".into(), Some("alice".into()), Some("alice@x.io".into())).await.unwrap();
+  let by_email = postgres.create_cnt_transfer("c3".into(), "bob".into(), None, Some(" Alice@X.io".into())).await.unwrap();
+
+  postgres.delete_account("alice".into()).await.unwrap();
+
+  let transfers = postgres.read_outgoing_cnt_transfers("bob".into()).await.unwrap();
+  assert!(transfers.is_empty());
+
+  let export = postgres.export_account("bob".into()).await.unwrap();
+  assert_eq!(export.transfers.len(), 2);
+  for transfer in export.transfers {
+    assert!([by_id.transfer_id, by_email.transfer_id].contains(&transfer.transfer_id));
+    assert_eq!(transfer.recipient_account_id.as_deref(), Some("alice"));
+    assert_eq!(transfer.recipient_email, None);
+    assert_eq!(transfer.status, TransferStatus::Declined);
+  }
+}
+
+#[tokio::test]
+async fn exports_include_refunds_waitlists_holds_scans_and_history() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+  postgres.request_refund("c3".into(), "bob".into()).await.unwrap();
+  postgres.hold_seats("e1".into(), 0, "bob".into(), vec![5], 60, None).await.unwrap();
+  postgres.allocate_seats("e3".into(), 0, "bob".into(), 1).await.unwrap();
+  postgres.check_in_cnt("e2".into(), "c4".into(), "gate-a".into(), None).await.unwrap();
+
+  postgres.borrow_mut().batch_execute("
+    INSERT INTO waitlist_entries (event_id, ticket_type_index, account_id) VALUES ('e2', 0, 'bob'), ('e2', 0, 'alice');
+  ")
+  .await
+  .unwrap();
+
+  let export = postgres.export_account("bob".into()).await.unwrap();
+  assert_eq!(export.refund_requests.iter().map(|refund| refund.seat_index).collect::<Vec<_>>(), [10]);
+  assert_eq!(export.waitlist_entries.iter().map(|entry| entry.account_id.as_str()).collect::<Vec<_>>(), ["bob"]);
+  assert_eq!(export.seat_holds.iter().map(|hold| hold.seat_index).collect::<Vec<_>>(), [5]);
+  assert_eq!(export.check_in_attempts.iter().map(|attempt| attempt.cnt_sui_address.as_str()).collect::<Vec<_>>(), ["c4"]);
+  assert_eq!(
+    export.ownership_history.iter().map(|entry| (entry.event_id.as_str(), entry.kind)).collect::<Vec<_>>(),
+    [("e3", OwnershipEventKind::Issued), ("e2", OwnershipEventKind::Attended)],
+  );
+
+  let export = postgres.export_account("alice".into()).await.unwrap();
+  assert!(export.refund_requests.is_empty() && export.seat_holds.is_empty() && export.ownership_history.is_empty());
+  assert_eq!(export.waitlist_entries.len(), 1);
+}
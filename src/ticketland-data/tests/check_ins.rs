@@ -0,0 +1,68 @@
+mod common;
+
+use chrono::Utc;
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  error::Error,
+  models::{check_in::{CheckInResult, API_SCANNER_ID}, event::EventStatus},
+};
+
+#[tokio::test]
+async fn scans_are_rejected_unless_the_event_admits() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.update_event_status("e1".into(), EventStatus::Paused).await.unwrap();
+  let attempt = postgres.check_in_cnt("e1".into(), "c1".into(), "gate-a".into(), None).await.unwrap();
+  assert_eq!(attempt.result, CheckInResult::Admitted);
+
+  postgres.cancel_event("e1".into()).await.unwrap();
+  let attempt = postgres.check_in_cnt("e1".into(), "c3".into(), "gate-a".into(), None).await.unwrap();
+  assert_eq!((attempt.result, attempt.seat_index), (CheckInResult::Rejected, Some(10)));
+  assert!(!postgres.has_attended("c3".into()).await.unwrap());
+
+  let now = Utc::now().naive_utc();
+  postgres.postpone_event("e2".into(), now, now).await.unwrap();
+  let attempt = postgres.check_in_cnt("e2".into(), "c4".into(), "gate-a".into(), None).await.unwrap();
+  assert_eq!(attempt.result, CheckInResult::Rejected);
+}
+
+#[tokio::test]
+async fn attendance_updates_are_recorded_as_scans() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let attempt = postgres.update_attended("c1".into()).await.unwrap();
+  assert_eq!((attempt.result, attempt.scanner_id.as_str()), (CheckInResult::Admitted, API_SCANNER_ID));
+  assert!(postgres.has_attended("c1".into()).await.unwrap());
+
+  assert_eq!(postgres.update_attended("c1".into()).await.unwrap().result, CheckInResult::Duplicate);
+  assert_eq!(postgres.read_cnt_check_in_attempts("c1".into()).await.unwrap().len(), 2);
+
+  let error = postgres.update_attended("missing".into()).await.unwrap_err();
+  assert_eq!(error.downcast_ref::<Error>(), Some(&Error::CntNotFound));
+
+  postgres.cancel_event("e1".into()).await.unwrap();
+  assert_eq!(postgres.update_attended("c3".into()).await.unwrap().result, CheckInResult::Rejected);
+}
+
+#[tokio::test]
+async fn cnts_being_refunded_are_not_admitted() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+  let refund = postgres.request_refund("c3".into(), "bob".into()).await.unwrap();
+
+  let attempt = postgres.check_in_cnt("e1".into(), "c3".into(), "gate-a".into(), None).await.unwrap();
+  assert_eq!(attempt.result, CheckInResult::Rejected);
+  assert!(!postgres.has_attended("c3".into()).await.unwrap());
+
+  // e.g. synced from chain, the attended ticket can no longer be paid back
+  postgres.approve_refund(refund.refund_id).await.unwrap();
+  postgres.borrow_mut().batch_execute("UPDATE cnts SET attended = true WHERE cnt_sui_address = 'c3'").await.unwrap();
+
+  let error = postgres.complete_refund(refund.refund_id).await.unwrap_err();
+  assert_eq!(error.downcast_ref::<Error>(), Some(&Error::CntAttended));
+  assert_eq!(postgres.read_cnt("c3".into()).await.unwrap().len(), 1);
+}
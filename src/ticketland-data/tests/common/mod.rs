@@ -0,0 +1,60 @@
+use std::{env, sync::{Mutex, MutexGuard}};
+use diesel::{sql_query, sql_types::Text, QueryableByName};
+use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+use ticketland_data::{connection::PostgresConnection, connection_pool::ConnectionPool};
+
+/// Tests share one database, so they run one at a time
+static DATABASE: Mutex<()> = Mutex::new(());
+
+const SEED: &str = include_str!("../fixtures/seed.sql");
+
+#[derive(QueryableByName)]
+struct TableName {
+  #[diesel(sql_type = Text)]
+  tablename: String,
+}
+
+pub struct TestDb {
+  pool: ConnectionPool,
+  _guard: MutexGuard<'static, ()>,
+}
+
+impl TestDb {
+  pub async fn connection(&self) -> PostgresConnection {
+    self.pool.connection().await.unwrap()
+  }
+}
+
+/// Brings the database in `DATABASE_URL` to the latest schema, empties it and loads `fixtures/seed.sql`.
+/// Returns None, so the test is skipped, when `DATABASE_URL` is not set.
+///
+/// Each test runs on its own thread, so holding the lock while awaiting cannot block another test's progress.
+#[allow(clippy::await_holding_lock)]
+pub async fn setup() -> Option<TestDb> {
+  let Ok(db_uri) = env::var("DATABASE_URL") else {
+    eprintln!("DATABASE_URL is not set, skipping");
+    return None
+  };
+
+  // A failed test poisons the lock but leaves nothing behind that the next setup does not clear
+  let guard = DATABASE.lock().unwrap_or_else(|error| error.into_inner());
+  let pool = ConnectionPool::new(&db_uri).await;
+  pool.run_pending_migrations().await.unwrap();
+
+  let mut postgres = pool.connection().await.unwrap();
+  let conn = postgres.borrow_mut();
+
+  let tables = sql_query("SELECT tablename FROM pg_tables WHERE schemaname = 'public' AND tablename NOT LIKE '\\_\\_%'")
+  .load::<TableName>(conn)
+  .await
+  .unwrap()
+  .into_iter()
+  .map(|table| format!("\"{}\"", table.tablename))
+  .collect::<Vec<_>>()
+  .join(", ");
+
+  conn.batch_execute(&format!("TRUNCATE {tables} RESTART IDENTITY CASCADE")).await.unwrap();
+  conn.batch_execute(SEED).await.unwrap();
+
+  Some(TestDb {pool, _guard: guard})
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  connection::PostgresConnection,
+  error::Error,
+  models::{event::EventStatus, refund_request::{RefundReason, RefundStatus}},
+};
+
+fn assert_error<T>(result: eyre::Result<T>, expected: Error) {
+  let Err(error) = result else {panic!("expected {expected}")};
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+fn invalid_transition(from: EventStatus, to: EventStatus) -> Error {
+  Error::InvalidEventTransition {from: from.as_str(), to: to.as_str()}
+}
+
+/// Sells a VIP seat of e1 to the account as a CNT with the given address
+async fn sell_vip_seat(postgres: &mut PostgresConnection, account_id: &str, seat_index: i32, cnt_address: &str) {
+  postgres.hold_seats("e1".into(), 1, account_id.into(), vec![seat_index], 60, None).await.unwrap();
+  postgres.convert_seat_holds("e1".into(), account_id.into(), vec![seat_index], None).await.unwrap();
+  postgres.borrow_mut()
+  .batch_execute(&format!("UPDATE cnts SET cnt_sui_address = '{cnt_address}' WHERE event_id = 'e1' AND seat_index = {seat_index}"))
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn statuses_follow_the_event_lifecycle() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let event = postgres.update_event_status("e1".into(), EventStatus::Paused).await.unwrap();
+  assert_eq!(event.status, EventStatus::Paused);
+  postgres.update_event_status("e1".into(), EventStatus::OnSale).await.unwrap();
+
+  assert_error(
+    postgres.update_event_status("e1".into(), EventStatus::Published).await,
+    invalid_transition(EventStatus::OnSale, EventStatus::Published),
+  );
+  // Cancelling has its own operation
+  assert_error(
+    postgres.update_event_status("e1".into(), EventStatus::Cancelled).await,
+    invalid_transition(EventStatus::OnSale, EventStatus::Cancelled),
+  );
+
+  // Drafts are only published by committing them
+  postgres.borrow_mut().batch_execute("UPDATE events SET status = 'draft' WHERE event_id = 'e3'").await.unwrap();
+  assert_error(
+    postgres.update_event_status("e3".into(), EventStatus::Published).await,
+    invalid_transition(EventStatus::Draft, EventStatus::Published),
+  );
+
+  let start_date = Utc::now().date_naive().and_hms_opt(20, 0, 0).unwrap() + Duration::days(30);
+  assert_error(
+    postgres.postpone_event("e2".into(), start_date, start_date - Duration::hours(1)).await,
+    Error::InvalidEventStatus("ending before it starts"),
+  );
+  let event = postgres.postpone_event("e2".into(), start_date, start_date + Duration::hours(4)).await.unwrap();
+  assert_eq!((event.status, event.start_date), (EventStatus::Postponed, start_date));
+  postgres.update_event_status("e2".into(), EventStatus::OnSale).await.unwrap();
+
+  // Completed events are final
+  postgres.update_event_status("e2".into(), EventStatus::Completed).await.unwrap();
+  assert_error(
+    postgres.update_event_status("e2".into(), EventStatus::OnSale).await,
+    invalid_transition(EventStatus::Completed, EventStatus::OnSale),
+  );
+  assert_error(postgres.cancel_event("e2".into()).await, invalid_transition(EventStatus::Completed, EventStatus::Cancelled));
+
+  assert_error(postgres.read_event_status("missing".into()).await, Error::EventNotFound);
+}
+
+#[tokio::test]
+async fn cancelled_events_refund_every_cnt_and_close_the_market() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // c3 was refunded and its seat sold again to alice as c5
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+  let refund = postgres.request_refund("c3".into(), "bob".into()).await.unwrap();
+  postgres.approve_refund(refund.refund_id).await.unwrap();
+  postgres.complete_refund(refund.refund_id).await.unwrap();
+  sell_vip_seat(&mut postgres, "alice", 10, "c5").await;
+
+  // Bob is already waiting for the refund of c6
+  sell_vip_seat(&mut postgres, "bob", 11, "c6").await;
+  let pending = postgres.request_refund("c6".into(), "bob".into()).await.unwrap();
+
+  postgres.hold_seats("e1".into(), 1, "org".into(), vec![20], 60, None).await.unwrap();
+  postgres.create_cnt_transfer("c5".into(), "alice".into(), Some("bob".into()), None).await.unwrap();
+
+  let cancellation = postgres.cancel_event("e1".into()).await.unwrap();
+  assert_eq!(
+    (
+      cancellation.refund_requests,
+      cancellation.closed_listings,
+      cancellation.closed_offers,
+      cancellation.cancelled_transfers,
+      cancellation.released_seat_holds,
+    ),
+    // c1, c5 and c6 but not the attended c2; l1; o1, o2 and o3
+    (3, 1, 3, 1, 1),
+  );
+  assert_eq!(postgres.read_event_status("e1".into()).await.unwrap(), EventStatus::Cancelled);
+
+  let refunds = postgres.read_account_refunds("alice".into()).await.unwrap();
+  let mut refunds = refunds.iter().map(|refund| (refund.seat_index, refund.reason, refund.status)).collect::<Vec<_>>();
+  refunds.sort_by_key(|refund| refund.0);
+  assert_eq!(refunds, [(0, RefundReason::EventCancelled, RefundStatus::Approved), (10, RefundReason::EventCancelled, RefundStatus::Approved)]);
+
+  let refunds = postgres.read_account_refunds("bob".into()).await.unwrap();
+  let refunds = refunds.iter().map(|refund| (refund.refund_id, refund.status)).collect::<Vec<_>>();
+  assert_eq!(refunds, [(pending.refund_id, RefundStatus::Approved), (refund.refund_id, RefundStatus::Completed)]);
+
+  let listing = postgres.read_listing("l1".into()).await.unwrap();
+  assert!(!listing.is_open && listing.closed_at.is_some());
+  let offer = postgres.read_offer("o1".into()).await.unwrap();
+  assert!(!offer.is_open && offer.closed_at.is_some());
+
+  assert!(postgres.read_active_seat_holds("e1".into()).await.unwrap().is_empty());
+  assert!(postgres.read_outgoing_cnt_transfers("alice".into()).await.unwrap().is_empty());
+
+  assert_error(postgres.cancel_event("e1".into()).await, invalid_transition(EventStatus::Cancelled, EventStatus::Cancelled));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  connection::PostgresConnection,
+  models::{
+    event::{EventFilters, EventSort, GeoFilter},
+    promo_code::{NewPromoCode, PromoCodeKind},
+  },
+};
+
+const ATHENS: GeoFilter = GeoFilter {latitude: 37.98, longitude: 23.72, radius_km: 100.0};
+
+async fn search(postgres: &mut PostgresConnection, filters: EventFilters) -> Vec<String> {
+  postgres.read_filtered_events(filters, 0, 10)
+  .await
+  .unwrap()
+  .into_iter()
+  .map(|event| event.event_id)
+  .collect()
+}
+
+#[tokio::test]
+async fn filters_by_each_criterion() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  let now = Utc::now().naive_utc();
+
+  assert_eq!(search(&mut postgres, EventFilters::default()).await, ["e1", "e2", "e3"]);
+  assert_eq!(search(&mut postgres, EventFilters {category: Some(1), ..Default::default()}).await, ["e1", "e3"]);
+  assert_eq!(search(&mut postgres, EventFilters {category: Some(3), ..Default::default()}).await, Vec::<String>::new());
+
+  // e3 is free, e1 starts at 5000 and the auction of e2 has dropped twice in its first two hours, to 8400
+  assert_eq!(search(&mut postgres, EventFilters {price_range: Some((0, 0)), ..Default::default()}).await, ["e3"]);
+  assert_eq!(search(&mut postgres, EventFilters {price_range: Some((8000, 9000)), ..Default::default()}).await, ["e2"]);
+  assert_eq!(search(&mut postgres, EventFilters {price_range: Some((4000, 6000)), ..Default::default()}).await, ["e1"]);
+  assert_eq!(search(&mut postgres, EventFilters {price_range: Some((15000, 30000)), ..Default::default()}).await, ["e1"]);
+
+  let filters = EventFilters {
+    start_date_from: Some(now + Duration::days(7)),
+    start_date_to: Some(now + Duration::days(15)),
+    ..Default::default()
+  };
+  assert_eq!(search(&mut postgres, filters).await, ["e1"]);
+
+  assert_eq!(search(&mut postgres, EventFilters {name: Some("NIGHT".into()), ..Default::default()}).await, ["e1"]);
+  assert_eq!(search(&mut postgres, EventFilters {name: Some("100% live".into()), ..Default::default()}).await, ["e2"]);
+
+  assert_eq!(search(&mut postgres, EventFilters {text: Some("techno".into()), ..Default::default()}).await, ["e3"]);
+  assert_eq!(search(&mut postgres, EventFilters {text: Some("\"smooth saxophone\"".into()), ..Default::default()}).await, ["e2"]);
+  assert_eq!(search(&mut postgres, EventFilters {text: Some("athens -jazz".into()), ..Default::default()}).await, ["e1"]);
+  assert_eq!(search(&mut postgres, EventFilters {text: Some("gazi".into()), ..Default::default()}).await, ["e1"]);
+
+  assert_eq!(search(&mut postgres, EventFilters {near: Some(ATHENS), ..Default::default()}).await, ["e1"]);
+
+  let filters = EventFilters {
+    category: Some(1),
+    price_range: Some((0, 10000)),
+    near: Some(GeoFilter {radius_km: 400.0, ..ATHENS}),
+    ..Default::default()
+  };
+  assert_eq!(search(&mut postgres, filters).await, ["e1", "e3"]);
+}
+
+#[tokio::test]
+async fn sorts_and_pages() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  assert_eq!(search(&mut postgres, EventFilters {sort: EventSort::StartDate, ..Default::default()}).await, ["e3", "e1", "e2"]);
+  assert_eq!(search(&mut postgres, EventFilters {sort: EventSort::Price, ..Default::default()}).await, ["e3", "e1", "e2"]);
+
+  let filters = EventFilters {near: Some(GeoFilter {radius_km: 2000.0, ..ATHENS}), sort: EventSort::Distance, ..Default::default()};
+  assert_eq!(search(&mut postgres, filters).await, ["e1", "e3", "e2"]);
+
+  // e3 mentions both words, e1 only the first
+  let filters = EventFilters {text: Some("night or techno".into()), sort: EventSort::Relevance, ..Default::default()};
+  assert_eq!(search(&mut postgres, filters).await, ["e3", "e1"]);
+
+  assert!(postgres.read_filtered_events(EventFilters {sort: EventSort::Relevance, ..Default::default()}, 0, 10).await.is_err());
+  assert!(postgres.read_filtered_events(EventFilters {sort: EventSort::Distance, ..Default::default()}, 0, 10).await.is_err());
+
+  // Pages are of whole events, not of ticket type and seat range rows
+  let first = postgres.read_filtered_events(EventFilters::default(), 0, 2).await.unwrap();
+  assert_eq!(first.iter().map(|event| event.event_id.as_str()).collect::<Vec<_>>(), ["e1", "e2"]);
+  assert_eq!(first[0].ticket_types.len(), 2);
+  assert_eq!(first[0].ticket_types[1].seat_ranges.len(), 2);
+
+  let second = postgres.read_filtered_events(EventFilters::default(), 1, 2).await.unwrap();
+  assert_eq!(second.iter().map(|event| event.event_id.as_str()).collect::<Vec<_>>(), ["e3"]);
+}
+
+#[tokio::test]
+async fn hides_unlisted_events_and_access_code_ticket_types() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.borrow_mut().batch_execute("UPDATE events SET status = 'draft' WHERE event_id = 'e2'").await.unwrap();
+  postgres.create_promo_code(NewPromoCode {
+    event_id: "e1".into(),
+    code: "backstage".into(),
+    kind: PromoCodeKind::Access,
+    value: 0,
+    ticket_type_index: Some(1),
+    max_redemptions: None,
+    valid_from: None,
+    valid_until: None,
+  })
+  .await
+  .unwrap();
+
+  let events = postgres.read_filtered_events(EventFilters::default(), 0, 10).await.unwrap();
+  assert_eq!(events.iter().map(|event| event.event_id.as_str()).collect::<Vec<_>>(), ["e1", "e3"]);
+  assert_eq!(events[0].ticket_types.iter().map(|tt| tt.ticket_type_index).collect::<Vec<_>>(), [0]);
+
+  // The hidden VIP ticket type no longer makes e1 match on its price
+  assert!(search(&mut postgres, EventFilters {price_range: Some((15000, 30000)), ..Default::default()}).await.is_empty());
+}
@@ -0,0 +1,42 @@
+-- Three on sale events, e1 with a fixed price and a refundable ticket type, e2 with a dutch auction and e3 free.
+-- alice owns c1 (listed) and c2 (attended) in e1, bob owns c3 (listed) in e1 and c4 in e2.
+
+INSERT INTO accounts (uid, dapp_share, pubkey, name, email) VALUES
+ ('org', 'ds_org', 'pk_org', 'Organizer', 'org@x.io'),
+ ('alice', 'ds_alice', 'pk_alice', 'Alice', 'alice@x.io'),
+ ('bob', 'ds_bob', 'pk_bob', 'Bob', 'bob@x.io');
+
+INSERT INTO events (event_id, account_id, name, description, location, venue, event_type, visibility, start_date, end_date, category, status) VALUES
+ ('e1', 'org', 'Rock''n Roll night', 'Loud guitars in Athens', '{"name":"Athens","latitude":37.98,"longitude":23.72}', 'Gazi Music Hall', 0, 0, now() + interval '10 days', now() + interval '11 days', 1, 'on_sale'),
+ ('e2', 'org', 'Jazz 100% live', 'Smooth saxophone evening', '{"name":"Berlin","latitude":52.52,"longitude":13.40}', 'A-Trane', 0, 0, now() + interval '20 days', now() + interval '21 days', 2, 'on_sale'),
+ ('e3', 'org', 'Techno_rave', 'All night techno', '{"name":"Thessaloniki","latitude":40.64,"longitude":22.94}', 'Block 33', 0, 0, now() + interval '5 days', now() + interval '6 days', 1, 'on_sale');
+
+INSERT INTO ticket_types (ticket_type_sui_address, event_id, ticket_type_index, ticket_type_name, n_tickets, sale_start_ts, sale_end_ts, sale_type) VALUES
+ ('tt_e1_0', 'e1', 0, 'GA', 10, now() - interval '1 day', now() + interval '9 days', '{"FixedPrice":{"price":5000}}'),
+ ('tt_e1_1', 'e1', 1, 'VIP', 4, now() - interval '1 day', now() + interval '9 days', '{"Refundable":{"price":20000}}'),
+ ('tt_e2_0', 'e2', 0, 'GA', 10, now() - interval '2 hours', now() + interval '19 days', '{"DutchAuction":{"start_price":10000,"end_price":2000,"curve_length":600,"drop_interval":60}}'),
+ ('tt_e3_0', 'e3', 0, 'Free', 100, now() - interval '1 day', now() + interval '4 days', '{"Free":{}}');
+
+INSERT INTO seat_ranges (event_id, ticket_type_index, l, r) VALUES
+ ('e1', 0, 0, 10), ('e1', 1, 10, 12), ('e1', 1, 20, 22), ('e2', 0, 0, 10), ('e3', 0, 0, 100);
+
+INSERT INTO nft_details (nft_name, nft_description, content_type, arweave_tx_id) VALUES
+ ('n1', 'd', 'image/png', 'ar1'), ('n2', 'd', 'image/png', 'ar2');
+
+INSERT INTO ticket_type_nft_details (ref_name, event_id, ticket_type_index, nft_details_id) VALUES
+ ('r_e1_0', 'e1', 0, 'ar1'), ('r_e1_1', 'e1', 1, 'ar1'), ('r_e2_0', 'e2', 0, 'ar2'), ('r_e3_0', 'e3', 0, 'ar2');
+
+INSERT INTO cnts (cnt_sui_address, event_id, account_id, created_at, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+ ('c1', 'e1', 'alice', now() - interval '3 hours', 0, '0', 0, false, false),
+ ('c2', 'e1', 'alice', now() - interval '2 hours', 0, '1', 1, true, false),
+ ('c3', 'e1', 'bob', now() - interval '1 hours', 1, '10', 10, false, false),
+ ('c4', 'e2', 'bob', now() - interval '1 hours', 0, '0', 0, false, false);
+
+INSERT INTO listings (listing_id, listing_sui_address, account_id, event_id, cnt_sui_address, created_at, ask_price, is_open, draft) VALUES
+ ('l1', 'ls1', 'alice', 'e1', 'c1', now() - interval '50 minutes', 6000, true, false),
+ ('l2', 'ls2', 'bob', 'e1', 'c3', now() - interval '40 minutes', 25000, true, false);
+
+INSERT INTO offers (offer_id, offer_sui_address, account_id, event_id, ticket_type_index, created_at, bid_price, is_open, draft) VALUES
+ ('o1', 'os1', 'bob', 'e1', 0, now() - interval '30 minutes', 5500, true, false),
+ ('o2', 'os2', 'alice', 'e1', 1, now() - interval '20 minutes', 21000, true, false),
+ ('o3', 'os3', 'org', 'e1', 0, now() - interval '10 minutes', 5800, true, false);
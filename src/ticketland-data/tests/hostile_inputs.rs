@@ -0,0 +1,399 @@
+//! Feeds strings that would break or rewrite a query built with `format!` through every public repository method
+//! that takes caller supplied text. Methods may reject the input with a domain error or a constraint violation,
+//! but the input must never end up as SQL.
+
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use ticketland_data::{
+  connection::PostgresConnection,
+  models::{
+    account::Account,
+    canva_account::CanvaAccount,
+    canva_design::CanvaDesign,
+    event::{Event, EventFilters, EventSort, EventStatus, GeoFilter},
+    listing::NewListing,
+    nft::NewTicketTypeNft,
+    nft_detail::{NewEventNftDetail, NewNftDetail, NewTicketTypeNftDetail},
+    offer::NewOffer,
+    promo_code::{NewPromoCode, PromoCodeKind},
+    seat_range::SeatRange,
+    stripe_account::StripeAccount,
+    stripe_customer::StripeCustomer,
+    ticket_type::{NewTicketType, SaleType},
+  },
+};
+
+const HOSTILE: &[&str] = &[
+  "'",
+  "''",
+  "Rock'n Roll",
+  "\"",
+  "\\",
+  "%",
+  "_",
+  "100%_off\\",
+  ";",
+  "'; DROP TABLE events; --",
+  "' OR '1'='1",
+  "') OR 1=1 --",
+  "$1",
+  "/* */",
+  "Ελληνικά μουσική",
+  "日本語のイベント",
+  "🎸🎷",
+  "e\u{301}",
+];
+
+/// Fails the test if the input made it into the SQL text. Postgres reports syntax errors and type errors from
+/// spliced values with an unknown error kind, while rejections of well formed queries are domain errors or
+/// constraint violations.
+fn assert_safe<T>(method: &str, input: &str, result: eyre::Result<T>) {
+  let Err(error) = result else {return};
+
+  if let Some(DieselError::DatabaseError(DatabaseErrorKind::Unknown, info)) = error.downcast_ref::<DieselError>() {
+    panic!("{method}({input:?}) broke the query: {}", info.message());
+  }
+}
+
+fn filters(input: &str) -> Vec<EventFilters> {
+  vec![
+    EventFilters {name: Some(input.to_string()), ..Default::default()},
+    EventFilters {text: Some(input.to_string()), sort: EventSort::Relevance, ..Default::default()},
+    EventFilters {
+      name: Some(input.to_string()),
+      text: Some(input.to_string()),
+      near: Some(GeoFilter {latitude: 37.98, longitude: 23.72, radius_km: 100.0}),
+      sort: EventSort::Distance,
+      ..Default::default()
+    },
+  ]
+}
+
+async fn read_everything(postgres: &mut PostgresConnection, input: &str) {
+  let s = || input.to_string();
+
+  assert_safe("read_account_by_id", input, postgres.read_account_by_id(s()).await);
+  assert_safe("read_account_by_canva_id", input, postgres.read_account_by_canva_id(s()).await);
+  assert_safe("export_account", input, postgres.export_account(s()).await);
+  assert_safe("read_api_client", input, postgres.read_api_client(s()).await);
+  assert_safe("read_canva_designs", input, postgres.read_canva_designs(s()).await);
+  assert_safe("read_check_ins_count", input, postgres.read_check_ins_count(s(), 3600, 0).await);
+  assert_safe("read_scanner_check_ins", input, postgres.read_scanner_check_ins(s()).await);
+  assert_safe("read_duplicate_scans", input, postgres.read_duplicate_scans(s(), 0, 10).await);
+  assert_safe("read_cnt_check_in_attempts", input, postgres.read_cnt_check_in_attempts(s()).await);
+  assert_safe("read_no_shows", input, postgres.read_no_shows(s(), 0, 10).await);
+  assert_safe("read_cnts_for_event", input, postgres.read_cnts_for_event(s(), 0, 10).await);
+  assert_safe("read_cnts_for_event_after", input, postgres.read_cnts_for_event_after(s(), Some(s()), 10).await);
+  assert_safe("read_user_cnts", input, postgres.read_user_cnts(s(), Some(s()), 0, 10).await);
+  assert_safe("read_user_cnts_after", input, postgres.read_user_cnts_after(s(), Some(s()), Some(s()), 10).await);
+  assert_safe("has_attended", input, postgres.has_attended(s()).await);
+  assert_safe("read_cnt", input, postgres.read_cnt(s()).await);
+  assert_safe("read_cnt_history", input, postgres.read_cnt_history(s()).await);
+  assert_safe("read_incoming_cnt_transfers", input, postgres.read_incoming_cnt_transfers(s()).await);
+  assert_safe("read_outgoing_cnt_transfers", input, postgres.read_outgoing_cnt_transfers(s()).await);
+  assert_safe("read_event_organizer_account", input, postgres.read_event_organizer_account(s()).await);
+  assert_safe(
+    "read_account_events",
+    input,
+    postgres.read_account_events(s(), None, None, None, None, Some(s()), 0, 10).await,
+  );
+  assert_safe(
+    "read_account_ticket_events",
+    input,
+    postgres.read_account_ticket_events(s(), None, None, None, None, Some(s()), 0, 10).await,
+  );
+  assert_safe("read_event", input, postgres.read_event(s()).await);
+  assert_safe("read_event_and_organizer", input, postgres.read_event_and_organizer(s()).await);
+
+  for filters in filters(input) {
+    assert_safe("read_filtered_events", input, postgres.read_filtered_events(filters.clone(), 0, 10).await);
+    assert_safe("read_filtered_events_after", input, postgres.read_filtered_events_after(filters, Some(s()), 10).await);
+  }
+
+  assert_safe("read_event_with_ticket_types", input, postgres.read_event_with_ticket_types(s(), false).await);
+  assert_safe("read_attended_tickets_count", input, postgres.read_attended_tickets_count(s()).await);
+  assert_safe("read_event_status", input, postgres.read_event_status(s()).await);
+  assert_safe("read_available_tickets", input, postgres.read_available_tickets(s(), 0).await);
+  assert_safe("read_listing", input, postgres.read_listing(s()).await);
+  assert_safe("read_listings_for_event", input, postgres.read_listings_for_event(s(), 0, 10).await);
+  assert_safe("read_listings_for_event_after", input, postgres.read_listings_for_event_after(s(), Some(s()), 10).await);
+  assert_safe("read_listings_for_account", input, postgres.read_listings_for_account(Some(s()), s(), 0, 10).await);
+  assert_safe("read_offer", input, postgres.read_offer(s()).await);
+  assert_safe("read_offers_for_event", input, postgres.read_offers_for_event(s(), 0, 10).await);
+  assert_safe("read_offers_for_event_after", input, postgres.read_offers_for_event_after(s(), Some(s()), 10).await);
+  assert_safe("read_offers_for_account", input, postgres.read_offers_for_account(Some(s()), s(), 0, 10).await);
+  assert_safe("read_order_book", input, postgres.read_order_book(s(), 5).await);
+  assert_safe("read_event_promo_codes", input, postgres.read_event_promo_codes(s()).await);
+  assert_safe("read_access_code_ticket_type", input, postgres.read_access_code_ticket_type("e1".into(), s()).await);
+  assert_safe("quote_ticket_price", input, postgres.quote_ticket_price("e1".into(), 0, Some(s())).await);
+  assert_safe("read_purchase_limits", input, postgres.read_purchase_limits(s()).await);
+  assert_safe("read_purchase_allowance", input, postgres.read_purchase_allowance(s(), 0, s()).await);
+  assert_safe("read_pending_refunds", input, postgres.read_pending_refunds(s(), 0, 10).await);
+  assert_safe("read_account_refunds", input, postgres.read_account_refunds(s()).await);
+  assert_safe("read_resale_policy", input, postgres.read_resale_policy(s()).await);
+  assert_safe("read_closed_sales_count", input, postgres.read_closed_sales_count(s(), 3600, 0).await);
+  assert_safe("read_average_sales_price", input, postgres.read_average_sales_price(s(), 3600, 0).await);
+  assert_safe("read_primary_sales_count", input, postgres.read_primary_sales_count(s(), 3600, 0).await);
+  assert_safe("read_revenue_per_sale_type", input, postgres.read_revenue_per_sale_type(s()).await);
+  assert_safe("read_sell_through", input, postgres.read_sell_through(s()).await);
+  assert_safe("read_resale_volume", input, postgres.read_resale_volume(s(), 3600, 0).await);
+  assert_safe("read_active_seat_holds", input, postgres.read_active_seat_holds(s()).await);
+  assert_safe("read_ticket_type_seat_ranges", input, postgres.read_ticket_type_seat_ranges(s(), 0).await);
+  assert_safe("read_stripe_account", input, postgres.read_stripe_account(s()).await);
+  assert_safe("read_event_organizer_stripe_account", input, postgres.read_event_organizer_stripe_account(s()).await);
+  assert_safe("read_stripe_customer", input, postgres.read_stripe_customer(s()).await);
+  assert_safe("read_ticket_type", input, postgres.read_ticket_type(s(), 0).await);
+  assert_safe("read_ticket_type_by_sui_address", input, postgres.read_ticket_type_by_sui_address(s()).await);
+  assert_safe("read_account_trades", input, postgres.read_account_trades(s(), Some(s()), 0, 10).await);
+  assert_safe("read_event_trades", input, postgres.read_event_trades(s(), 0, 10).await);
+  assert_safe("read_waitlist_position", input, postgres.read_waitlist_position(s(), 0, s()).await);
+  assert_safe("read_account_waitlist_positions", input, postgres.read_account_waitlist_positions(s()).await);
+  assert_safe("read_waitlist_length", input, postgres.read_waitlist_length(s(), 0).await);
+}
+
+async fn write_everything(postgres: &mut PostgresConnection, input: &str) {
+  let s = || input.to_string();
+
+  assert_safe("update_delete_request_at", input, postgres.update_delete_request_at(s(), None).await);
+  assert_safe("update_webbundle_uploaded", input, postgres.update_webbundle_uploaded(s(), s()).await);
+  assert_safe(
+    "commit_event",
+    input,
+    postgres.commit_event(s(), s(), s(), s(), s(), s(), vec![s()]).await,
+  );
+  assert_safe(
+    "upsert_claimed_nfts",
+    input,
+    postgres.upsert_claimed_nfts(vec![NewTicketTypeNft {
+      ticket_type_nft_sui_address: s(),
+      cnt_sui_address: s(),
+      account_id: s(),
+      ref_name: s(),
+      event_id: s(),
+      ticket_type_index: 0,
+    }]).await,
+  );
+  assert_safe("set_cnt_sui_address", input, postgres.set_cnt_sui_address(s(), 0, s()).await);
+  assert_safe("update_attended", input, postgres.update_attended(s()).await);
+  assert_safe("check_in_cnt", input, postgres.check_in_cnt(s(), s(), s(), Some(s())).await);
+  assert_safe("create_cnt_transfer", input, postgres.create_cnt_transfer(s(), s(), Some(s()), Some(s())).await);
+  assert_safe("cancel_listing", input, postgres.cancel_listing(s(), s()).await);
+  assert_safe("cancel_offer", input, postgres.cancel_offer(s(), s()).await);
+  assert_safe("fill_listing", input, postgres.fill_listing(s(), s(), s()).await);
+  assert_safe("fill_offer", input, postgres.fill_offer(s(), s(), s()).await);
+  assert_safe("match_listing", input, postgres.match_listing(s()).await);
+  assert_safe("match_offer", input, postgres.match_offer(s()).await);
+  assert_safe("update_listing_draft", input, postgres.update_listing_draft(&s(), &s()).await);
+  assert_safe("update_offer_draft", input, postgres.update_offer_draft(&s(), &s()).await);
+  assert_safe("update_event_status", input, postgres.update_event_status(s(), EventStatus::Published).await);
+  assert_safe("cancel_event", input, postgres.cancel_event(s()).await);
+  assert_safe("allocate_seats", input, postgres.allocate_seats(s(), 0, s(), 1).await);
+  assert_safe(
+    "allocate_seats_with_promo_code",
+    input,
+    postgres.allocate_seats_with_promo_code("e1".into(), 0, "bob".into(), 1, s()).await,
+  );
+  assert_safe("delete_promo_code", input, postgres.delete_promo_code(s(), s()).await);
+  assert_safe("set_purchase_limit", input, postgres.set_purchase_limit(s(), None, Some(1)).await);
+  assert_safe("request_refund", input, postgres.request_refund(s(), s()).await);
+  assert_safe("delete_resale_policy", input, postgres.delete_resale_policy(s()).await);
+  assert_safe("hold_seats", input, postgres.hold_seats(s(), 0, s(), vec![5], 60, Some(s())).await);
+  assert_safe("release_seat_holds", input, postgres.release_seat_holds(s(), s(), vec![5]).await);
+  assert_safe("convert_seat_holds", input, postgres.convert_seat_holds(s(), s(), vec![5], Some(s())).await);
+  assert_safe("update_stripe_account_status", input, postgres.update_stripe_account_status(s()).await);
+  assert_safe("join_waitlist", input, postgres.join_waitlist(s(), 0, s()).await);
+  assert_safe("leave_waitlist", input, postgres.leave_waitlist(s(), 0, s()).await);
+  assert_safe("offer_waitlist_seat", input, postgres.offer_waitlist_seat(s(), 0, 5, 60).await);
+  assert_safe("delete_account", input, postgres.delete_account(s()).await);
+}
+
+#[tokio::test]
+async fn hostile_inputs_are_never_sql() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  for input in HOSTILE {
+    read_everything(&mut postgres, input).await;
+    write_everything(&mut postgres, input).await;
+  }
+
+  // Nothing was dropped or matched by a tautology
+  assert_eq!(postgres.read_event("e1".into()).await.unwrap().name, "Rock'n Roll night");
+  assert_eq!(postgres.read_account_by_id("alice".into()).await.unwrap().name.as_deref(), Some("Alice"));
+
+  for input in ["' OR '1'='1", "') OR 1=1 --", "%%", "__"] {
+    let filters = EventFilters {name: Some(input.to_string()), ..Default::default()};
+    assert!(postgres.read_filtered_events(filters, 0, 10).await.unwrap().is_empty(), "{input:?} matched events");
+    assert!(postgres.read_listings_for_account(None, input.to_string(), 0, 10).await.unwrap().is_empty());
+    assert!(postgres.read_user_cnts(input.to_string(), None, 0, 10).await.unwrap().is_empty());
+  }
+
+  // Wildcards in a name search only match themselves
+  for (input, event_id) in [("%", "e2"), ("_", "e3"), ("Rock'n", "e1")] {
+    let filters = EventFilters {name: Some(input.to_string()), ..Default::default()};
+    let found = postgres.read_filtered_events(filters, 0, 10).await.unwrap();
+    assert_eq!(found.iter().map(|event| event.event_id.as_str()).collect::<Vec<_>>(), [event_id], "{input:?}");
+  }
+}
+
+#[tokio::test]
+async fn hostile_values_round_trip() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  let now = Utc::now().naive_utc();
+
+  for (i, input) in HOSTILE.iter().enumerate() {
+    let uid = format!("{input}#{i}");
+    let event_id = format!("{input}@{i}");
+
+    postgres.upsert_account(Account {
+      uid: uid.clone(),
+      dapp_share: format!("share {uid}"),
+      pubkey: format!("pubkey {uid}"),
+      name: Some(input.to_string()),
+      email: Some(format!("{uid}@x.io")),
+      ..Default::default()
+    }).await.unwrap();
+
+    postgres.upsert_canva_account(CanvaAccount {
+      canva_uid: uid.clone(),
+      account_id: uid.clone(),
+      ..Default::default()
+    }).await.unwrap();
+
+    postgres.upsert_ticket_design(CanvaDesign {
+      design_id: uid.clone(),
+      canva_uid: uid.clone(),
+      url: input.to_string(),
+      name: input.to_string(),
+      file_type: input.to_string(),
+      ..Default::default()
+    }).await.unwrap();
+
+    postgres.upsert_stripe_account(StripeAccount {
+      stripe_uid: uid.clone(),
+      account_id: uid.clone(),
+      account_link: Some(input.to_string()),
+      ..Default::default()
+    }).await.unwrap();
+
+    postgres.upsert_stripe_customer(StripeCustomer {
+      customer_uid: uid.clone(),
+      account_id: uid.clone(),
+      ..Default::default()
+    }).await.unwrap();
+
+    postgres.upsert_event(
+      Event {
+        event_id: event_id.clone(),
+        account_id: uid.clone(),
+        name: format!("{input} live"),
+        description: input.to_string(),
+        venue: input.to_string(),
+        start_date: now + Duration::days(10),
+        end_date: now + Duration::days(11),
+        ..Default::default()
+      },
+      vec![SeatRange::new(event_id.clone(), 0, 0, 10)],
+      vec![NewTicketType {
+        event_id: event_id.clone(),
+        ticket_type_index: 0,
+        ticket_type_name: input.to_string(),
+        n_tickets: 10,
+        sale_start_ts: now - Duration::days(1),
+        sale_end_ts: now + Duration::days(9),
+        sale_type: SaleType::FixedPrice {price: 1000},
+      }],
+      vec![NewNftDetail {
+        nft_name: input.to_string(),
+        nft_description: input.to_string(),
+        content_type: "image/png".to_string(),
+        arweave_tx_id: format!("ar {uid}"),
+      }],
+      NewEventNftDetail {ref_name: format!("event {uid}"), event_id: event_id.clone(), nft_details_id: format!("ar {uid}")},
+      vec![NewTicketTypeNftDetail {
+        ref_name: format!("ticket type {uid}"),
+        event_id: event_id.clone(),
+        ticket_type_index: 0,
+        nft_details_id: format!("ar {uid}"),
+      }],
+    ).await.unwrap();
+
+    postgres.commit_event(
+      event_id.clone(),
+      format!("event {uid}"),
+      format!("organizer cap {uid}"),
+      format!("operator cap {uid}"),
+      format!("event nft {uid}"),
+      format!("bitmap {uid}"),
+      vec![format!("ticket type {uid}")],
+    ).await.unwrap();
+    postgres.update_event_status(event_id.clone(), EventStatus::OnSale).await.unwrap();
+
+    let cnts = postgres.allocate_seats(event_id.clone(), 0, uid.clone(), 1).await.unwrap();
+    let cnt_address = format!("cnt {uid}");
+    postgres.set_cnt_sui_address(event_id.clone(), cnts[0].seat_index, cnt_address.clone()).await.unwrap();
+
+    postgres.upsert_listing(NewListing {
+      listing_id: &uid,
+      account_id: &uid,
+      event_id: &event_id,
+      listing_sui_address: Some(&uid),
+      cnt_sui_address: &cnt_address,
+      ask_price: 1500,
+      is_open: true,
+      draft: false,
+      expires_at: None,
+    }).await.unwrap();
+
+    postgres.upsert_offer(NewOffer {
+      offer_id: &uid,
+      offer_sui_address: Some(&uid),
+      account_id: &uid,
+      event_id: &event_id,
+      ticket_type_index: 0,
+      bid_price: 500,
+      is_open: true,
+      draft: false,
+      expires_at: None,
+    }).await.unwrap();
+
+    postgres.create_promo_code(NewPromoCode {
+      event_id: event_id.clone(),
+      code: format!("code {i}"),
+      kind: PromoCodeKind::Fixed,
+      value: 100,
+      ticket_type_index: None,
+      max_redemptions: None,
+      valid_from: None,
+      valid_until: None,
+    }).await.unwrap();
+
+    // Every value comes back exactly as it was stored
+    assert_eq!(postgres.read_account_by_id(uid.clone()).await.unwrap().name.as_deref(), Some(*input));
+    assert_eq!(postgres.read_account_by_canva_id(uid.clone()).await.unwrap().uid, uid);
+    assert_eq!(postgres.read_canva_designs(uid.clone()).await.unwrap()[0].name, *input);
+    assert_eq!(postgres.read_stripe_account(uid.clone()).await.unwrap().account_link.as_deref(), Some(*input));
+    assert_eq!(postgres.read_event(event_id.clone()).await.unwrap().venue, *input);
+    assert_eq!(postgres.read_ticket_type(event_id.clone(), 0).await.unwrap().ticket_type_name, *input);
+    assert_eq!(postgres.read_listing(uid.clone()).await.unwrap().cnt_sui_address, cnt_address);
+    assert_eq!(postgres.read_offer(uid.clone()).await.unwrap().account_id, uid);
+    assert_eq!(postgres.read_user_cnts(uid.clone(), Some(event_id.clone()), 0, 10).await.unwrap().len(), 1);
+    assert_eq!(postgres.read_listings_for_account(Some(event_id.clone()), uid.clone(), 0, 10).await.unwrap().len(), 1);
+    assert_eq!(postgres.read_offers_for_account(Some(event_id.clone()), uid.clone(), 0, 10).await.unwrap().len(), 1);
+    assert_eq!(postgres.read_event_promo_codes(event_id.clone()).await.unwrap().len(), 1);
+
+    let account_events = postgres.read_account_events(uid.clone(), None, None, None, None, Some(input.to_string()), 0, 10)
+    .await
+    .unwrap();
+    assert_eq!(account_events.len(), 1);
+    assert_eq!(account_events[0].event_id, event_id);
+
+    let filters = EventFilters {name: Some(format!("{input} live")), ..Default::default()};
+    let found = postgres.read_filtered_events(filters, 0, 100).await.unwrap();
+    assert!(found.iter().any(|event| event.event_id == event_id), "{input:?} not found by name");
+    assert!(found.iter().all(|event| event.name.contains(&format!("{input} live"))), "{input:?} matched other events");
+  }
+}
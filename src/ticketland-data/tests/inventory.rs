@@ -0,0 +1,86 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::error::Error;
+
+fn assert_error<T>(result: eyre::Result<T>, expected: Error) {
+  let Err(error) = result else {panic!("expected {expected}")};
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+#[tokio::test]
+async fn seats_are_allocated_in_order_within_the_sale_window() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // c1 and c2 are seats 0 and 1 of GA
+  let new_cnts = postgres.allocate_seats("e1".into(), 0, "bob".into(), 2).await.unwrap();
+  assert_eq!(new_cnts.iter().map(|cnt| (cnt.seat_index, cnt.draft)).collect::<Vec<_>>(), [(2, true), (3, true)]);
+  assert_eq!(new_cnts[0].account_id, "bob");
+  assert_eq!(postgres.read_available_tickets("e1".into(), 0).await.unwrap(), 6);
+
+  postgres.borrow_mut()
+  .batch_execute("UPDATE ticket_types SET sale_start_ts = now() + interval '1 day' WHERE event_id = 'e1' AND ticket_type_index = 0")
+  .await
+  .unwrap();
+  assert_error(postgres.allocate_seats("e1".into(), 0, "bob".into(), 1).await, Error::SaleNotStarted);
+
+  postgres.borrow_mut()
+  .batch_execute("
+    UPDATE ticket_types SET sale_start_ts = now() - interval '1 day', sale_end_ts = now() - interval '1 minute'
+    WHERE event_id = 'e1' AND ticket_type_index = 0
+  ")
+  .await
+  .unwrap();
+  assert_error(postgres.allocate_seats("e1".into(), 0, "bob".into(), 1).await, Error::SaleEnded);
+
+  assert_eq!(postgres.read_available_tickets("e1".into(), 0).await.unwrap(), 6);
+}
+
+#[tokio::test]
+async fn allocations_never_go_over_n_tickets() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // 8 of the 10 GA tickets are left
+  assert_error(postgres.allocate_seats("e1".into(), 0, "bob".into(), 9).await, Error::SoldOut);
+  assert_eq!(postgres.read_available_tickets("e1".into(), 0).await.unwrap(), 8);
+
+  // Seats held by others can not be allocated either
+  postgres.hold_seats("e1".into(), 0, "alice".into(), vec![9], 60, None).await.unwrap();
+  assert_error(postgres.allocate_seats("e1".into(), 0, "bob".into(), 8).await, Error::SoldOut);
+
+  let new_cnts = postgres.allocate_seats("e1".into(), 0, "bob".into(), 7).await.unwrap();
+  assert_eq!(new_cnts.last().unwrap().seat_index, 8);
+  assert_error(postgres.allocate_seats("e1".into(), 0, "org".into(), 1).await, Error::SoldOut);
+}
+
+#[tokio::test]
+async fn concurrent_buyers_can_not_both_get_the_last_seat() {
+  let Some(db) = common::setup().await else {return};
+  let mut first = db.connection().await;
+  let mut second = db.connection().await;
+
+  // Only seat 21 of VIP is left
+  first.borrow_mut().batch_execute("
+    INSERT INTO cnts (cnt_sui_address, event_id, account_id, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+    ('c5', 'e1', 'org', 1, '11', 11, false, false),
+    ('c6', 'e1', 'org', 1, '20', 20, false, false);
+  ")
+  .await
+  .unwrap();
+
+  let (alice, bob) = tokio::join!(
+    first.allocate_seats("e1".into(), 1, "alice".into(), 1),
+    second.allocate_seats("e1".into(), 1, "bob".into(), 1),
+  );
+
+  let (winner, loser) = match (alice, bob) {
+    (Ok(winner), loser) | (loser, Ok(winner)) => (winner, loser),
+    (Err(alice), Err(bob)) => panic!("neither buyer got the seat: {alice}, {bob}"),
+  };
+
+  assert_eq!(winner.iter().map(|cnt| cnt.seat_index).collect::<Vec<_>>(), [21]);
+  assert_error(loser, Error::SoldOut);
+  assert_eq!(first.read_available_tickets("e1".into(), 1).await.unwrap(), 0);
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::error::Error;
+
+fn assert_error<T: std::fmt::Debug>(result: eyre::Result<T>, expected: Error) {
+  let error = result.unwrap_err();
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+#[tokio::test]
+async fn listings_are_filled_once() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let trade = postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await.unwrap();
+  assert_eq!((trade.seller_account_id.as_str(), trade.buyer_account_id.as_str()), ("alice", "bob"));
+
+  assert_error(postgres.fill_listing("l1".into(), "c1".into(), "org".into()).await, Error::ListingNotAvailable);
+  assert_error(postgres.fill_listing("missing".into(), "c1".into(), "org".into()).await, Error::ListingNotAvailable);
+
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "bob");
+  assert_eq!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn stale_listings_cannot_be_filled() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // l1 is for c1, not for another CNT of the buyer
+  assert_error(postgres.fill_listing("l1".into(), "c3".into(), "bob".into()).await, Error::ListingNotAvailable);
+
+  // c1 was transferred after it was listed
+  postgres.borrow_mut().batch_execute("UPDATE cnts SET account_id = 'org' WHERE cnt_sui_address = 'c1'").await.unwrap();
+  assert_error(postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await, Error::ListingNotAvailable);
+
+  // c3 is a CNT of e1
+  postgres.borrow_mut().batch_execute("UPDATE listings SET event_id = 'e2' WHERE listing_id = 'l2'").await.unwrap();
+  assert_error(postgres.fill_listing("l2".into(), "c3".into(), "alice".into()).await, Error::ListingNotAvailable);
+
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "org");
+  assert_eq!(postgres.read_cnt("c3".into()).await.unwrap()[0].account_id, "bob");
+  assert!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn offers_are_filled_once() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let trade = postgres.fill_offer("o1".into(), "c1".into(), "bob".into()).await.unwrap();
+  assert_eq!((trade.seller_account_id.as_str(), trade.buyer_account_id.as_str()), ("alice", "bob"));
+
+  assert_error(postgres.fill_offer("o1".into(), "c2".into(), "bob".into()).await, Error::OfferNotAvailable);
+
+  // Alice's listing of c1 was closed with the fill
+  let listing = postgres.read_listing("l1".into()).await.unwrap();
+  assert!(!listing.is_open && listing.closed_at.is_some());
+  assert_error(postgres.fill_listing("l1".into(), "c1".into(), "org".into()).await, Error::ListingNotAvailable);
+
+  // o3 is for GA tickets of e1, c3 is a VIP ticket and c4 one of e2
+  assert_error(postgres.fill_offer("o3".into(), "c3".into(), "org".into()).await, Error::CntNotForOffer);
+  assert_error(postgres.fill_offer("o3".into(), "c4".into(), "org".into()).await, Error::CntNotForOffer);
+
+  postgres.cancel_offer("org".into(), "o3".into()).await.unwrap();
+  assert_error(postgres.fill_offer("o3".into(), "c2".into(), "org".into()).await, Error::OfferNotAvailable);
+
+  assert_eq!(postgres.read_cnt("c2".into()).await.unwrap()[0].account_id, "alice");
+  assert_eq!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn expired_orders_cannot_be_filled() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.borrow_mut()
+  .batch_execute("UPDATE listings SET expires_at = now() - interval '1 minute' WHERE listing_id = 'l1';
+    UPDATE offers SET expires_at = now() - interval '1 minute' WHERE offer_id = 'o1';")
+  .await
+  .unwrap();
+
+  assert_error(postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await, Error::ListingNotAvailable);
+  assert_error(postgres.fill_offer("o1".into(), "c1".into(), "bob".into()).await, Error::OfferNotAvailable);
+
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "alice");
+  assert!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn listings_are_matched_with_the_best_crossing_offer() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // l1 asks 6000 and the seeded bids of at most 5800 do not cross it
+  assert!(postgres.match_listing("l1".into()).await.unwrap().is_none());
+  assert!(postgres.read_listing("l1".into()).await.unwrap().is_open);
+
+  postgres.borrow_mut().batch_execute("
+    INSERT INTO offers (offer_id, offer_sui_address, account_id, event_id, ticket_type_index, created_at, bid_price, is_open, draft) VALUES
+    ('o4', 'os4', 'org', 'e1', 0, now() - interval '1 hour', 6200, true, false),
+    ('o5', 'os5', 'bob', 'e1', 0, now() - interval '5 minutes', 6500, true, false),
+    ('o6', 'os6', 'org', 'e1', 0, now(), 6500, true, false),
+    ('o7', 'os7', 'alice', 'e1', 0, now(), 9000, true, false);
+  ")
+  .await
+  .unwrap();
+
+  // Alice's own bid is ignored, the highest bid wins over the oldest and the oldest wins on equal bids
+  let trade = postgres.match_listing("l1".into()).await.unwrap().unwrap();
+  assert_eq!(
+    (trade.offer_id.as_deref(), trade.seller_account_id.as_str(), trade.buyer_account_id.as_str(), trade.price),
+    (Some("o5"), "alice", "bob", 6500),
+  );
+
+  assert!(!postgres.read_offer("o5".into()).await.unwrap().is_open);
+  assert!(postgres.read_offer("o6".into()).await.unwrap().is_open);
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "bob");
+  assert!(postgres.match_listing("l1".into()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn offers_are_matched_with_the_best_crossing_listing() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // o3 bids 5800 and the seeded ask of 6000 does not cross it
+  assert!(postgres.match_offer("o3".into()).await.unwrap().is_none());
+  assert!(postgres.read_offer("o3".into()).await.unwrap().is_open);
+
+  postgres.borrow_mut().batch_execute("
+    INSERT INTO cnts (cnt_sui_address, event_id, account_id, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+    ('c5', 'e1', 'org', 0, '5', 5, false, false),
+    ('c6', 'e1', 'org', 0, '6', 6, false, false),
+    ('c7', 'e1', 'org', 0, '7', 7, false, false),
+    ('c8', 'e1', 'bob', 0, '8', 8, false, false);
+
+    INSERT INTO listings (listing_id, listing_sui_address, account_id, event_id, cnt_sui_address, created_at, ask_price, is_open, draft) VALUES
+    ('l3', 'ls3', 'org', 'e1', 'c5', now() - interval '2 hours', 5450, true, false),
+    ('l4', 'ls4', 'org', 'e1', 'c6', now(), 5400, true, false),
+    ('l5', 'ls5', 'org', 'e1', 'c7', now() - interval '1 hour', 5400, true, false),
+    ('l6', 'ls6', 'bob', 'e1', 'c8', now(), 5000, true, false);
+  ")
+  .await
+  .unwrap();
+
+  // Bob's own ask is ignored, the lowest ask wins over the oldest and the oldest wins on equal asks
+  let trade = postgres.match_offer("o1".into()).await.unwrap().unwrap();
+  assert_eq!(
+    (trade.listing_id.as_deref(), trade.seller_account_id.as_str(), trade.buyer_account_id.as_str(), trade.price),
+    (Some("l5"), "org", "bob", 5400),
+  );
+
+  assert!(!postgres.read_listing("l5".into()).await.unwrap().is_open);
+  assert!(postgres.read_listing("l4".into()).await.unwrap().is_open);
+  assert_eq!(postgres.read_cnt("c7".into()).await.unwrap()[0].account_id, "bob");
+  assert!(postgres.match_offer("o1".into()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn orders_filled_directly_are_not_matched() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // o3 crosses both l1 and l3
+  postgres.borrow_mut().batch_execute("
+    UPDATE offers SET bid_price = 6000 WHERE offer_id = 'o3';
+
+    INSERT INTO cnts (cnt_sui_address, event_id, account_id, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+    ('c5', 'e1', 'alice', 0, '5', 5, false, false);
+
+    INSERT INTO listings (listing_id, listing_sui_address, account_id, event_id, cnt_sui_address, created_at, ask_price, is_open, draft) VALUES
+    ('l3', 'ls3', 'alice', 'e1', 'c5', now(), 5900, true, false);
+  ")
+  .await
+  .unwrap();
+
+  postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await.unwrap();
+  assert!(postgres.match_listing("l1".into()).await.unwrap().is_none());
+
+  postgres.fill_offer("o3".into(), "c1".into(), "org".into()).await.unwrap();
+  assert!(postgres.match_offer("o3".into()).await.unwrap().is_none());
+
+  assert!(postgres.read_listing("l3".into()).await.unwrap().is_open);
+  assert_eq!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().len(), 2);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::models::order_book::{PriceLevel, TicketTypeOrderBook};
+
+/// Orders next to the seeded ones that must or must not show up in the book of e1
+const ORDERS: &str = "
+  INSERT INTO offers (offer_id, offer_sui_address, account_id, event_id, ticket_type_index, created_at, bid_price, is_open, draft, expires_at) VALUES
+  ('o4', 'os4', 'alice', 'e1', 0, now(), 5800, true, false, now() + interval '1 day'),
+  ('o5', NULL, 'alice', 'e1', 0, now(), 9000, true, true, NULL),
+  ('o6', 'os6', 'alice', 'e1', 0, now(), 9000, true, false, now() - interval '1 minute'),
+  ('o7', 'os7', 'alice', 'e1', 0, now(), 9000, false, false, NULL),
+  ('o8', 'os8', 'bob', 'e2', 0, now(), 9000, true, false, NULL);
+
+  INSERT INTO listings (listing_id, listing_sui_address, account_id, event_id, cnt_sui_address, created_at, ask_price, is_open, draft, expires_at) VALUES
+  ('l3', 'ls3', 'alice', 'e1', 'c2', now(), 1000, true, false, now() - interval '1 minute');
+";
+
+fn levels(levels: &[PriceLevel]) -> Vec<(i64, i64)> {
+  levels.iter().map(|level| (level.price, level.quantity)).collect()
+}
+
+fn ticket_type(book: &[TicketTypeOrderBook], ticket_type_index: i16) -> &TicketTypeOrderBook {
+  book.iter().find(|book| book.ticket_type_index == ticket_type_index).unwrap()
+}
+
+#[tokio::test]
+async fn aggregates_open_orders_per_ticket_type_and_price() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.borrow_mut().batch_execute(ORDERS).await.unwrap();
+
+  let book = postgres.read_order_book("e1".into(), 10).await.unwrap();
+  assert_eq!(book.event_id, "e1");
+  assert_eq!(book.ticket_types.iter().map(|book| book.ticket_type_index).collect::<Vec<_>>(), [0, 1]);
+
+  let ga = ticket_type(&book.ticket_types, 0);
+  assert_eq!(levels(&ga.bids), [(5800, 2), (5500, 1)]);
+  assert_eq!(levels(&ga.asks), [(6000, 1)]);
+  assert_eq!((ga.best_bid, ga.best_ask, ga.spread), (Some(5800), Some(6000), Some(200)));
+  assert_eq!(ga.last_price, None);
+
+  let vip = ticket_type(&book.ticket_types, 1);
+  assert_eq!(levels(&vip.bids), [(21000, 1)]);
+  assert_eq!(levels(&vip.asks), [(25000, 1)]);
+  assert_eq!(vip.spread, Some(4000));
+
+  let book = postgres.read_order_book("e1".into(), 1).await.unwrap();
+  assert_eq!(levels(&ticket_type(&book.ticket_types, 0).bids), [(5800, 2)]);
+
+  let book = postgres.read_order_book("e3".into(), 10).await.unwrap();
+  assert!(book.ticket_types.is_empty());
+}
+
+#[tokio::test]
+async fn fills_move_orders_into_the_last_trade() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await.unwrap();
+
+  let book = postgres.read_order_book("e1".into(), 10).await.unwrap();
+  let ga = ticket_type(&book.ticket_types, 0);
+  assert!(ga.asks.is_empty());
+  assert_eq!((ga.best_bid, ga.best_ask, ga.spread), (Some(5800), None, None));
+  assert_eq!(ga.last_price, Some(6000));
+  assert!(ga.last_traded_at.is_some());
+
+  postgres.fill_offer("o3".into(), "c1".into(), "org".into()).await.unwrap();
+
+  let book = postgres.read_order_book("e1".into(), 10).await.unwrap();
+  let ga = ticket_type(&book.ticket_types, 0);
+  assert_eq!(levels(&ga.bids), [(5500, 1)]);
+  assert_eq!(ga.last_price, Some(5800));
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::connection::PostgresConnection;
+
+/// CNTs of e3 that share a creation time, have no sui address yet or no creation time at all
+const CNTS_WITHOUT_ADDRESS: &str = "
+  INSERT INTO cnts (cnt_sui_address, event_id, account_id, created_at, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+  (NULL, 'e3', 'alice', '2023-08-01 10:00:00+00', 0, '4', 4, false, false),
+  (NULL, 'e3', 'alice', '2023-08-01 10:00:00+00', 0, '2', 2, false, false),
+  ('c5', 'e3', 'alice', '2023-08-01 10:00:00+00', 0, '3', 3, false, false),
+  (NULL, 'e3', 'alice', NULL, 0, '7', 7, false, false);
+
+  INSERT INTO listings (listing_id, listing_sui_address, account_id, event_id, cnt_sui_address, created_at, ask_price, is_open, draft) VALUES
+  ('l3', 'ls3', 'alice', 'e1', 'c2', NULL, 7000, true, false);
+
+  INSERT INTO offers (offer_id, offer_sui_address, account_id, event_id, ticket_type_index, created_at, bid_price, is_open, draft) VALUES
+  ('o4', 'os4', 'alice', 'e1', 0, NULL, 4000, true, false);
+";
+
+async fn setup() -> Option<(common::TestDb, PostgresConnection)> {
+  let db = common::setup().await?;
+  let mut postgres = db.connection().await;
+  postgres.borrow_mut().batch_execute(CNTS_WITHOUT_ADDRESS).await.unwrap();
+
+  Some((db, postgres))
+}
+
+#[tokio::test]
+async fn cnt_pages_include_cnts_without_address() {
+  let Some((_db, mut postgres)) = setup().await else {return};
+
+  for limit in 1..=3 {
+    let mut seats = vec![];
+    let mut cursor = None;
+
+    loop {
+      let (cnts, next) = postgres.read_cnts_for_event_after("e3".into(), cursor, limit).await.unwrap();
+      seats.extend(cnts.iter().map(|cnt| cnt.seat_index));
+
+      let Some(next) = next else {break};
+      cursor = Some(next);
+    }
+
+    assert_eq!(seats, [7, 2, 3, 4], "limit {limit}");
+  }
+
+  let mut seats = vec![];
+  let mut cursor = None;
+
+  loop {
+    let (cnts, next) = postgres.read_user_cnts_after("alice".into(), Some("e3".into()), cursor, 1).await.unwrap();
+    seats.extend(cnts.iter().map(|cnt| cnt.seat_index));
+
+    let Some(next) = next else {break};
+    cursor = Some(next);
+  }
+
+  assert_eq!(seats, [7, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn listing_and_offer_pages_include_rows_without_created_at() {
+  let Some((_db, mut postgres)) = setup().await else {return};
+
+  let mut ids = vec![];
+  let mut cursor = None;
+
+  loop {
+    let (listings, next) = postgres.read_listings_for_event_after("e1".into(), cursor, 1).await.unwrap();
+    ids.extend(listings.into_iter().map(|listing| listing.listing_id));
+
+    let Some(next) = next else {break};
+    cursor = Some(next);
+  }
+
+  assert_eq!(ids, ["l2", "l1", "l3"]);
+
+  let mut ids = vec![];
+  let mut cursor = None;
+
+  loop {
+    let (offers, next) = postgres.read_offers_for_event_after("e1".into(), cursor, 1).await.unwrap();
+    ids.extend(offers.into_iter().map(|offer| offer.offer_id));
+
+    let Some(next) = next else {break};
+    cursor = Some(next);
+  }
+
+  assert_eq!(ids, ["o3", "o2", "o1", "o4"]);
+}
+
+#[tokio::test]
+async fn cnt_cursor_must_carry_the_seat() {
+  let Some((_db, mut postgres)) = setup().await else {return};
+
+  let (_, next) = postgres.read_listings_for_event_after("e1".into(), None, 1).await.unwrap();
+  assert!(postgres.read_cnts_for_event_after("e1".into(), next, 1).await.is_err());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::time::Duration;
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  error::Error,
+  models::promo_code::{NewPromoCode, PromoCodeKind},
+};
+
+fn assert_error<T>(result: eyre::Result<T>, expected: Error) {
+  let Err(error) = result else {panic!("expected {expected}")};
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+fn promo_code(code: &str, kind: PromoCodeKind, value: i64, ticket_type_index: i16) -> NewPromoCode {
+  NewPromoCode {
+    event_id: "e1".into(),
+    code: code.into(),
+    kind,
+    value,
+    ticket_type_index: Some(ticket_type_index),
+    max_redemptions: None,
+    valid_from: None,
+    valid_until: None,
+  }
+}
+
+#[tokio::test]
+async fn hidden_ticket_types_are_held_and_converted_with_an_access_code() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.create_promo_code(promo_code("vip-pass", PromoCodeKind::Access, 0, 1)).await.unwrap();
+
+  let hold = postgres.hold_seats("e1".into(), 1, "alice".into(), vec![11], 60, None).await;
+  assert_error(hold, Error::TicketTypeLocked);
+  postgres.hold_seats("e1".into(), 1, "alice".into(), vec![11], 60, Some("vip-pass".into())).await.unwrap();
+
+  let convert = postgres.convert_seat_holds("e1".into(), "alice".into(), vec![11], None).await;
+  assert_error(convert, Error::TicketTypeLocked);
+
+  let new_cnts = postgres.convert_seat_holds("e1".into(), "alice".into(), vec![11], Some("vip-pass".into())).await.unwrap();
+  assert_eq!(new_cnts[0].purchase_price, Some(20000));
+  assert_eq!(postgres.read_event_promo_codes("e1".into()).await.unwrap()[0].redemptions, 1);
+}
+
+#[tokio::test]
+async fn converted_holds_are_priced_with_the_code() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.create_promo_code(promo_code("early", PromoCodeKind::Percentage, 2500, 0)).await.unwrap();
+
+  postgres.hold_seats("e1".into(), 0, "bob".into(), vec![2, 3], 60, Some("early".into())).await.unwrap();
+  let new_cnts = postgres.convert_seat_holds("e1".into(), "bob".into(), vec![2, 3], Some("EARLY".into())).await.unwrap();
+
+  assert_eq!(new_cnts.iter().map(|cnt| cnt.purchase_price).collect::<Vec<_>>(), [Some(3750), Some(3750)]);
+  assert_eq!(postgres.read_event_promo_codes("e1".into()).await.unwrap()[0].redemptions, 2);
+
+  // The code does not apply to VIP
+  postgres.hold_seats("e1".into(), 1, "bob".into(), vec![11], 60, None).await.unwrap();
+  let convert = postgres.convert_seat_holds("e1".into(), "bob".into(), vec![11], Some("early".into())).await;
+  assert_error(convert, Error::PromoCodeNotFound);
+}
+
+#[tokio::test]
+async fn waitlist_seats_of_hidden_ticket_types_are_not_offered() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.borrow_mut().batch_execute("
+    INSERT INTO cnts (cnt_sui_address, event_id, account_id, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+    ('c5', 'e1', 'org', 1, '11', 11, false, false),
+    ('c6', 'e1', 'org', 1, '20', 20, false, false),
+    ('c7', 'e1', 'org', 1, '21', 21, false, false);
+  ")
+  .await
+  .unwrap();
+
+  postgres.join_waitlist("e1".into(), 1, "alice".into()).await.unwrap();
+  postgres.borrow_mut().batch_execute("DELETE FROM cnts WHERE cnt_sui_address = 'c7'").await.unwrap();
+  postgres.create_promo_code(promo_code("vip-pass", PromoCodeKind::Access, 0, 1)).await.unwrap();
+
+  assert_error(postgres.offer_waitlist_seat("e1".into(), 1, 21, 60).await, Error::TicketTypeLocked);
+}
+
+#[tokio::test]
+async fn concurrently_created_duplicate_codes_are_rejected() {
+  let Some(db) = common::setup().await else {return};
+  let mut first = db.connection().await;
+  let mut second = db.connection().await;
+
+  // The first code is not committed yet when the second one is checked
+  first.borrow_mut()
+  .batch_execute("BEGIN; INSERT INTO promo_codes (event_id, code, kind, value) VALUES ('e1', 'SUMMER', 'fixed', 100);")
+  .await
+  .unwrap();
+
+  let commit = async {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    first.borrow_mut().batch_execute("COMMIT").await.unwrap();
+  };
+  let create = second.create_promo_code(promo_code("summer", PromoCodeKind::Fixed, 200, 0));
+  let (_, result) = tokio::join!(commit, create);
+
+  assert_error(result, Error::InvalidPromoCode("code already exists".to_string()));
+}
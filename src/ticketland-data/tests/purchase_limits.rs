@@ -0,0 +1,32 @@
+mod common;
+
+use ticketland_data::error::Error;
+
+fn assert_error<T>(result: eyre::Result<T>, expected: Error) {
+  let Err(error) = result else {panic!("expected {expected}")};
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+#[tokio::test]
+async fn limits_count_owned_and_held_tickets() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.set_purchase_limit("e1".into(), None, Some(3)).await.unwrap();
+
+  // Alice owns c1 and c2
+  assert_eq!(postgres.read_purchase_allowance("e1".into(), 0, "alice".into()).await.unwrap(), Some(1));
+  postgres.hold_seats("e1".into(), 0, "alice".into(), vec![2], 60, None).await.unwrap();
+
+  assert_error(postgres.allocate_seats("e1".into(), 0, "alice".into(), 1).await, Error::EventPurchaseLimitExceeded(3));
+  assert_eq!(postgres.allocate_seats("e1".into(), 0, "bob".into(), 2).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn unknown_accounts_are_not_found() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.set_purchase_limit("e1".into(), Some(0), Some(4)).await.unwrap();
+
+  assert_error(postgres.allocate_seats("e1".into(), 0, "ghost".into(), 1).await, Error::AccountNotFound);
+  assert_error(postgres.hold_seats("e1".into(), 0, "ghost".into(), vec![2], 60, None).await, Error::AccountNotFound);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  connection::PostgresConnection,
+  error::Error,
+  models::{listing::NewListing, refund_request::{RefundRequest, RefundStatus}},
+};
+
+fn assert_error<T: std::fmt::Debug>(result: eyre::Result<T>, expected: Error) {
+  let error = result.unwrap_err();
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+/// Bob asks for a refund of c3, the VIP CNT he listed as l2
+async fn request_c3_refund(postgres: &mut PostgresConnection) -> RefundRequest {
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+  postgres.request_refund("c3".into(), "bob".into()).await.unwrap()
+}
+
+#[tokio::test]
+async fn open_refunds_block_resale_and_transfer() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  let refund = request_c3_refund(&mut postgres).await;
+
+  let listing = NewListing {
+    listing_id: "l3",
+    account_id: "bob",
+    event_id: "e1",
+    listing_sui_address: None,
+    cnt_sui_address: "c3",
+    ask_price: 22000,
+    is_open: true,
+    draft: true,
+    expires_at: None,
+  };
+  assert_error(postgres.upsert_listing(listing).await, Error::CntRefundPending);
+  assert_error(postgres.create_cnt_transfer("c3".into(), "bob".into(), Some("alice".into()), None).await, Error::CntRefundPending);
+  assert_error(postgres.fill_offer("o2".into(), "c3".into(), "bob".into()).await, Error::CntRefundPending);
+
+  // A listing left open by the marketplace can not be filled either
+  postgres.borrow_mut().batch_execute("UPDATE listings SET is_open = true WHERE listing_id = 'l2'").await.unwrap();
+  assert_error(postgres.fill_listing("l2".into(), "c3".into(), "alice".into()).await, Error::CntRefundPending);
+
+  // Nor matched against a crossing offer
+  postgres.borrow_mut().batch_execute("UPDATE listings SET ask_price = 20000 WHERE listing_id = 'l2'").await.unwrap();
+  assert_error(postgres.match_listing("l2".into()).await, Error::CntRefundPending);
+  assert_error(postgres.match_offer("o2".into()).await, Error::CntRefundPending);
+
+  // Approved refunds are still open
+  postgres.approve_refund(refund.refund_id).await.unwrap();
+  assert_error(postgres.fill_offer("o2".into(), "c3".into(), "bob".into()).await, Error::CntRefundPending);
+
+  assert_eq!(postgres.read_cnt("c3".into()).await.unwrap()[0].account_id, "bob");
+  assert!(postgres.read_event_trades("e1".into(), 0, 10).await.unwrap().is_empty());
+
+  // Once closed the CNT can change hands again
+  postgres.borrow_mut().batch_execute("UPDATE refund_requests SET status = 'rejected'").await.unwrap();
+  postgres.fill_listing("l2".into(), "c3".into(), "alice".into()).await.unwrap();
+}
+
+#[tokio::test]
+async fn refunds_of_cnts_that_changed_hands_are_not_completed() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  let refund = request_c3_refund(&mut postgres).await;
+  postgres.approve_refund(refund.refund_id).await.unwrap();
+
+  // e.g. synced from chain after a transfer outside of the marketplace
+  postgres.borrow_mut().batch_execute("UPDATE cnts SET account_id = 'alice' WHERE cnt_sui_address = 'c3'").await.unwrap();
+  assert_error(postgres.complete_refund(refund.refund_id).await, Error::CntResold);
+  assert_eq!(postgres.read_cnt("c3".into()).await.unwrap()[0].account_id, "alice");
+
+  postgres.borrow_mut().batch_execute("UPDATE cnts SET account_id = 'bob' WHERE cnt_sui_address = 'c3'").await.unwrap();
+  let refund = postgres.complete_refund(refund.refund_id).await.unwrap();
+  assert_eq!(refund.status, RefundStatus::Completed);
+  assert!(postgres.read_cnt("c3".into()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn the_history_of_a_resold_seat_starts_after_the_refund() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  let refund = request_c3_refund(&mut postgres).await;
+  postgres.approve_refund(refund.refund_id).await.unwrap();
+  postgres.complete_refund(refund.refund_id).await.unwrap();
+
+  // The seat of c3 is sold again as a draft CNT that gets its address later on
+  postgres.hold_seats("e1".into(), 1, "alice".into(), vec![10], 60, None).await.unwrap();
+  postgres.convert_seat_holds("e1".into(), "alice".into(), vec![10], None).await.unwrap();
+  postgres.borrow_mut()
+  .batch_execute("UPDATE cnts SET cnt_sui_address = 'c5' WHERE event_id = 'e1' AND seat_index = 10")
+  .await
+  .unwrap();
+
+  let history = postgres.read_cnt_history("c5".into()).await.unwrap();
+  let history = history.iter().map(|event| (event.kind.as_str(), event.to_account_id.as_str())).collect::<Vec<_>>();
+  assert_eq!(history, [("issued", "alice")]);
+
+  let history = postgres.read_cnt_history("c3".into()).await.unwrap();
+  let history = history.iter().map(|event| (event.kind.as_str(), event.to_account_id.as_str())).collect::<Vec<_>>();
+  assert_eq!(history, [("refunded", "org")]);
+}
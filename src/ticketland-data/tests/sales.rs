@@ -0,0 +1,103 @@
+mod common;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::models::resale_policy::ResalePolicy;
+
+/// 2030-01-01 00:00:00 UTC
+const DAY_START: u64 = 1_893_456_000;
+
+fn at(hour: u32, minute: u32) -> NaiveDateTime {
+  NaiveDate::from_ymd_opt(2030, 1, 1).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+}
+
+#[tokio::test]
+async fn primary_sales_are_counted_per_ticket_type_and_interval() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.borrow_mut().batch_execute("
+    UPDATE cnts SET created_at = '2030-01-01 10:15+00' WHERE cnt_sui_address = 'c1';
+    UPDATE cnts SET created_at = '2030-01-01 10:45+00' WHERE cnt_sui_address = 'c2';
+    UPDATE cnts SET created_at = '2030-01-01 11:30+00' WHERE cnt_sui_address = 'c3';
+  ")
+  .await
+  .unwrap();
+
+  let sales = postgres.read_primary_sales_count("e1".into(), 3600, DAY_START).await.unwrap();
+  let sales = sales.iter().map(|sales| (sales.timestamp, sales.ticket_type_index, sales.count)).collect::<Vec<_>>();
+  assert_eq!(sales, [(at(11, 0), 1, 1), (at(10, 0), 0, 2)]);
+
+  // Only sales after start_ts
+  let sales = postgres.read_primary_sales_count("e1".into(), 86400, DAY_START + 11 * 3600).await.unwrap();
+  let sales = sales.iter().map(|sales| (sales.timestamp, sales.ticket_type_index, sales.count)).collect::<Vec<_>>();
+  assert_eq!(sales, [(at(0, 0), 1, 1)]);
+}
+
+#[tokio::test]
+async fn revenue_is_summed_per_sale_type() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // The seeded CNTs have no purchase price
+  postgres.allocate_seats("e1".into(), 0, "bob".into(), 2).await.unwrap();
+
+  let revenue = postgres.read_revenue_per_sale_type("e1".into()).await.unwrap();
+  let revenue = revenue
+  .iter()
+  .map(|revenue| (revenue.sale_type.as_str(), revenue.tickets_sold, revenue.unpriced_tickets, revenue.gross_revenue))
+  .collect::<Vec<_>>();
+  assert_eq!(revenue, [("FixedPrice", 4, 2, 10000), ("Refundable", 1, 1, 0)]);
+
+  assert!(postgres.read_revenue_per_sale_type("e3".into()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sell_through_includes_unsold_ticket_types() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let sell_through = postgres.read_sell_through("e1".into()).await.unwrap();
+  let sell_through = sell_through
+  .iter()
+  .map(|sell_through| (sell_through.ticket_type_name.as_str(), sell_through.n_tickets, sell_through.sold, sell_through.sell_through))
+  .collect::<Vec<_>>();
+  assert_eq!(sell_through, [("GA", 10, 2, 20.0), ("VIP", 4, 1, 25.0)]);
+
+  let sell_through = postgres.read_sell_through("e3".into()).await.unwrap();
+  assert_eq!((sell_through.len(), sell_through[0].sold, sell_through[0].sell_through), (1, 0, 0.0));
+}
+
+#[tokio::test]
+async fn resale_volume_and_royalties_are_summed_per_interval() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.upsert_resale_policy(ResalePolicy {
+    event_id: "e1".into(),
+    royalty_bps: 1000,
+    ..Default::default()
+  })
+  .await
+  .unwrap();
+
+  let first = postgres.fill_listing("l1".into(), "c1".into(), "bob".into()).await.unwrap();
+  let second = postgres.fill_offer("o3".into(), "c1".into(), "org".into()).await.unwrap();
+
+  postgres.borrow_mut().batch_execute(&format!("
+    UPDATE trades SET created_at = '2030-01-01 10:10+00' WHERE trade_id = {};
+    UPDATE trades SET created_at = '2030-01-01 12:20+00' WHERE trade_id = {};
+  ", first.trade_id, second.trade_id))
+  .await
+  .unwrap();
+
+  let volume = postgres.read_resale_volume("e1".into(), 3600, DAY_START).await.unwrap();
+  let volume = volume.iter().map(|volume| (volume.timestamp, volume.count, volume.volume, volume.royalties)).collect::<Vec<_>>();
+  assert_eq!(volume, [(at(12, 0), 1, 5800, 580), (at(10, 0), 1, 6000, 600)]);
+
+  let volume = postgres.read_resale_volume("e1".into(), 86400, DAY_START).await.unwrap();
+  let volume = volume.iter().map(|volume| (volume.timestamp, volume.count, volume.volume, volume.royalties)).collect::<Vec<_>>();
+  assert_eq!(volume, [(at(0, 0), 2, 11800, 1180)]);
+
+  assert!(postgres.read_resale_volume("e2".into(), 3600, DAY_START).await.unwrap().is_empty());
+}
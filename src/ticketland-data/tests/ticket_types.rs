@@ -0,0 +1,95 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  connection::PostgresConnection,
+  error::Error,
+  models::{seat_range::SeatRange, ticket_type::NewTicketType},
+};
+
+/// The stored ticket type with another number of tickets
+async fn resized(postgres: &mut PostgresConnection, event_id: &str, ticket_type_index: i16, n_tickets: i32) -> NewTicketType {
+  let ticket_type = postgres.read_ticket_type(event_id.into(), ticket_type_index).await.unwrap();
+
+  NewTicketType {
+    event_id: ticket_type.event_id,
+    ticket_type_index,
+    ticket_type_name: ticket_type.ticket_type_name,
+    n_tickets,
+    sale_start_ts: ticket_type.sale_start_ts,
+    sale_end_ts: ticket_type.sale_end_ts,
+    sale_type: ticket_type.sale_type,
+  }
+}
+
+fn ranges(event_id: &str, ticket_type_index: i16, ranges: &[(i32, i32)]) -> Vec<SeatRange> {
+  ranges.iter().map(|(l, r)| SeatRange::new(event_id.into(), ticket_type_index, *l, *r)).collect()
+}
+
+fn assert_invalid(result: eyre::Result<()>) {
+  let error = result.unwrap_err();
+  assert!(matches!(error.downcast_ref::<Error>(), Some(Error::InvalidSeatRanges(_))), "{error}");
+}
+
+#[tokio::test]
+async fn replaces_seat_ranges_and_n_tickets() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // Storing the same ranges again is not an overlap
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(10, 12), (20, 22)])).await.unwrap();
+
+  // The VIP section is merged into one range with a seat less
+  let vip = resized(&mut postgres, "e1", 1, 3).await;
+  postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(10, 13)])).await.unwrap();
+
+  assert_eq!(postgres.read_ticket_type("e1".into(), 1).await.unwrap().n_tickets, 3);
+  assert_eq!(postgres.read_ticket_type_seat_ranges("e1".into(), 1).await.unwrap(), ranges("e1", 1, &[(10, 13)]));
+
+  // The seats freed by VIP can go to GA
+  let ga = resized(&mut postgres, "e1", 0, 18).await;
+  postgres.upsert_ticket_types(vec![ga], ranges("e1", 0, &[(0, 10), (13, 21)])).await.unwrap();
+
+  assert_eq!(postgres.read_ticket_type("e1".into(), 0).await.unwrap().n_tickets, 18);
+  assert_eq!(postgres.read_available_tickets("e1".into(), 0).await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn rejects_invalid_replacements() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // n_tickets does not match the new ranges
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  assert_invalid(postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(10, 13)])).await);
+
+  // Overlaps with GA
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  assert_invalid(postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(8, 12)])).await);
+
+  // c3 is seat 10 of VIP
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  assert_invalid(postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(11, 13), (20, 22)])).await);
+
+  // Nothing changed
+  assert_eq!(postgres.read_ticket_type("e1".into(), 1).await.unwrap().n_tickets, 4);
+  assert_eq!(postgres.read_ticket_type_seat_ranges("e1".into(), 1).await.unwrap(), ranges("e1", 1, &[(10, 12), (20, 22)]));
+}
+
+#[tokio::test]
+async fn only_active_holds_keep_their_seats() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.hold_seats("e1".into(), 1, "alice".into(), vec![21], 60, None).await.unwrap();
+
+  // Seat 21 is held
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  assert_invalid(postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(10, 12), (22, 24)])).await);
+
+  postgres.borrow_mut().batch_execute("UPDATE seat_holds SET expires_at = now() - interval '1 minute'").await.unwrap();
+
+  let vip = resized(&mut postgres, "e1", 1, 4).await;
+  postgres.upsert_ticket_types(vec![vip], ranges("e1", 1, &[(10, 12), (22, 24)])).await.unwrap();
+  assert_eq!(postgres.read_ticket_type_seat_ranges("e1".into(), 1).await.unwrap(), ranges("e1", 1, &[(10, 12), (22, 24)]));
+}
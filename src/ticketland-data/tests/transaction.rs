@@ -0,0 +1,62 @@
+mod common;
+
+use eyre::eyre;
+use ticketland_data::models::check_in::CheckInResult;
+
+#[tokio::test]
+async fn failing_callback_rolls_back() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let result = postgres.transaction(|tx| Box::pin(async move {
+    let attempt = tx.check_in_cnt("e1".into(), "c1".into(), "gate-a".into(), None).await?;
+    assert_eq!(attempt.result, CheckInResult::Admitted);
+    assert!(tx.has_attended("c1".into()).await?);
+
+    Err::<(), _>(eyre!("payment failed"))
+  }))
+  .await;
+
+  assert_eq!(result.unwrap_err().to_string(), "payment failed");
+  assert!(!postgres.has_attended("c1".into()).await.unwrap());
+  assert!(postgres.read_cnt_check_in_attempts("c1".into()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn successful_callback_commits() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  let result = postgres.transaction(|tx| Box::pin(async move {
+    Ok(tx.check_in_cnt("e1".into(), "c1".into(), "gate-a".into(), None).await?.result)
+  }))
+  .await;
+
+  assert_eq!(result.unwrap(), CheckInResult::Admitted);
+  assert!(postgres.has_attended("c1".into()).await.unwrap());
+  assert_eq!(postgres.read_cnt_check_in_attempts("c1".into()).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn failing_nested_transaction_only_rolls_back_itself() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.transaction(|tx| Box::pin(async move {
+    tx.check_in_cnt("e1".into(), "c1".into(), "gate-a".into(), None).await?;
+
+    let nested = tx.transaction(|tx| Box::pin(async move {
+      tx.check_in_cnt("e1".into(), "c3".into(), "gate-a".into(), None).await?;
+      Err::<(), _>(eyre!("scanner offline"))
+    }))
+    .await;
+    assert!(nested.is_err());
+
+    Ok(())
+  }))
+  .await
+  .unwrap();
+
+  assert!(postgres.has_attended("c1".into()).await.unwrap());
+  assert!(!postgres.has_attended("c3".into()).await.unwrap());
+}
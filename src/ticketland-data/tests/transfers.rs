@@ -0,0 +1,98 @@
+mod common;
+
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  error::Error,
+  models::{cnt_ownership_event::OwnershipEventKind, cnt_transfer::TransferStatus},
+};
+
+fn assert_error<T>(result: eyre::Result<T>, expected: Error) {
+  let Err(error) = result else {panic!("expected {expected}")};
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+#[tokio::test]
+async fn only_cnts_that_can_change_hands_are_transferred() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  // c2 is attended, c1 is listed as l1 and c1 is not bob's
+  assert_error(postgres.create_cnt_transfer("c2".into(), "alice".into(), Some("bob".into()), None).await, Error::CntAttended);
+  assert_error(postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("bob".into()), None).await, Error::CntListed);
+  assert_error(postgres.create_cnt_transfer("c1".into(), "bob".into(), Some("org".into()), None).await, Error::CntNotFound);
+
+  // c3 is being refunded
+  postgres.cancel_listing("bob".into(), "l2".into()).await.unwrap();
+  postgres.request_refund("c3".into(), "bob".into()).await.unwrap();
+  assert_error(postgres.create_cnt_transfer("c3".into(), "bob".into(), Some("alice".into()), None).await, Error::CntRefundPending);
+
+  postgres.cancel_listing("alice".into(), "l1".into()).await.unwrap();
+  assert_error(postgres.create_cnt_transfer("c1".into(), "alice".into(), None, Some(" ALICE@x.io".into())).await, Error::InvalidTransferRecipient);
+  postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("bob".into()), None).await.unwrap();
+  assert_error(postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("org".into()), None).await, Error::TransferAlreadyPending);
+}
+
+#[tokio::test]
+async fn accepted_transfers_move_the_cnt_and_are_recorded() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.cancel_listing("alice".into(), "l1".into()).await.unwrap();
+
+  let transfer = postgres.create_cnt_transfer("c1".into(), "alice".into(), None, Some(" Bob@x.io ".into())).await.unwrap();
+  assert_eq!(transfer.recipient_email.as_deref(), Some("bob@x.io"));
+  assert_eq!(postgres.read_incoming_cnt_transfers("bob".into()).await.unwrap().len(), 1);
+
+  assert_error(postgres.accept_cnt_transfer(transfer.transfer_id, "org".into()).await, Error::TransferNotFound);
+
+  let transfer = postgres.accept_cnt_transfer(transfer.transfer_id, "bob".into()).await.unwrap();
+  assert_eq!((transfer.status, transfer.recipient_account_id.as_deref()), (TransferStatus::Accepted, Some("bob")));
+  assert!(transfer.resolved_at.is_some());
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "bob");
+
+  let history = postgres.read_cnt_history("c1".into()).await.unwrap();
+  let last = history.last().unwrap();
+  assert_eq!(
+    (last.kind, last.from_account_id.as_deref(), last.to_account_id.as_str(), last.transfer_id),
+    (OwnershipEventKind::Transferred, Some("alice"), "bob", Some(transfer.transfer_id)),
+  );
+
+  assert_error(postgres.accept_cnt_transfer(transfer.transfer_id, "bob".into()).await, Error::TransferNotFound);
+  assert!(postgres.read_incoming_cnt_transfers("bob".into()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn transfers_are_not_accepted_once_the_cnt_is_listed() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  postgres.cancel_listing("alice".into(), "l1".into()).await.unwrap();
+  let transfer = postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("bob".into()), None).await.unwrap();
+  postgres.borrow_mut().batch_execute("UPDATE listings SET is_open = true WHERE listing_id = 'l1'").await.unwrap();
+
+  assert_error(postgres.accept_cnt_transfer(transfer.transfer_id, "bob".into()).await, Error::CntListed);
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "alice");
+}
+
+#[tokio::test]
+async fn declined_and_cancelled_transfers_leave_the_cnt_with_the_sender() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  postgres.cancel_listing("alice".into(), "l1".into()).await.unwrap();
+
+  let transfer = postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("bob".into()), None).await.unwrap();
+  assert_error(postgres.decline_cnt_transfer(transfer.transfer_id, "alice".into()).await, Error::TransferNotFound);
+
+  let declined = postgres.decline_cnt_transfer(transfer.transfer_id, "bob".into()).await.unwrap();
+  assert_eq!(declined.status, TransferStatus::Declined);
+  assert_error(postgres.cancel_cnt_transfer(transfer.transfer_id, "alice".into()).await, Error::TransferNotFound);
+
+  let transfer = postgres.create_cnt_transfer("c1".into(), "alice".into(), Some("bob".into()), None).await.unwrap();
+  assert_error(postgres.cancel_cnt_transfer(transfer.transfer_id, "bob".into()).await, Error::TransferNotFound);
+
+  let cancelled = postgres.cancel_cnt_transfer(transfer.transfer_id, "alice".into()).await.unwrap();
+  assert_eq!(cancelled.status, TransferStatus::Cancelled);
+  assert_error(postgres.accept_cnt_transfer(transfer.transfer_id, "bob".into()).await, Error::TransferNotFound);
+
+  assert!(postgres.read_outgoing_cnt_transfers("alice".into()).await.unwrap().is_empty());
+  assert_eq!(postgres.read_cnt("c1".into()).await.unwrap()[0].account_id, "alice");
+}
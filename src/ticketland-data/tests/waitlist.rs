@@ -0,0 +1,89 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel_async::SimpleAsyncConnection;
+use ticketland_data::{
+  connection::PostgresConnection,
+  error::Error,
+  models::{event::EventStatus, waitlist::WaitlistStatus},
+};
+
+fn assert_error<T: std::fmt::Debug>(result: eyre::Result<T>, expected: Error) {
+  let error = result.unwrap_err();
+  assert_eq!(error.downcast_ref::<Error>(), Some(&expected), "{error}");
+}
+
+/// Sells the VIP seats of e1 that are still free to org
+async fn sell_out_vip(postgres: &mut PostgresConnection) {
+  postgres.borrow_mut().batch_execute("
+    INSERT INTO cnts (cnt_sui_address, event_id, account_id, ticket_type_index, seat_name, seat_index, attended, draft) VALUES
+    ('c5', 'e1', 'org', 1, '11', 11, false, false),
+    ('c6', 'e1', 'org', 1, '20', 20, false, false),
+    ('c7', 'e1', 'org', 1, '21', 21, false, false);
+  ")
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn only_sold_out_ticket_types_of_events_on_sale_can_be_joined() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+
+  assert_error(postgres.join_waitlist("e1".into(), 1, "alice".into()).await, Error::NotSoldOut);
+  assert_error(postgres.join_waitlist("e1".into(), 0, "alice".into()).await, Error::NotSoldOut);
+
+  sell_out_vip(&mut postgres).await;
+  let position = postgres.join_waitlist("e1".into(), 1, "alice".into()).await.unwrap();
+  assert_eq!(position.position, 1);
+
+  postgres.update_event_status("e1".into(), EventStatus::Paused).await.unwrap();
+  assert_error(
+    postgres.join_waitlist("e1".into(), 1, "bob".into()).await,
+    Error::InvalidEventStatus("paused"),
+  );
+}
+
+#[tokio::test]
+async fn expired_offers_release_their_seat() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  sell_out_vip(&mut postgres).await;
+
+  postgres.join_waitlist("e1".into(), 1, "alice".into()).await.unwrap();
+  postgres.join_waitlist("e1".into(), 1, "bob".into()).await.unwrap();
+  postgres.borrow_mut().batch_execute("DELETE FROM cnts WHERE cnt_sui_address = 'c7'").await.unwrap();
+
+  let offer = postgres.offer_waitlist_seat("e1".into(), 1, 21, 60).await.unwrap().unwrap();
+  assert_eq!((offer.account_id.as_str(), offer.status), ("alice", WaitlistStatus::Offered));
+
+  let expired = postgres.expire_waitlist_offers(Utc::now().naive_utc() + Duration::hours(1)).await.unwrap();
+  assert_eq!(expired.len(), 1);
+  assert_eq!(expired[0].status, WaitlistStatus::Expired);
+
+  // Alice's hold would still be active if it had been left to expire on its own
+  let offer = postgres.offer_waitlist_seat("e1".into(), 1, 21, 60).await.unwrap().unwrap();
+  assert_eq!(offer.account_id, "bob");
+}
+
+#[tokio::test]
+async fn accounts_at_their_purchase_limit_are_skipped() {
+  let Some(db) = common::setup().await else {return};
+  let mut postgres = db.connection().await;
+  sell_out_vip(&mut postgres).await;
+
+  postgres.join_waitlist("e1".into(), 1, "alice".into()).await.unwrap();
+  postgres.join_waitlist("e1".into(), 1, "bob".into()).await.unwrap();
+  postgres.borrow_mut().batch_execute("DELETE FROM cnts WHERE cnt_sui_address IN ('c6', 'c7')").await.unwrap();
+
+  // Alice already owns c1 and c2
+  postgres.set_purchase_limit("e1".into(), None, Some(2)).await.unwrap();
+
+  let offer = postgres.offer_waitlist_seat("e1".into(), 1, 21, 60).await.unwrap().unwrap();
+  assert_eq!(offer.account_id, "bob");
+
+  // Alice keeps her place but nobody else is waiting
+  assert!(postgres.offer_waitlist_seat("e1".into(), 1, 20, 60).await.unwrap().is_none());
+  let position = postgres.read_waitlist_position("e1".into(), 1, "alice".into()).await.unwrap().unwrap();
+  assert_eq!((position.entry.status, position.position), (WaitlistStatus::Waiting, 1));
+}